@@ -0,0 +1,46 @@
+//! Compare [Command::roll_totals] against rolling with full history.
+//!
+//! Run with `cargo run --release --example roll_totals`.
+
+use std::time::{Duration, Instant};
+
+use dicey::{Command, Rollable};
+
+const ROLLS: usize = 100_000;
+
+/// Timings are the best of this many, alternating between the two ways of rolling.
+const ROUNDS: usize = 10;
+
+fn time(f: impl FnOnce()) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+fn main() {
+    for spec in [
+        "1d20 + 5",
+        "4d6 d1",
+        "10d6 e6 K8 + 4",
+        "(2d6 + 3) ^+ 4",
+        "8d10 t7 f1",
+    ] {
+        let command = Command::parse(spec).unwrap();
+        let rng = &mut rand::rng();
+        let (mut full, mut totals) = (Duration::MAX, Duration::MAX);
+        for _ in 0..ROUNDS {
+            full = full.min(time(|| {
+                for _ in 0..ROLLS {
+                    command.roll_with(rng).unwrap().total().unwrap();
+                }
+            }));
+            totals = totals.min(time(|| {
+                command.roll_totals(ROLLS, rng).unwrap();
+            }));
+        }
+        println!(
+            "{spec:>16}: full {full:>10.2?}, totals {totals:>10.2?} ({:.1}x)",
+            full.as_secs_f64() / totals.as_secs_f64()
+        );
+    }
+}
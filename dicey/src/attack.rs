@@ -0,0 +1,387 @@
+//! A d20 attack roll: to hit, then damage, with critical hits and misses.
+
+use std::fmt::Display;
+
+use crate::{
+    DiceRollSource, EvaluatedExpression, Expression, FancyFormat, NaturalRule, Number, Result,
+    Rollable, Verbosity,
+    expression::{format_bold, format_italic},
+};
+
+/// How the d20 for an attack is rolled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Advantage {
+    /// Roll one d20.
+    #[default]
+    Normal,
+    /// Roll two d20 and keep the highest.
+    Advantage,
+    /// Roll two d20 and keep the lowest.
+    Disadvantage,
+}
+
+impl Advantage {
+    /// Dice notation for the d20 roll.
+    pub fn notation(&self) -> &'static str {
+        match self {
+            Advantage::Normal => "1d20",
+            Advantage::Advantage => "2d20K1",
+            Advantage::Disadvantage => "2d20k1",
+        }
+    }
+
+    fn expression(&self) -> Expression {
+        Expression::parse(self.notation()).unwrap()
+    }
+}
+
+/// How a critical hit increases damage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum CritDamage {
+    /// Roll the damage dice twice.
+    #[default]
+    DoubleDice,
+    /// Roll the damage dice, and add their maximum possible result.
+    MaxDice,
+    /// Double the total damage, including fixed damage.
+    DoubleTotal,
+}
+
+/// Result of the to hit roll.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttackOutcome {
+    /// Natural 1: always misses.
+    CriticalMiss,
+    /// Did not reach the target armor class.
+    Miss,
+    /// Reached the target armor class, or no target armor class was provided.
+    Hit,
+    /// Natural roll in the crit range: always hits.
+    CriticalHit,
+}
+
+/// An attack roll: d20 plus modifier to hit, and damage dice plus fixed damage.
+#[derive(Clone, Debug)]
+pub struct AttackTemplate {
+    /// Added to the d20 roll to hit.
+    pub modifier: Expression,
+    /// Damage which is increased by critical hits.
+    pub damage_dice: Expression,
+    /// Damage which is only increased by [CritDamage::DoubleTotal].
+    pub damage_fixed: Expression,
+    /// Lowest natural d20 roll which is a critical hit.
+    pub crit_range: u32,
+    /// How critical hits increase damage.
+    pub crit_damage: CritDamage,
+    /// How the d20 is rolled.
+    advantage: Advantage,
+    /// If provided, rolls to hit below this miss.
+    pub target_ac: Option<i64>,
+    /// The d20 roll for `advantage`.
+    d20: Expression,
+}
+
+impl AttackTemplate {
+    /// An attack which crits on a natural 20, doubles damage dice on a crit, and has no target armor class.
+    pub fn new(modifier: Expression, damage_dice: Expression, damage_fixed: Expression) -> Self {
+        AttackTemplate {
+            modifier,
+            damage_dice,
+            damage_fixed,
+            crit_range: 20,
+            crit_damage: CritDamage::default(),
+            advantage: Advantage::default(),
+            target_ac: None,
+            d20: Advantage::default().expression(),
+        }
+    }
+
+    /// The same attack, rolling the d20 with `advantage`.
+    pub fn with_advantage(mut self, advantage: Advantage) -> Self {
+        self.advantage = advantage;
+        self.d20 = advantage.expression();
+        self
+    }
+
+    /// How the d20 is rolled.
+    pub fn advantage(&self) -> Advantage {
+        self.advantage
+    }
+}
+
+impl FancyFormat for AttackTemplate {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        let mut s = format!(
+            "{} + {} to hit for {} + {} damage",
+            self.advantage.notation(),
+            self.modifier.format(markdown, verbose),
+            self.damage_dice.format(markdown, verbose),
+            self.damage_fixed.format(markdown, verbose)
+        );
+        if let Some(ac) = self.target_ac {
+            s += &format!(" vs AC {ac}");
+        }
+        s
+    }
+}
+
+impl Display for AttackTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.format(false, Verbosity::Medium))
+    }
+}
+
+impl Rollable for AttackTemplate {
+    type Roll = Result<EvaluatedAttack>;
+
+    fn roll_with_source(&self, rng: &mut dyn DiceRollSource) -> Self::Roll {
+        let d20 = self.d20.roll_with_source(rng)?;
+        let modifier = self.modifier.roll_with_source(rng)?.total();
+        let to_hit = d20.total() + modifier;
+
+        let outcome = if NaturalRule::AnyMin.matches_expression(&*d20) {
+            AttackOutcome::CriticalMiss
+        } else if NaturalRule::AnyAtLeast(self.crit_range.into()).matches_expression(&*d20) {
+            AttackOutcome::CriticalHit
+        } else if self.target_ac.is_some_and(|ac| to_hit < Number::from(ac)) {
+            AttackOutcome::Miss
+        } else {
+            AttackOutcome::Hit
+        };
+
+        let damage = match outcome {
+            AttackOutcome::CriticalMiss | AttackOutcome::Miss => None,
+            AttackOutcome::Hit | AttackOutcome::CriticalHit => {
+                let dice = self.damage_dice.roll_with_source(rng)?;
+                let fixed = self.damage_fixed.roll_with_source(rng)?.total();
+                let crit = match (outcome, self.crit_damage) {
+                    (AttackOutcome::CriticalHit, CritDamage::DoubleDice) => {
+                        Some(self.damage_dice.roll_with_source(rng)?)
+                    }
+                    (AttackOutcome::CriticalHit, CritDamage::MaxDice) => {
+                        Some(self.damage_dice.roll_max()?)
+                    }
+                    _ => None,
+                };
+                Some(EvaluatedDamage { dice, crit, fixed })
+            }
+        };
+
+        Ok(EvaluatedAttack {
+            outcome,
+            d20,
+            modifier,
+            damage,
+            crit_damage: self.crit_damage,
+            target_ac: self.target_ac,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct EvaluatedDamage {
+    dice: Box<dyn EvaluatedExpression>,
+    /// Extra dice from a critical hit.
+    crit: Option<Box<dyn EvaluatedExpression>>,
+    fixed: Number,
+}
+
+/// Result of rolling an [AttackTemplate].
+#[derive(Debug)]
+pub struct EvaluatedAttack {
+    outcome: AttackOutcome,
+    d20: Box<dyn EvaluatedExpression>,
+    modifier: Number,
+    damage: Option<EvaluatedDamage>,
+    crit_damage: CritDamage,
+    target_ac: Option<i64>,
+}
+
+impl EvaluatedAttack {
+    /// If the attack hit, missed, or was a critical.
+    pub fn outcome(&self) -> AttackOutcome {
+        self.outcome
+    }
+
+    /// The d20 roll, before adding the modifier.
+    pub fn natural(&self) -> Number {
+        self.d20.total()
+    }
+
+    /// Total roll to hit.
+    pub fn to_hit(&self) -> Number {
+        self.d20.total() + self.modifier
+    }
+
+    /// Total damage, or [None] if the attack missed.
+    pub fn damage(&self) -> Option<Number> {
+        self.damage.as_ref().map(|damage| {
+            let total = damage.dice.total()
+                + damage
+                    .crit
+                    .as_ref()
+                    .map_or(Number::from(0), |crit| crit.total())
+                + damage.fixed;
+            match (self.outcome, self.crit_damage) {
+                (AttackOutcome::CriticalHit, CritDamage::DoubleTotal) => total * Number::from(2),
+                _ => total,
+            }
+        })
+    }
+}
+
+impl FancyFormat for EvaluatedAttack {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        let d20 = self.d20.format_history(markdown, verbose);
+        let to_hit = format!(
+            "{}: {} = {d20} + {}",
+            format_italic("To Hit", markdown),
+            format_bold(self.to_hit(), markdown),
+            self.modifier
+        );
+        let damage = self.damage.as_ref().map(|damage| {
+            let mut parts = vec![damage.dice.format_history(markdown, verbose)];
+            if let Some(crit) = &damage.crit {
+                parts.push(crit.format_history(markdown, verbose));
+            }
+            parts.push(format!("{}", damage.fixed));
+            let mut sum = parts.join(" + ");
+            if self.outcome == AttackOutcome::CriticalHit
+                && self.crit_damage == CritDamage::DoubleTotal
+            {
+                sum = format!("2\u{d7}({sum})");
+            }
+            format!(
+                "{}: {} = {sum}",
+                format_italic("Damage", markdown),
+                format_bold(self.damage().unwrap(), markdown)
+            )
+        });
+        let against = match self.target_ac {
+            Some(ac) => format!(" vs AC {ac}"),
+            None => String::new(),
+        };
+        match (self.outcome, damage) {
+            (AttackOutcome::CriticalMiss, _) => {
+                format!("{} {d20}", format_bold("Crit Miss", markdown))
+            }
+            (AttackOutcome::CriticalHit, Some(damage)) => {
+                format!("{} {d20}{against} {damage}", format_bold("Crit", markdown))
+            }
+            (AttackOutcome::Miss, _) => {
+                format!("{} {to_hit}{against}", format_bold("Miss", markdown))
+            }
+            (_, Some(damage)) => format!("{to_hit}{against} {damage}"),
+            (_, None) => unreachable!(),
+        }
+    }
+}
+
+impl Display for EvaluatedAttack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.format(false, Verbosity::Medium))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::IteratorDiceRollSource;
+
+    fn template() -> AttackTemplate {
+        AttackTemplate::new(
+            Expression::parse("5").unwrap(),
+            Expression::parse("1d8").unwrap(),
+            Expression::parse("3").unwrap(),
+        )
+    }
+
+    fn roll(template: &AttackTemplate, rolls: Vec<u64>) -> EvaluatedAttack {
+        template
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut rolls.into_iter(),
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn hit() {
+        let result = roll(&template(), vec![12, 6]);
+        assert_eq!(result.outcome(), AttackOutcome::Hit);
+        assert_eq!(result.to_hit(), 17.0);
+        assert_eq!(result.damage().unwrap(), 9.0);
+        assert_eq!(
+            result.format(true, Verbosity::Medium),
+            "*To Hit*: **17** = \\[12\\] + 5 *Damage*: **9** = \\[6\\] + 3"
+        );
+    }
+
+    #[test]
+    fn miss() {
+        let mut template = template();
+        template.target_ac = Some(18);
+        let result = roll(&template, vec![12]);
+        assert_eq!(result.outcome(), AttackOutcome::Miss);
+        assert_eq!(result.damage(), None);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "Miss To Hit: 17 = [12] + 5 vs AC 18"
+        );
+    }
+
+    #[test]
+    fn critical_miss() {
+        let result = roll(&template(), vec![1]);
+        assert_eq!(result.outcome(), AttackOutcome::CriticalMiss);
+        assert_eq!(result.damage(), None);
+        assert_eq!(
+            result.format(true, Verbosity::Medium),
+            "**Crit Miss** \\[1\\]"
+        );
+    }
+
+    #[test]
+    fn critical_hit() {
+        let mut template = template();
+        template.target_ac = Some(30);
+        template.crit_range = 19;
+        let result = roll(&template, vec![19, 2, 5]);
+        assert_eq!(result.outcome(), AttackOutcome::CriticalHit);
+        assert_eq!(result.damage().unwrap(), 10.0);
+        assert_eq!(
+            result.format(true, Verbosity::Medium),
+            "**Crit** \\[19\\] vs AC 30 *Damage*: **10** = \\[2\\] + \\[5\\] + 3"
+        );
+
+        template.crit_damage = CritDamage::MaxDice;
+        let result = roll(&template, vec![20, 2]);
+        assert_eq!(result.damage().unwrap(), 13.0);
+
+        // Exploding dice are maximized without exploding
+        let mut exploding = template.clone();
+        exploding.damage_dice = Expression::parse("2d6!").unwrap();
+        let result = roll(&exploding, vec![20, 6, 1, 2]);
+        assert_eq!(result.damage().unwrap(), 24.0);
+
+        template.crit_damage = CritDamage::DoubleTotal;
+        let result = roll(&template, vec![20, 2]);
+        assert_eq!(result.damage().unwrap(), 10.0);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "Crit [20] vs AC 30 Damage: 10 = 2\u{d7}([2] + 3)"
+        );
+    }
+
+    #[test]
+    fn advantage() {
+        let template = template().with_advantage(Advantage::Advantage);
+        let result = roll(&template, vec![3, 20, 4, 4]);
+        assert_eq!(result.outcome(), AttackOutcome::CriticalHit);
+        assert_eq!(result.natural(), 20.0);
+
+        let template = template.with_advantage(Advantage::Disadvantage);
+        let result = roll(&template, vec![3, 20, 4]);
+        assert_eq!(result.outcome(), AttackOutcome::Hit);
+        assert_eq!(result.to_hit(), 8.0);
+    }
+}
@@ -0,0 +1,403 @@
+//! Bags of tokens, pulled at random like chaos bags and chit pulls.
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    DiceRollSource, EvaluatedExpression, Expression, FancyFormat, KeptDie, Number, Result,
+    Rollable, Verbosity,
+    expression::{ExpressionResult, ExpressionRollable, format_italic},
+};
+
+/// Tokens of one kind in a [Bag].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Token {
+    /// Name of the token, like "Elder Sign" or "-1".
+    pub name: String,
+    /// How many of this token are in the bag.
+    pub count: u32,
+    /// Dice expression evaluated each time this token is pulled, like "-1" or "0 - 1d4".
+    pub effect: Option<String>,
+}
+
+/// A bag of named tokens.
+///
+/// Tokens can be pulled and returned ([Bag::pull]), or pulled and removed ([Bag::take]).
+/// The state can be serialized, so a bag can be kept between sessions.
+///
+/// In commands, a bag stored in a variable with [Expression::bag] is pulled from with `pull($bag)`, or taken from with `take($bag)`.
+/// The value of a pull is the total of the token's effect, or 0 if it has none.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bag {
+    tokens: Vec<Token>,
+}
+
+impl Bag {
+    /// Parse a bag from a list of tokens separated by commas or new lines, like `3x Elder Sign, 5x -1 = -1, Skull = 0 - 1d4`.
+    ///
+    /// Each entry is an optional count (like `3x`, defaulting to 1), a name, and an optional effect after `=`.
+    pub fn parse(s: &str) -> Result<Bag> {
+        let mut bag = Bag::default();
+        for entry in s.split([',', '\n']) {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            let (entry, effect) = match entry.split_once('=') {
+                Some((entry, effect)) => (entry.trim(), Some(effect.trim())),
+                None => (entry, None),
+            };
+            let (count, name) = match entry.split_once(char::is_whitespace) {
+                Some((count, name))
+                    if let Some(count) = count
+                        .strip_suffix(['x', 'X'])
+                        .and_then(|c| c.parse::<u32>().ok()) =>
+                {
+                    (count, name.trim())
+                }
+                _ => (1, entry),
+            };
+            bag.add(name, count, effect)?;
+        }
+        Ok(bag)
+    }
+
+    /// All kinds of tokens, including ones with a count of 0.
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// Number of tokens called `name`.
+    pub fn count(&self, name: &str) -> u32 {
+        self.find(name).map_or(0, |token| token.count)
+    }
+
+    /// Total number of tokens in the bag.
+    pub fn len(&self) -> u64 {
+        self.tokens.iter().map(|token| u64::from(token.count)).sum()
+    }
+
+    /// True if there are no tokens to pull.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add `count` tokens called `name`.
+    ///
+    /// If there are already tokens with this name, `effect` replaces their effect unless it is [None].
+    pub fn add(&mut self, name: &str, count: u32, effect: Option<&str>) -> Result<()> {
+        if name.is_empty() {
+            return Err("Token name must not be empty".into());
+        }
+        if let Some(effect) = effect {
+            Expression::parse(effect)?;
+        }
+        let effect = effect.map(str::to_string);
+        match self.tokens.iter_mut().find(|token| token.name == name) {
+            Some(token) => {
+                token.count = token
+                    .count
+                    .checked_add(count)
+                    .ok_or_else(|| format!("Too many \"{name}\" tokens in the bag"))?;
+                if effect.is_some() {
+                    token.effect = effect;
+                }
+            }
+            None => self.tokens.push(Token {
+                name: name.to_string(),
+                count,
+                effect,
+            }),
+        }
+        Ok(())
+    }
+
+    /// Remove `count` tokens called `name`.
+    pub fn remove(&mut self, name: &str, count: u32) -> Result<()> {
+        let token = self
+            .tokens
+            .iter_mut()
+            .find(|token| token.name == name)
+            .filter(|token| token.count >= count)
+            .ok_or_else(|| format!("Cannot remove {count} \"{name}\" from the bag"))?;
+        token.count -= count;
+        Ok(())
+    }
+
+    /// Pull a random token and put it back.
+    pub fn pull(&self, rng: &mut dyn DiceRollSource) -> Result<EvaluatedPull> {
+        let index = self.choose(rng)?;
+        self.evaluate(index, rng)
+    }
+
+    /// Pull a random token and leave it out of the bag.
+    pub fn take(&mut self, rng: &mut dyn DiceRollSource) -> Result<EvaluatedPull> {
+        let index = self.choose(rng)?;
+        self.tokens[index].count -= 1;
+        self.evaluate(index, rng)
+    }
+
+    fn find(&self, name: &str) -> Option<&Token> {
+        self.tokens.iter().find(|token| token.name == name)
+    }
+
+    /// Index of a random token, weighted by count.
+    fn choose(&self, rng: &mut dyn DiceRollSource) -> Result<usize> {
+        if self.is_empty() {
+            return Err("The bag is empty".into());
+        }
+        let mut roll = rng.roll_single_die(self.len());
+        for (index, token) in self.tokens.iter().enumerate() {
+            if roll <= token.count.into() {
+                return Ok(index);
+            }
+            roll -= u64::from(token.count);
+        }
+        unreachable!()
+    }
+
+    fn evaluate(&self, index: usize, rng: &mut dyn DiceRollSource) -> Result<EvaluatedPull> {
+        let token = &self.tokens[index];
+        Ok(EvaluatedPull {
+            token: token.name.clone(),
+            effect: match &token.effect {
+                Some(effect) => Some(Expression::parse(effect)?.roll_with_source(rng)?),
+                None => None,
+            },
+        })
+    }
+}
+
+impl FancyFormat for Bag {
+    fn format(&self, _markdown: bool, _verbose: Verbosity) -> String {
+        self.tokens
+            .iter()
+            .filter(|token| token.count > 0)
+            .map(|token| {
+                let effect = match &token.effect {
+                    Some(effect) => format!(" = {effect}"),
+                    None => "".to_string(),
+                };
+                format!("{}x {}{effect}", token.count, token.name)
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// A token pulled from a [Bag].
+#[derive(Debug)]
+pub struct EvaluatedPull {
+    token: String,
+    effect: Option<Box<dyn EvaluatedExpression>>,
+}
+
+impl EvaluatedPull {
+    /// Name of the token.
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+}
+
+impl EvaluatedExpression for EvaluatedPull {
+    fn total(&self) -> Number {
+        self.effect
+            .as_ref()
+            .map_or(Number::from(0), |effect| effect.total())
+    }
+
+    fn format_history(&self, markdown: bool, verbose: Verbosity) -> String {
+        let token = format_italic(&self.token, markdown);
+        match &self.effect {
+            Some(effect) => format!("{token} ({})", effect.format_history(markdown, verbose)),
+            None => token,
+        }
+    }
+
+    fn dice(&self) -> Vec<KeptDie> {
+        self.effect.as_ref().map_or(vec![], |effect| effect.dice())
+    }
+
+    fn outputs(&self) -> Vec<(String, Number)> {
+        self.effect
+            .as_ref()
+            .map_or(vec![], |effect| effect.outputs())
+    }
+
+    fn tags(&self) -> Vec<(String, Number)> {
+        self.effect.as_ref().map_or(vec![], |effect| effect.tags())
+    }
+}
+
+impl Expression {
+    /// An expression holding a bag, for use as a variable which commands can `pull` or `take` from.
+    ///
+    /// Pulls in commands update the shared bag.
+    pub fn bag(bag: Rc<RefCell<Bag>>) -> Expression {
+        Expression::new(BagExpression(bag))
+    }
+}
+
+/// A bag stored in a variable. It can not be rolled itself.
+#[derive(Debug)]
+pub(crate) struct BagExpression(pub(crate) Rc<RefCell<Bag>>);
+
+impl FancyFormat for BagExpression {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        format!("bag({})", self.0.borrow().format(markdown, verbose))
+    }
+}
+
+impl ExpressionRollable for BagExpression {
+    fn expression_roll(&self, _rng: &mut dyn DiceRollSource) -> ExpressionResult {
+        Err("Use pull($bag) or take($bag) to draw from a bag".into())
+    }
+
+    fn roll_total(&self, rng: &mut dyn DiceRollSource) -> Result<Number> {
+        self.expression_roll(rng).map(|result| result.total())
+    }
+
+    fn bag(&self) -> Option<&Rc<RefCell<Bag>>> {
+        Some(&self.0)
+    }
+}
+
+/// `pull($bag)` or `take($bag)`.
+#[derive(Debug)]
+pub(crate) struct BagPull {
+    pub(crate) identifier: String,
+    pub(crate) bag: Rc<RefCell<Bag>>,
+    /// Take the token out of the bag, instead of returning it.
+    pub(crate) remove: bool,
+}
+
+impl FancyFormat for BagPull {
+    fn format(&self, _markdown: bool, _verbose: Verbosity) -> String {
+        let op = if self.remove { "take" } else { "pull" };
+        format!("{op}(${})", self.identifier)
+    }
+}
+
+impl ExpressionRollable for BagPull {
+    fn expression_roll(&self, rng: &mut dyn DiceRollSource) -> ExpressionResult {
+        let pull = if self.remove {
+            self.bag.borrow_mut().take(rng)?
+        } else {
+            self.bag.borrow().pull(rng)?
+        };
+        Ok(Box::new(pull))
+    }
+
+    fn roll_total(&self, rng: &mut dyn DiceRollSource) -> Result<Number> {
+        self.expression_roll(rng).map(|result| result.total())
+    }
+}
+
+/// Parse `pull($identifier)` or `take($identifier)`.
+pub(crate) fn parse_pull(
+    identifier: &str,
+    remove: bool,
+    variables: &HashMap<String, Expression>,
+) -> Result<Expression> {
+    let bag = variables
+        .get(identifier)
+        .and_then(|expression| expression.as_bag())
+        .ok_or_else(|| format!("\"{identifier}\" is not a bag"))?;
+    Ok(Expression::new(BagPull {
+        identifier: identifier.to_string(),
+        bag: bag.clone(),
+        remove,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Command, RollError, tests::IteratorDiceRollSource};
+
+    fn chaos_bag() -> Bag {
+        Bag::parse("3x Elder Sign = 1, 5x -1 = -1, Skull = 0 - 1d4\nTentacle").unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        let bag = chaos_bag();
+        assert_eq!(bag.len(), 10);
+        assert_eq!(bag.count("-1"), 5);
+        assert_eq!(bag.count("Tentacle"), 1);
+        assert_eq!(
+            bag.format(false, Verbosity::Medium),
+            "3x Elder Sign = 1, 5x -1 = -1, 1x Skull = 0 - 1d4, 1x Tentacle"
+        );
+        assert!(Bag::parse("Skull = 1d").is_err());
+        assert_eq!(
+            Bag::parse("4294967295x a, a").unwrap_err(),
+            RollError::ParamError("Too many \"a\" tokens in the bag".into())
+        );
+        assert_eq!(Bag::parse("4294967295x a, b").unwrap().len(), 4294967296);
+    }
+
+    #[test]
+    fn pull_and_take() {
+        let mut bag = chaos_bag();
+        let pull = bag
+            .pull(&mut IteratorDiceRollSource {
+                iterator: &mut [9, 3].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(pull.token(), "Skull");
+        assert_eq!(pull.total(), -3);
+        assert_eq!(bag.len(), 10);
+
+        let pull = bag
+            .take(&mut IteratorDiceRollSource {
+                iterator: &mut [10].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(pull.format(true, Verbosity::Medium), "*Tentacle* = **0**");
+        assert_eq!(bag.len(), 9);
+        assert_eq!(bag.count("Tentacle"), 0);
+
+        bag.remove("Elder Sign", 3).unwrap();
+        assert!(bag.remove("Elder Sign", 1).is_err());
+        bag.add("Cultist", 2, Some("-2")).unwrap();
+        assert_eq!(bag.len(), 8);
+    }
+
+    #[test]
+    fn command() {
+        let bag = Rc::new(RefCell::new(Bag::parse("2x Blank, Skull = -2").unwrap()));
+        let mut variables = HashMap::default();
+        variables.insert("chaos_bag".to_string(), Expression::bag(bag.clone()));
+        let command = Command::parse_with_variables("1d6 + take($chaos_bag)", &variables).unwrap();
+        assert_eq!(format!("{command}"), "1d6 + take($chaos_bag)");
+        let result = command
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [4, 3].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "[4] + Skull (-2) = 2"
+        );
+        assert_eq!(bag.borrow().count("Skull"), 0);
+
+        assert!(
+            Command::parse_with_variables("$chaos_bag", &variables)
+                .unwrap()
+                .roll()
+                .is_err()
+        );
+        variables.insert("x".to_string(), Expression::parse("1").unwrap());
+        assert!(Command::parse_with_variables("pull($x)", &variables).is_err());
+    }
+
+    #[test]
+    fn serialize() {
+        let bag = chaos_bag();
+        let json = serde_json::to_string(&bag).unwrap();
+        let restored: Bag = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, bag);
+    }
+}
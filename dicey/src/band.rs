@@ -0,0 +1,177 @@
+//! Labels for ranges of totals, like `2d6+2 => {..6: Miss, 7..9: Weak hit, 10..: Strong hit}`.
+
+use std::fmt::Display;
+
+use pest::iterators::Pairs;
+
+use crate::{Number, Result, Rounding, parser::Rule};
+
+/// A label for totals from `low` to `high`, either of which can be open.
+#[derive(Clone, Debug)]
+struct Band {
+    low: Option<i64>,
+    high: Option<i64>,
+    label: String,
+}
+
+impl Band {
+    fn contains(&self, total: i64) -> bool {
+        self.low.is_none_or(|low| total >= low) && self.high.is_none_or(|high| total <= high)
+    }
+
+    fn overlaps(&self, other: &Band) -> bool {
+        self.low
+            .is_none_or(|low| other.high.is_none_or(|high| low <= high))
+            && other
+                .low
+                .is_none_or(|low| self.high.is_none_or(|high| low <= high))
+    }
+}
+
+impl Display for Band {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.low, self.high) {
+            (Some(low), Some(high)) if low == high => write!(f, "{low}")?,
+            (low, high) => {
+                if let Some(low) = low {
+                    write!(f, "{low}")?;
+                }
+                f.write_str("..")?;
+                if let Some(high) = high {
+                    write!(f, "{high}")?;
+                }
+            }
+        }
+        write!(f, ": {}", self.label)
+    }
+}
+
+/// Bands a command's totals are labelled with.
+#[derive(Clone, Debug)]
+pub(crate) struct Bands(Vec<Band>);
+
+impl Bands {
+    /// The label of the band `total` (rounded down) falls in, if any.
+    pub(crate) fn label(&self, total: Number) -> Option<String> {
+        let total = total.to_integer(Rounding::Down);
+        self.0
+            .iter()
+            .find(|band| band.contains(total))
+            .map(|band| band.label.clone())
+    }
+}
+
+impl Display for Bands {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bands: Vec<String> = self.0.iter().map(|band| band.to_string()).collect();
+        write!(f, "=> {{{}}}", bands.join(", "))
+    }
+}
+
+/// Parse the bands in `=> {..6: Miss, 7..9: Weak hit}`.
+pub(crate) fn parse_bands(pairs: Pairs<Rule>) -> Result<Bands> {
+    let mut bands: Vec<Band> = vec![];
+    for pair in pairs {
+        let mut inner = pair.into_inner();
+        let (mut low, mut high, mut to) = (None, None, false);
+        for bound in inner.next().unwrap().into_inner() {
+            match bound.as_rule() {
+                Rule::band_to => to = true,
+                _ => {
+                    let value = bound.as_str().replace(' ', "").parse::<i64>()?;
+                    if to {
+                        high = Some(value);
+                    } else {
+                        low = Some(value);
+                    }
+                }
+            }
+        }
+        if !to {
+            high = low;
+        }
+        let band = Band {
+            low,
+            high,
+            label: inner.next().unwrap().as_str().trim().to_string(),
+        };
+        if let (Some(low), Some(high)) = (low, high)
+            && low > high
+        {
+            return Err(format!("Empty band \"{band}\"").into());
+        }
+        if let Some(other) = bands.iter().find(|other| other.overlaps(&band)) {
+            return Err(format!("Band \"{band}\" overlaps \"{other}\"").into());
+        }
+        bands.push(band);
+    }
+    Ok(Bands(bands))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Command, FancyFormat, RollError, Rollable, Verbosity, tests::IteratorDiceRollSource,
+    };
+
+    #[test]
+    fn labels() {
+        let command =
+            Command::parse("2d6+2 => {..6: Miss, 7..9: Weak hit, 10..: Strong hit}").unwrap();
+        let roll = |rolls: Vec<u64>| {
+            command
+                .roll_with_source(&mut IteratorDiceRollSource {
+                    iterator: &mut rolls.into_iter(),
+                })
+                .unwrap()
+        };
+        assert_eq!(roll(vec![1, 3]).label(), Some("Miss"));
+        assert_eq!(roll(vec![3, 4]).label(), Some("Weak hit"));
+        let result = roll(vec![6, 5]);
+        assert_eq!(result.label(), Some("Strong hit"));
+        assert_eq!(
+            result.format(true, Verbosity::Medium),
+            "\\[6, 5\\] + 2 = **13** 🡲 *Strong hit*"
+        );
+        assert_eq!(
+            format!("{command}"),
+            "2d6 + 2 => {..6: Miss, 7..9: Weak hit, 10..: Strong hit}"
+        );
+    }
+
+    #[test]
+    fn repeated() {
+        // Hit locations, with a gap left unlabelled
+        let command =
+            Command::parse("(1d10) ^ 3 => {1: Head, 2..4: Arms, 6..10: Body}: locations").unwrap();
+        let result = command
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [1, 5, 7].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(result.label(), None);
+        assert_eq!(result.result_label(0), Some("Head"));
+        assert_eq!(result.result_label(1), None);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "([1] = 1 🡲 Head) ([5] = 5) ([7] = 7 🡲 Body) : locations"
+        );
+        assert_eq!(
+            format!("{command}"),
+            "1d10 ^ 3 => {1: Head, 2..4: Arms, 6..10: Body} : locations"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Command::parse("2d6 => {..7: Miss, 7..: Hit}").unwrap_err(),
+            RollError::ParamError("Band \"7..: Hit\" overlaps \"..7: Miss\"".into())
+        );
+        assert_eq!(
+            Command::parse("2d6 => {9..7: Miss}").unwrap_err(),
+            RollError::ParamError("Empty band \"9..7: Miss\"".into())
+        );
+        assert!(Command::parse("2d6 => {}").is_err());
+    }
+}
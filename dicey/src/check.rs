@@ -0,0 +1,280 @@
+//! Checks of a command's total against a difficulty, like `1d20+7 >= 15`.
+
+use std::fmt::Display;
+
+use crate::{
+    DiceRollSource, EvaluatedExpression, Expression, FancyFormat, Number, Result, Rollable,
+    Verbosity, expression::format_italic,
+};
+
+/// How the margin of a check maps to a [Degree].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Degrees {
+    /// Success when the total meets the difficulty, failure otherwise.
+    #[default]
+    PassFail,
+    /// Pathfinder 2e: critical success (or failure) when 10 or more over (or under) the difficulty,
+    /// with a natural 20 (or 1) on the d20 one degree better (or worse).
+    Pf2e,
+    /// Fate: the margin is shifts, tying at 0 and succeeding with style at 3 or more.
+    Fate,
+}
+
+impl Display for Degrees {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Degrees::PassFail => Ok(()),
+            Degrees::Pf2e => f.write_str(" degrees=pf2e"),
+            Degrees::Fate => f.write_str(" degrees=fate"),
+        }
+    }
+}
+
+/// Result of a check, from worst to best.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Degree {
+    /// Only with [Degrees::Pf2e].
+    CriticalFailure,
+    /// Missed the difficulty.
+    Failure,
+    /// Exactly met the difficulty, only with [Degrees::Fate].
+    Tie,
+    /// Met the difficulty.
+    Success,
+    /// Succeeded by a lot: with [Degrees::Fate], this is succeeding with style.
+    CriticalSuccess,
+}
+
+impl Degree {
+    fn shift(self, by: i8) -> Degree {
+        let degrees = [
+            Degree::CriticalFailure,
+            Degree::Failure,
+            Degree::Success,
+            Degree::CriticalSuccess,
+        ];
+        let index = degrees.iter().position(|d| *d == self).unwrap_or(1) as i8;
+        degrees[(index + by).clamp(0, 3) as usize]
+    }
+}
+
+/// The difficulty a command's expression is checked against.
+#[derive(Debug)]
+pub(crate) struct Check {
+    pub(crate) dc: Expression,
+    pub(crate) degrees: Degrees,
+}
+
+impl FancyFormat for Check {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        format!(">= {}{}", self.dc.format(markdown, verbose), self.degrees)
+    }
+}
+
+impl Check {
+    /// Roll the difficulty and check `result` against it.
+    pub(crate) fn resolve(
+        &self,
+        result: &dyn EvaluatedExpression,
+        rng: &mut dyn DiceRollSource,
+    ) -> Result<EvaluatedCheck> {
+        let dc = self.dc.roll_with_source(rng)?;
+        let natural = result
+            .dice()
+            .into_iter()
+            .find(|d| d.min == 1 && d.max == 20)
+            .map(|d| d.value);
+        Ok(EvaluatedCheck {
+            margin: result.total() - dc.total(),
+            dc,
+            degrees: self.degrees,
+            natural,
+        })
+    }
+}
+
+/// Result of checking a total against a difficulty.
+#[derive(Debug)]
+pub struct EvaluatedCheck {
+    dc: Box<dyn EvaluatedExpression>,
+    margin: Number,
+    degrees: Degrees,
+    /// The kept d20, if there was one.
+    natural: Option<i64>,
+}
+
+impl EvaluatedCheck {
+    /// The difficulty.
+    pub fn dc(&self) -> Number {
+        self.dc.total()
+    }
+
+    /// Total minus difficulty: shifts, for Fate.
+    pub fn margin(&self) -> Number {
+        self.margin
+    }
+
+    /// If the total met the difficulty, including a Fate tie.
+    pub fn passed(&self) -> bool {
+        self.margin >= Number::from(0)
+    }
+
+    /// How well the check went.
+    pub fn degree(&self) -> Degree {
+        let margin = self.margin;
+        match self.degrees {
+            Degrees::PassFail if self.passed() => Degree::Success,
+            Degrees::PassFail => Degree::Failure,
+            Degrees::Pf2e => {
+                let degree = if margin >= Number::from(10) {
+                    Degree::CriticalSuccess
+                } else if self.passed() {
+                    Degree::Success
+                } else if margin <= Number::from(-10) {
+                    Degree::CriticalFailure
+                } else {
+                    Degree::Failure
+                };
+                match self.natural {
+                    Some(20) => degree.shift(1),
+                    Some(1) => degree.shift(-1),
+                    _ => degree,
+                }
+            }
+            Degrees::Fate => {
+                if margin >= Number::from(3) {
+                    Degree::CriticalSuccess
+                } else if margin > Number::from(0) {
+                    Degree::Success
+                } else if self.passed() {
+                    Degree::Tie
+                } else {
+                    Degree::Failure
+                }
+            }
+        }
+    }
+
+    /// Like "Critical success", or "Success with style" for Fate.
+    pub fn label(&self) -> &'static str {
+        match (self.degree(), self.degrees) {
+            (Degree::CriticalFailure, _) => "Critical failure",
+            (Degree::Failure, _) => "Failure",
+            (Degree::Tie, _) => "Tie",
+            (Degree::Success, _) => "Success",
+            (Degree::CriticalSuccess, Degrees::Fate) => "Success with style",
+            (Degree::CriticalSuccess, _) => "Critical success",
+        }
+    }
+}
+
+impl FancyFormat for EvaluatedCheck {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        let dc = if self.dc.dice().is_empty() {
+            self.dc.total().to_string()
+        } else {
+            format!("({})", self.dc.format(markdown, verbose))
+        };
+        let label = format_italic(self.label(), markdown);
+        let result = match self.degrees {
+            Degrees::Fate => format!("{label}, {} shifts", self.margin),
+            _ => {
+                let by = if self.passed() {
+                    self.margin
+                } else {
+                    -self.margin
+                };
+                format!("{label} by {by}")
+            }
+        };
+        let natural = match (self.degrees, self.natural) {
+            (Degrees::Pf2e, Some(n @ (1 | 20))) => format!(" (natural {n})"),
+            _ => String::new(),
+        };
+        format!("vs DC {dc} 🡲 {result}{natural}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Command, EvaluatedCommand, tests::IteratorDiceRollSource};
+
+    fn roll(command: &str, rolls: Vec<u64>) -> EvaluatedCommand {
+        Command::parse(command)
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut rolls.into_iter(),
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn pass_fail() {
+        let result = roll("1d20+7 >= 15", vec![12]);
+        let check = result.check().unwrap();
+        assert!(check.passed());
+        assert_eq!(check.margin(), 4);
+        assert_eq!(result.total(), Some(19.into()));
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "[12] + 7 = 19 vs DC 15 🡲 Success by 4"
+        );
+        let result = roll("check(1d20+7, dc=10+1d10)", vec![2, 6]);
+        assert_eq!(result.check().unwrap().degree(), Degree::Failure);
+        assert_eq!(
+            result.format(true, Verbosity::Medium),
+            "\\[2\\] + 7 = **9** vs DC (10 + \\[6\\] = **16**) 🡲 *Failure* by 7"
+        );
+    }
+
+    #[test]
+    fn pf2e() {
+        let degree = |rolls: Vec<u64>| {
+            roll("check(1d20+7, dc=20, degrees=pf2e)", rolls)
+                .check()
+                .unwrap()
+                .degree()
+        };
+        assert_eq!(degree(vec![3]), Degree::CriticalFailure);
+        assert_eq!(degree(vec![4]), Degree::Failure);
+        assert_eq!(degree(vec![13]), Degree::Success);
+        assert_eq!(degree(vec![19]), Degree::Success);
+        // 27 would be a success, and 8 a failure, but naturals shift them
+        assert_eq!(degree(vec![20]), Degree::CriticalSuccess);
+        assert_eq!(degree(vec![1]), Degree::CriticalFailure);
+        assert_eq!(
+            roll("1d20+7 >= 20 degrees=pf2e", vec![20]).format(false, Verbosity::Medium),
+            "[20] + 7 = 27 vs DC 20 🡲 Critical success by 7 (natural 20)"
+        );
+    }
+
+    #[test]
+    fn fate() {
+        let result = roll("4dF+2 >= 1 degrees=fate", vec![3, 1, 2, 3]);
+        assert_eq!(result.check().unwrap().degree(), Degree::Success);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "[(+), (-), ( ), (+)] + 2 = 3 vs DC 1 🡲 Success, 2 shifts"
+        );
+        let result = roll("4dF+2 >= 3 degrees=fate", vec![2, 2, 2, 2]);
+        assert_eq!(result.check().unwrap().label(), "Failure");
+        let result = roll("4dF >= 0 degrees=fate", vec![2, 2, 2, 2]);
+        assert_eq!(result.check().unwrap().degree(), Degree::Tie);
+        assert!(result.check().unwrap().passed());
+        let result = roll("4dF + 4 >= 1 degrees=fate", vec![2, 2, 2, 2]);
+        assert_eq!(result.check().unwrap().label(), "Success with style");
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            format!(
+                "{}",
+                Command::parse("check(1d20+7, dc=15, degrees=pf2e): stealth").unwrap()
+            ),
+            "1d20 + 7 >= 15 degrees=pf2e : stealth"
+        );
+        assert!(Command::parse("(1d20) ^ 2 >= 15").is_err());
+    }
+}
@@ -1,11 +1,20 @@
 use super::{EvaluatedExpression, Expression};
 use crate::{
-    DiceRollSource, Result, Rollable, Verbosity,
+    DiceRollSource, NaturalRule, Number, Result, RngDiceRollSource, Rollable, Rounding, Verbosity,
+    band::{Bands, parse_bands},
+    check::{Check, Degrees, EvaluatedCheck},
+    compiled::{DynDiceRollSource, Program, ProgramRollSource},
+    contest::{Contest, EvaluatedContest, TieBreak},
     dice_expression::limit_dice,
-    expression::{FancyFormat, format_bold, parse_expression},
+    expression::{
+        FancyFormat, format_bold, format_italic, format_outputs, merge_outputs, parse_expression,
+    },
+    keep_or_drop::KeepOrDrop,
     parser::{RollParser, Rule},
+    tag::{Resistances, Subtotal, format_subtotals},
 };
 use pest::{Parser, iterators::Pair};
+use rand::Rng;
 use std::{collections::HashMap, fmt::Display};
 
 /// Parse a single (non-repeated) dice expression.
@@ -31,7 +40,12 @@ pub(crate) fn parse_single_command(
     Ok(Command {
         expression,
         repeat: None,
+        contest: None,
+        check: None,
+        bands: None,
+        resistances: Resistances::default(),
         reason,
+        flags: vec![],
     })
 }
 
@@ -40,7 +54,17 @@ pub(crate) fn parse_single_command(
 pub struct Command {
     expression: Expression,
     repeat: Option<RepeatedCommand>,
+    /// Other expressions rolled against `expression`.
+    contest: Option<Contest>,
+    /// Difficulty the total is checked against.
+    check: Option<Check>,
+    /// Labels for ranges of totals.
+    bands: Option<Bands>,
+    /// Multipliers for tagged subtotals of the total.
+    resistances: Resistances,
     reason: Option<String>,
+    /// Named rules checked against the kept dice of each result.
+    flags: Vec<(String, NaturalRule)>,
 }
 
 impl Display for Command {
@@ -53,32 +77,120 @@ impl Rollable for Command {
     type Roll = Result<EvaluatedCommand>;
 
     fn roll_with_source(&self, rng: &mut dyn DiceRollSource) -> Self::Roll {
-        let count: usize = self.repeat.as_ref().map(|r| r.count).unwrap_or(1);
-        let expressions: Result<Vec<Box<dyn EvaluatedExpression>>> = (0..count as isize)
-            .map(|_i| self.expression.roll_with_source(rng))
-            .collect();
+        let (count, count_roll) = match &self.repeat {
+            Some(repeat) => repeat.count.roll(rng)?,
+            None => (1, None),
+        };
+        let participants = self.participants();
+        let expressions: Result<Vec<Box<dyn EvaluatedExpression>>> = if self.contest.is_some() {
+            participants
+                .iter()
+                .map(|x| x.roll_with_source(rng))
+                .collect()
+        } else {
+            (0..count as isize)
+                .map(|_i| self.expression.roll_with_source(rng))
+                .collect()
+        };
         let mut expressions = expressions?;
+        let mut names: Vec<Option<String>> = match &self.repeat {
+            Some(RepeatedCommand {
+                count: RepeatCount::Names(names),
+                ..
+            }) => names.iter().cloned().map(Some).collect(),
+            _ => vec![None; expressions.len()],
+        };
+        let contest = match &self.contest {
+            Some(contest) => Some(contest.resolve(&participants, &expressions, rng)?),
+            None => None,
+        };
+        let check = match &self.check {
+            Some(check) => Some(check.resolve(&*expressions[0], rng)?),
+            None => None,
+        };
 
-        let total: Option<f64> = match self.repeat {
-            Some(repeat) => match repeat.mode {
-                RepeatedMode::Sum => Some(expressions.iter().fold(0.0, |x, y| x + y.total())),
-                RepeatedMode::Sort => {
-                    expressions.sort_by(|a, b| f64::total_cmp(&a.total(), &b.total()));
-                    None
-                }
-                RepeatedMode::None => None,
-            },
-            None => Some(expressions.first().unwrap().total()),
+        if let Some(RepeatedCommand {
+            mode: RepeatedMode::Sort,
+            ..
+        }) = self.repeat
+        {
+            let mut sorted: Vec<_> = expressions.into_iter().zip(names).collect();
+            sorted.sort_by(|(a, _), (b, _)| Number::total_cmp(&a.total(), &b.total()));
+            (expressions, names) = sorted.into_iter().unzip();
+        }
+        let totals: Vec<Number> = expressions.iter().map(|x| x.total()).collect();
+        let kept = match &self.repeat {
+            Some(repeat) => repeat.kept(&totals)?,
+            None => vec![true; totals.len()],
+        };
+        let total: Option<Number> = match &self.repeat {
+            Some(repeat) => repeat.total(&totals, &kept),
+            None if contest.is_some() => None,
+            None => Some(totals[0]),
         };
+        // Like outputs, subtotals are for a total of the kept results rather than a count of them
+        let subtotals =
+            if total.is_none() || self.repeat.as_ref().is_some_and(|r| r.target.is_some()) {
+                vec![]
+            } else {
+                self.resistances.apply(
+                    expressions
+                        .iter()
+                        .zip(&kept)
+                        .filter(|(_, kept)| **kept)
+                        .fold(vec![], |tags, (x, _)| merge_outputs(tags, x.tags())),
+                )
+            };
+        let total = total.map(|total| {
+            subtotals.iter().fold(total, |total, subtotal| {
+                total - subtotal.raw + subtotal.total
+            })
+        });
 
-        let repeat: Option<RepeatedCommand> = self.repeat;
+        let label = match (&self.bands, total) {
+            (Some(bands), Some(total)) => bands.label(total),
+            _ => None,
+        };
+        let labels: Vec<Option<String>> = match (&self.bands, &self.repeat) {
+            (Some(_), None) if contest.is_none() => vec![label.clone()],
+            (
+                Some(bands),
+                None
+                | Some(RepeatedCommand {
+                    mode: RepeatedMode::Sort | RepeatedMode::None,
+                    ..
+                }),
+            ) => expressions.iter().map(|x| bands.label(x.total())).collect(),
+            _ => vec![None; expressions.len()],
+        };
+
+        let repeat: Option<RepeatedCommand> = self.repeat.clone();
         let reason: Option<String> = self.reason.clone();
+        let flags = expressions
+            .iter()
+            .map(|e| {
+                self.flags
+                    .iter()
+                    .filter(|(_, rule)| rule.matches_expression(&**e))
+                    .map(|(name, _)| name.clone())
+                    .collect()
+            })
+            .collect();
 
         Ok(EvaluatedCommand {
             total,
             expressions,
             repeat,
+            count_roll,
+            names,
+            kept,
+            contest,
+            check,
+            labels,
+            label,
+            subtotals,
             reason,
+            flags,
         })
     }
 }
@@ -86,10 +198,26 @@ impl Rollable for Command {
 /// Result of rolling a [Command].
 #[derive(Debug)]
 pub struct EvaluatedCommand {
-    total: Option<f64>,
+    total: Option<Number>,
     expressions: Vec<Box<dyn EvaluatedExpression>>,
     repeat: Option<RepeatedCommand>,
+    /// The repeat count, if it was rolled.
+    count_roll: Option<Box<dyn EvaluatedExpression>>,
+    /// Name of each result, if repetitions were named.
+    names: Vec<Option<String>>,
+    /// If each result was kept, rather than dropped by [KeepOrDrop].
+    kept: Vec<bool>,
+    contest: Option<EvaluatedContest>,
+    check: Option<EvaluatedCheck>,
+    /// Band labels for each result.
+    labels: Vec<Option<String>>,
+    /// Band label for the total.
+    label: Option<String>,
+    /// Parts of the total from each tag, after resistances.
+    subtotals: Vec<Subtotal>,
     reason: Option<String>,
+    /// Names of the flags which matched, for each result.
+    flags: Vec<Vec<String>>,
 }
 
 impl Display for EvaluatedCommand {
@@ -106,15 +234,116 @@ impl Display for dyn EvaluatedExpression {
 
 impl EvaluatedCommand {
     /// If this command is a single (non-repeated) expression, OR a summed repeated expression, this gives the total.
-    /// Otherwise there is no total, and [None] is returned.
-    pub fn total(&self) -> Option<f64> {
+    /// For a repeated expression with a target, like `(1d20+5) ^ 4 t15`, it is the number of kept results meeting the target.
+    /// Otherwise (including contests) there is no total, and [None] is returned.
+    pub fn total(&self) -> Option<Number> {
         self.total
     }
 
-    /// Results from each run of the expression
+    /// Named outputs (see [EvaluatedExpression::outputs]) to go with [EvaluatedCommand::total],
+    /// summed over the kept results for a summed repeated expression.
+    /// Empty when there is no total, or it counts targets: use the outputs of each of the [EvaluatedCommand::results] instead.
+    pub fn outputs(&self) -> Vec<(String, Number)> {
+        if self.total.is_none() || self.repeat.as_ref().is_some_and(|r| r.target.is_some()) {
+            return vec![];
+        }
+        self.expressions
+            .iter()
+            .zip(&self.kept)
+            .filter(|(_, kept)| **kept)
+            .fold(vec![], |outputs, (x, _)| {
+                merge_outputs(outputs, x.outputs())
+            })
+    }
+
+    /// Parts of [EvaluatedCommand::total] from tagged leaves, like `fire` in `1d8[fire] + 2d6[slashing]`,
+    /// after any resistances. Empty when there is no total, or it counts targets.
+    pub fn subtotals(&self) -> Vec<(String, Number)> {
+        self.subtotals
+            .iter()
+            .map(|subtotal| (subtotal.tag.clone(), subtotal.total))
+            .collect()
+    }
+
+    /// Results from each run of the expression, or of each participant in a contest
     pub fn results(&self) -> &Vec<Box<dyn EvaluatedExpression>> {
         &self.expressions
     }
+
+    /// Name of the repetition which gave the result at `index` in [EvaluatedCommand::results],
+    /// like "Goblin B" for `(1d20+4) ^ [Goblin A, Goblin B]`.
+    pub fn result_name(&self, index: usize) -> Option<&str> {
+        self.names[index].as_deref()
+    }
+
+    /// If the result at `index` in [EvaluatedCommand::results] was kept, like the best six of `(4d6 d1) ^ 7 K6`.
+    pub fn result_kept(&self, index: usize) -> bool {
+        self.kept[index]
+    }
+
+    /// Winner and ranking, if this command is a contest.
+    pub fn contest(&self) -> Option<&EvaluatedContest> {
+        self.contest.as_ref()
+    }
+
+    /// Label of the band (see [Command::parse]) the total falls in, if any.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Label of the band the result at `index` in [EvaluatedCommand::results] falls in, if any.
+    pub fn result_label(&self, index: usize) -> Option<&str> {
+        self.labels[index].as_deref()
+    }
+
+    /// Pass or fail, margin and degree, if this command is checked against a difficulty.
+    pub fn check(&self) -> Option<&EvaluatedCheck> {
+        self.check.as_ref()
+    }
+
+    /// Names of the flags (see [Command::with_flag]) which matched any of the results, in the order they were added.
+    pub fn flags(&self) -> Vec<&str> {
+        let mut flags: Vec<&str> = vec![];
+        for name in self.flags.iter().flatten() {
+            if !flags.contains(&name.as_str()) {
+                flags.push(name);
+            }
+        }
+        flags
+    }
+
+    /// Names of the flags which matched the result at `index` in [EvaluatedCommand::results].
+    pub fn result_flags(&self, index: usize) -> &[String] {
+        &self.flags[index]
+    }
+}
+
+impl EvaluatedCommand {
+    /// Subtotals by tag, followed by the total when resistances changed it.
+    fn format_subtotals(&self, markdown: bool) -> String {
+        let s = format_subtotals(&self.subtotals, markdown);
+        match self.total {
+            Some(total) if self.subtotals.iter().any(|s| s.raw != s.total) => {
+                format!("{s} = {}", format_bold(total, markdown))
+            }
+            _ => s,
+        }
+    }
+}
+
+fn format_label(label: &Option<String>, markdown: bool) -> String {
+    match label {
+        Some(label) => format!(" 🡲 {}", format_italic(label, markdown)),
+        None => String::new(),
+    }
+}
+
+fn format_flags(flags: &[String], markdown: bool) -> String {
+    if flags.is_empty() {
+        return String::new();
+    }
+    let flags: Vec<String> = flags.iter().map(|f| format_italic(f, markdown)).collect();
+    format!(" ({})", flags.join(", "))
 }
 
 impl FancyFormat for EvaluatedCommand {
@@ -123,26 +352,76 @@ impl FancyFormat for EvaluatedCommand {
         let inner: Vec<String> = self
             .expressions
             .iter()
-            .map(|x| x.format(markdown, verbose))
+            .zip(&self.flags)
+            .zip(&self.labels)
+            .map(|((x, flags), label)| {
+                // A single result shows the subtotals of the command's total
+                let subtotals = match self.repeat {
+                    None => self.format_subtotals(markdown),
+                    Some(_) => String::new(),
+                };
+                format!(
+                    "{}{subtotals}{}{}",
+                    x.format(markdown, verbose),
+                    format_label(label, markdown),
+                    format_flags(flags, markdown)
+                )
+            })
             .collect();
+        // Results of repeated commands and contests are in parentheses, or struck out when dropped
+        let inner_each: Vec<String> = inner
+            .iter()
+            .zip(&self.kept)
+            .map(|(s, kept)| match (kept, markdown) {
+                (true, _) => format!("({s})"),
+                (false, true) => format!("~~*({s})*~~"),
+                (false, false) => format!("Drop({s})"),
+            })
+            .zip(&self.names)
+            .map(|(s, name)| match name {
+                Some(name) => format!("{}: {s}", format_bold(name, markdown)),
+                None => s,
+            })
+            .collect();
+        let inner_each = match &self.count_roll {
+            Some(count) => {
+                let mut inner_each = inner_each;
+                inner_each.insert(0, format!("{} times:", count.format(markdown, verbose)));
+                inner_each
+            }
+            None => inner_each,
+        };
         let s = match &self.repeat {
             Some(repeat) => match repeat.mode {
                 RepeatedMode::Sum => format!(
-                    "{} = {}",
-                    inner
-                        .iter()
-                        .map(|s| format!("({s})"))
-                        .collect::<Vec<_>>()
-                        .join(" + "),
-                    format_bold(self.total.unwrap(), markdown)
+                    "{} = {}{}{}{}",
+                    inner_each.join(" + "),
+                    format_bold(self.total.unwrap(), markdown),
+                    format_outputs(&self.outputs(), markdown),
+                    self.format_subtotals(markdown),
+                    format_label(&self.label, markdown)
                 ),
-                RepeatedMode::Sort | RepeatedMode::None => inner
-                    .iter()
-                    .map(|s| format!("({s})"))
-                    .collect::<Vec<_>>()
-                    .join(" "),
+                RepeatedMode::Sort | RepeatedMode::None => match self.total {
+                    Some(total) => format!(
+                        "{} = {}{}",
+                        inner_each.join(" "),
+                        format_bold(total, markdown),
+                        format_label(&self.label, markdown)
+                    ),
+                    None => inner_each.join(" "),
+                },
+            },
+            None => match &self.contest {
+                Some(contest) => format!(
+                    "{} 🡲 {}",
+                    inner_each.join(" vs "),
+                    contest.format(markdown, verbose)
+                ),
+                None => match &self.check {
+                    Some(check) => format!("{} {}", inner[0], check.format(markdown, verbose)),
+                    None => inner[0].clone(),
+                },
             },
-            None => inner.first().unwrap().clone(),
         };
         match &self.reason {
             Some(reason) => format!("{s} : {reason}"),
@@ -153,6 +432,8 @@ impl FancyFormat for EvaluatedCommand {
 
 impl Command {
     /// Parse a command expression.
+    ///
+    /// A command can end with bands labelling ranges of totals, like `2d6 => {..6: Miss, 7..9: Weak hit, 10..: Strong hit}`.
     pub fn parse(s: &str) -> Result<Command> {
         Command::parse_with_variables(s, &HashMap::default())
     }
@@ -168,27 +449,153 @@ impl Command {
             Rule::expr => Command {
                 expression: parse_expression(expr_type.into_inner(), variables)?,
                 repeat: None,
+                contest: None,
+                check: None,
+                bands: None,
+                resistances: Resistances::default(),
                 reason: None,
+                flags: vec![],
             },
             Rule::repeated_expr => process_repeated_expr(expr_type, variables)?,
+            Rule::contest => process_contest(expr_type, variables)?,
+            Rule::check => process_check(expr_type, variables)?,
             _ => unreachable!(),
         };
 
-        if let Some(reason) = pairs.next()
-            && reason.as_rule() == Rule::reason_message
-        {
-            command.reason = Some(reason.as_str().trim().to_owned());
+        for pair in pairs {
+            match pair.as_rule() {
+                Rule::resistance => command.resistances.parse(pair)?,
+                Rule::bands => command.bands = Some(parse_bands(pair.into_inner())?),
+                Rule::reason_message => command.reason = Some(pair.as_str().trim().to_owned()),
+                _ => {}
+            }
+        }
+        let summed = match &command.repeat {
+            Some(repeat) => repeat.mode == RepeatedMode::Sum,
+            None => command.contest.is_none() && command.check.is_none(),
+        };
+        if !command.resistances.is_empty() && !summed {
+            return Err("Resistances only apply to a single or summed roll".into());
         }
         Ok(command)
     }
+
+    /// Flag results whose kept dice match `rule` with `name`.
+    ///
+    /// Matching flags are reported by [EvaluatedCommand::flags] and included in the formatted results.
+    pub fn with_flag(mut self, name: impl Into<String>, rule: NaturalRule) -> Command {
+        self.flags.push((name.into(), rule));
+        self
+    }
+
+    /// The expressions rolled: just the one, or each side of a contest.
+    fn participants(&self) -> Vec<&Expression> {
+        let mut participants = vec![&self.expression];
+        if let Some(contest) = &self.contest {
+            participants.extend(&contest.opponents);
+        }
+        participants
+    }
+}
+
+impl Command {
+    /// Roll the command `n` times, computing only the totals.
+    ///
+    /// This skips building the history used for formatting, so it is much faster than [Rollable::roll_with] for
+    /// simulations and bulk rolling.
+    /// Totals without dice which are rerolled or exploded may be picked at once from their odds, so they follow
+    /// the same odds as with [Rollable::roll_with], but not the same sequence from a seeded `rng`.
+    /// Returns an error if the command has no total (see [EvaluatedCommand::total]).
+    pub fn roll_totals(&self, n: usize, rng: &mut impl Rng) -> Result<Vec<Number>> {
+        self.roll_totals_from(n, &mut RngDiceRollSource { rng })
+    }
+
+    /// Like [Command::roll_totals], but with the provided dice roll source, which rolls every die.
+    pub fn roll_totals_with_source(
+        &self,
+        n: usize,
+        rng: &mut dyn DiceRollSource,
+    ) -> Result<Vec<Number>> {
+        self.roll_totals_from(n, &mut DynDiceRollSource(rng))
+    }
+
+    /// Roll the totals with the expression compiled once, and its dice rolled straight from `rng`.
+    fn roll_totals_from<S: ProgramRollSource>(&self, n: usize, rng: &mut S) -> Result<Vec<Number>> {
+        let repeat = match &self.repeat {
+            Some(repeat) if repeat.mode == RepeatedMode::Sum || repeat.target.is_some() => {
+                Some(repeat)
+            }
+            Some(_) => {
+                return Err("Repeated command without summing or a target has no total".into());
+            }
+            None if self.contest.is_some() => return Err("Contest has no total".into()),
+            None => None,
+        };
+        if !self.resistances.is_empty() {
+            // Resistances apply to subtotals, which need the full results
+            return (0..n)
+                .map(|_| Ok(self.roll_with_source(rng)?.total().unwrap()))
+                .collect();
+        }
+        // Summing a fixed number of totals is the same as rolling their sum
+        let (repeat, times) = match repeat {
+            Some(RepeatedCommand {
+                count: RepeatCount::Fixed(times),
+                mode: RepeatedMode::Sum,
+                keep: None,
+                target: None,
+            }) => (None, *times),
+            repeat => (repeat, 1),
+        };
+        // Working out odds is only worth it if it takes fewer steps than rolling
+        let budget = if S::PICKS_TOTALS { n } else { 0 };
+        let mut program = Program::new(&self.expression, times, budget);
+        let mut results = Vec::with_capacity(n);
+        let Some(repeat) = repeat else {
+            for _ in 0..n {
+                results.push(program.roll_total(rng)?);
+            }
+            return Ok(results);
+        };
+        let mut totals = vec![];
+        for _ in 0..n {
+            totals.clear();
+            for _ in 0..repeat.count.roll_total(rng)? {
+                totals.push(program.roll_total(rng)?);
+            }
+            let total = match repeat.keep {
+                Some(_) => repeat.total(&totals, &repeat.kept(&totals)?),
+                None => repeat.total_of(totals.iter().copied()),
+            };
+            results.push(total.unwrap());
+        }
+        Ok(results)
+    }
 }
 
 impl FancyFormat for Command {
     fn format(&self, markdown: bool, verbose: Verbosity) -> String {
         let inner = self.expression.format(markdown, verbose);
-        let s = match &self.repeat {
-            Some(repeat) => format!("{} {}", inner, repeat),
-            None => inner,
+        let s = match (&self.repeat, &self.contest) {
+            (Some(repeat), _) => format!("{} {}", inner, repeat),
+            (None, Some(contest)) => format!(
+                "{}{}",
+                self.participants()
+                    .iter()
+                    .map(|x| x.format(markdown, verbose))
+                    .collect::<Vec<_>>()
+                    .join(" vs "),
+                contest.tie_break
+            ),
+            (None, None) => match &self.check {
+                Some(check) => format!("{inner} {}", check.format(markdown, verbose)),
+                None => inner,
+            },
+        };
+        let s = format!("{s}{}", self.resistances);
+        let s = match &self.bands {
+            Some(bands) => format!("{s} {bands}"),
+            None => s,
         };
         match &self.reason {
             Some(reason) => format!("{s} : {reason}"),
@@ -200,20 +607,129 @@ impl FancyFormat for Command {
 impl Display for RepeatedCommand {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.mode {
-            RepeatedMode::Sum => write!(f, "^+ {}", self.count),
-            RepeatedMode::Sort => write!(f, "^# {}", self.count),
-            RepeatedMode::None => write!(f, "^ {}", self.count),
+            RepeatedMode::Sum => write!(f, "^+ {}", self.count)?,
+            RepeatedMode::Sort => write!(f, "^# {}", self.count)?,
+            RepeatedMode::None => write!(f, "^ {}", self.count)?,
+        }
+        if let Some(keep) = self.keep {
+            write!(f, " {keep}")?;
         }
+        if let Some(target) = self.target {
+            write!(f, " t{target}")?;
+        }
+        Ok(())
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 struct RepeatedCommand {
-    count: usize,
+    count: RepeatCount,
     mode: RepeatedMode,
+    /// Results to keep, by total.
+    keep: Option<KeepOrDrop<usize>>,
+    /// Count kept results with at least this total.
+    target: Option<i64>,
 }
 
-#[derive(Clone, Copy, Debug)]
+impl RepeatedCommand {
+    /// If each result with these `totals` is kept.
+    fn kept(&self, totals: &[Number]) -> Result<Vec<bool>> {
+        let Some(keep) = self.keep else {
+            return Ok(vec![true; totals.len()]);
+        };
+        // Rank the totals, so that equal totals are ordered by position
+        let mut order: Vec<usize> = (0..totals.len()).collect();
+        order.sort_by(|a, b| totals[*a].total_cmp(&totals[*b]));
+        let mut ranks = vec![0; totals.len()];
+        for (rank, index) in order.into_iter().enumerate() {
+            ranks[index] = rank;
+        }
+        Ok(keep
+            .apply(&ranks, |rank| *rank)?
+            .into_iter()
+            .map(|(kept, _)| kept)
+            .collect())
+    }
+
+    /// The total of the `kept` results: their count meeting the target, or their sum.
+    fn total(&self, totals: &[Number], kept: &[bool]) -> Option<Number> {
+        self.total_of(
+            totals
+                .iter()
+                .zip(kept)
+                .filter(|(_, kept)| **kept)
+                .map(|(total, _)| *total),
+        )
+    }
+
+    /// Like [RepeatedCommand::total], given only the kept totals.
+    fn total_of(&self, kept: impl Iterator<Item = Number>) -> Option<Number> {
+        match (self.target, self.mode) {
+            (Some(target), _) => {
+                Some((kept.filter(|total| *total >= Number::from(target)).count() as i64).into())
+            }
+            (None, RepeatedMode::Sum) => Some(kept.sum()),
+            (None, _) => None,
+        }
+    }
+}
+
+/// How many times to repeat a command.
+#[derive(Clone, Debug)]
+enum RepeatCount {
+    Fixed(usize),
+    /// Rolled (or looked up, for a variable) each time the command is rolled.
+    Rolled(Expression),
+    /// Once for each name, like `[Goblin A, Goblin B]`.
+    Names(Vec<String>),
+}
+
+impl RepeatCount {
+    /// The count, with the result of rolling it if it was rolled.
+    fn roll(
+        &self,
+        rng: &mut dyn DiceRollSource,
+    ) -> Result<(usize, Option<Box<dyn EvaluatedExpression>>)> {
+        match self {
+            RepeatCount::Fixed(count) => Ok((*count, None)),
+            RepeatCount::Rolled(count) => {
+                let count = count.roll_with_source(rng)?;
+                Ok((check_repeat_count(count.total())?, Some(count)))
+            }
+            RepeatCount::Names(names) => Ok((names.len(), None)),
+        }
+    }
+
+    /// Like [RepeatCount::roll], but computing only the count.
+    fn roll_total(&self, rng: &mut dyn DiceRollSource) -> Result<usize> {
+        match self {
+            RepeatCount::Rolled(count) => check_repeat_count(count.roll_total(rng)?),
+            _ => Ok(self.roll(rng)?.0),
+        }
+    }
+}
+
+impl Display for RepeatCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepeatCount::Fixed(count) => write!(f, "{count}"),
+            RepeatCount::Rolled(count) => write!(f, "{count}"),
+            RepeatCount::Names(names) => write!(f, "[{}]", names.join(", ")),
+        }
+    }
+}
+
+/// A rolled or looked up count of 0 repeats nothing, but a negative one is an error.
+fn check_repeat_count(count: Number) -> Result<usize> {
+    let count = count.to_integer(Rounding::Down);
+    if count < 0 {
+        return Err("Can't repeat negatively".into());
+    }
+    limit_dice(count as usize, "repeated roll count")?;
+    Ok(count as usize)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum RepeatedMode {
     Sum,
     Sort,
@@ -228,30 +744,134 @@ fn process_repeated_expr(
     let expr = pairs.next().unwrap();
     let maybe_option = pairs.next().unwrap();
     let (count, mode) = match maybe_option.as_rule() {
-        Rule::number => (maybe_option.as_str().parse::<usize>()?, RepeatedMode::None),
-        Rule::add => (
-            pairs.next().unwrap().as_str().parse::<usize>()?,
-            RepeatedMode::Sum,
-        ),
-        Rule::sort => (
-            pairs.next().unwrap().as_str().parse::<usize>()?,
-            RepeatedMode::Sort,
-        ),
+        Rule::repeat_count => (maybe_option, RepeatedMode::None),
+        Rule::add => (pairs.next().unwrap(), RepeatedMode::Sum),
+        Rule::sort => (pairs.next().unwrap(), RepeatedMode::Sort),
         _ => unreachable!(),
     };
-    if count == 0 {
-        Err("Can't repeat 0 times or negatively".into())
+    let inner = count.into_inner();
+    let count = inner.peek().unwrap();
+    let count = match count.as_rule() {
+        Rule::number => RepeatCount::Fixed(count.as_str().parse::<usize>()?),
+        Rule::repeat_names => RepeatCount::Names(
+            count
+                .into_inner()
+                .map(|name| name.as_str().trim().to_string())
+                .collect(),
+        ),
+        _ => RepeatCount::Rolled(parse_expression(inner, variables)?),
+    };
+    let (mut keep, mut target) = (None, None);
+    for pair in pairs {
+        let value = pair.clone().into_inner().as_str().replace(' ', "");
+        match pair.as_rule() {
+            Rule::keep_hi => keep = Some(KeepOrDrop::KeepHi(value.parse()?)),
+            Rule::keep_lo => keep = Some(KeepOrDrop::KeepLo(value.parse()?)),
+            Rule::drop_hi => keep = Some(KeepOrDrop::DropHi(value.parse()?)),
+            Rule::drop_lo => keep = Some(KeepOrDrop::DropLo(value.parse()?)),
+            Rule::repeat_target => target = Some(value.parse()?),
+            _ => unreachable!(),
+        }
+    }
+    if mode == RepeatedMode::Sum && target.is_some() {
+        Err("Can't both sum repeated rolls and count them against a target".into())
     } else {
-        limit_dice(count, "repeated roll count")?;
+        let fixed = match &count {
+            RepeatCount::Fixed(0) => return Err("Can't repeat 0 times".into()),
+            RepeatCount::Fixed(count) => Some(check_repeat_count((*count as i64).into())?),
+            RepeatCount::Names(names) => Some(names.len()),
+            RepeatCount::Rolled(_) => None,
+        };
+        if let (Some(keep), Some(count)) = (keep, fixed) {
+            keep.sorted_range(count)?;
+        }
         let c = parse_expression(expr.clone().into_inner(), variables)?;
         Ok(Command {
             expression: c,
-            repeat: Some(RepeatedCommand { count, mode }),
+            repeat: Some(RepeatedCommand {
+                count,
+                mode,
+                keep,
+                target,
+            }),
+            contest: None,
+            check: None,
+            bands: None,
+            resistances: Resistances::default(),
             reason: None,
+            flags: vec![],
         })
     }
 }
 
+fn process_contest(
+    expr_type: Pair<Rule>,
+    variables: &HashMap<String, Expression>,
+) -> Result<Command> {
+    let mut expressions = vec![];
+    let mut tie_break = TieBreak::Tie;
+    for pair in expr_type.into_inner() {
+        match pair.as_rule() {
+            Rule::expr => expressions.push(parse_expression(pair.into_inner(), variables)?),
+            Rule::tie_break => {
+                tie_break = match pair.into_inner().next().unwrap().as_rule() {
+                    Rule::tie_first => TieBreak::First,
+                    _ => TieBreak::Reroll,
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+    if expressions.len() > Contest::MAX_PARTICIPANTS {
+        return Err(format!(
+            "Too many contest participants (at most {})",
+            Contest::MAX_PARTICIPANTS
+        )
+        .into());
+    }
+    let expression = expressions.remove(0);
+    Ok(Command {
+        expression,
+        repeat: None,
+        contest: Some(Contest {
+            opponents: expressions,
+            tie_break,
+        }),
+        check: None,
+        bands: None,
+        resistances: Resistances::default(),
+        reason: None,
+        flags: vec![],
+    })
+}
+
+fn process_check(
+    expr_type: Pair<Rule>,
+    variables: &HashMap<String, Expression>,
+) -> Result<Command> {
+    let mut pairs = expr_type.into_inner();
+    let expression = parse_expression(pairs.next().unwrap().into_inner(), variables)?;
+    let dc = parse_expression(pairs.next().unwrap().into_inner(), variables)?;
+    let degrees = match pairs
+        .next()
+        .map(|p| p.into_inner().next().unwrap().as_rule())
+    {
+        Some(Rule::pf2e) => Degrees::Pf2e,
+        Some(_) => Degrees::Fate,
+        None => Degrees::PassFail,
+    };
+    Ok(Command {
+        expression,
+        repeat: None,
+        contest: None,
+        check: Some(Check { dc, degrees }),
+        bands: None,
+        resistances: Resistances::default(),
+        reason: None,
+        flags: vec![],
+    })
+}
+
 impl Expression {
     /// Parse as string into an [Expression].
     pub fn parse(expression: &str) -> Result<Expression> {
@@ -375,6 +995,333 @@ mod tests {
         );
     }
 
+    #[test]
+    fn command_repeated_keep() {
+        let spec = Command::parse("(1d6) ^ 3 K2").unwrap();
+        let result = spec
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [5, 2, 5].into_iter(),
+            })
+            .unwrap();
+        assert!(!result.result_kept(1));
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "([5] = 5) Drop([2] = 2) ([5] = 5)"
+        );
+        assert_eq!(format!("{spec}"), "1d6 ^ 3 K2");
+
+        // Ability scores: drop the lowest die, and the lowest of seven stat lines (the first of tied ones)
+        let spec = Command::parse("(4d6 d1) ^+ 7 d1").unwrap();
+        let result = spec
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut (1..=6).cycle(),
+            })
+            .unwrap();
+        assert_eq!(result.total(), Some(74.into()));
+        assert!(!result.result_kept(0));
+        assert_eq!(
+            result.format(true, Verbosity::Medium),
+            "~~*(\\[~~*1*~~, 2, 3, 4\\]d1 = **9**)*~~ + (\\[5, 6, ~~*1*~~, 2\\]d1 = **13**) + (\\[~~*3*~~, 4, 5, 6\\]d1 = **15**) + \
+             (\\[~~*1*~~, 2, 3, 4\\]d1 = **9**) + (\\[5, 6, ~~*1*~~, 2\\]d1 = **13**) + (\\[~~*3*~~, 4, 5, 6\\]d1 = **15**) + \
+             (\\[~~*1*~~, 2, 3, 4\\]d1 = **9**) = **74**"
+        );
+        assert!(Command::parse("(1d6) ^ 3 d4").is_err());
+    }
+
+    #[test]
+    fn command_repeated_target() {
+        let spec = Command::parse("(1d20+5) ^ 4 t15").unwrap();
+        let result = spec
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [12, 3, 10, 18].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(result.total(), Some(3.into()));
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "([12] + 5 = 17) ([3] + 5 = 8) ([10] + 5 = 15) ([18] + 5 = 23) = 3"
+        );
+        // Only kept results count
+        let spec = Command::parse("(1d20) ^# 3 K1 t10").unwrap();
+        let result = spec
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [12, 3, 10].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(result.total(), Some(1.into()));
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "Drop([3] = 3) Drop([10] = 10) ([12] = 12) = 1"
+        );
+        assert_eq!(format!("{spec}"), "1d20 ^# 3 K1 t10");
+        let totals = spec
+            .roll_totals_with_source(
+                2,
+                &mut IteratorDiceRollSource {
+                    iterator: &mut [12, 3, 10, 1, 9, 2].into_iter(),
+                },
+            )
+            .unwrap();
+        assert_eq!(totals, [Number::from(1), Number::from(0)]);
+        assert!(Command::parse("(1d20) ^+ 3 t10").is_err());
+    }
+
+    #[test]
+    fn command_repeated_dynamic() {
+        let mut variables = HashMap::new();
+        variables.insert("attacks".to_string(), Expression::parse("2").unwrap());
+        let spec = Command::parse_with_variables("(1d20+4) ^ $attacks t15", &variables).unwrap();
+        let result = spec
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [7, 15].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(result.total(), Some(1.into()));
+        assert_eq!(
+            result.format(false, Verbosity::Short),
+            "$attacks = 2 times: ([7] + 4 = 11) ([15] + 4 = 19) = 1"
+        );
+        // A count of 0 rolls nothing, though writing `^ 0` is an error
+        variables.insert("attacks".to_string(), Expression::parse("0").unwrap());
+        let spec = Command::parse_with_variables("(1d20+4) ^ $attacks t15", &variables).unwrap();
+        let result = spec
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [].into_iter(),
+            })
+            .unwrap();
+        assert!(result.results().is_empty());
+        assert_eq!(result.total(), Some(0.into()));
+        assert_eq!(
+            result.format(false, Verbosity::Short),
+            "$attacks = 0 times: = 0"
+        );
+        assert_eq!(
+            Command::parse("(1d20+4) ^ 0 t15").unwrap_err(),
+            RollError::ParamError("Can't repeat 0 times".into())
+        );
+
+        // The count is rolled first
+        let spec = Command::parse("(2d6) ^ (1d4)").unwrap();
+        let result = spec
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [2, 3, 4, 5, 6].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(
+            result.format(true, Verbosity::Medium),
+            "(\\[2\\]) = **2** times: (\\[3, 4\\] = **7**) (\\[5, 6\\] = **11**)"
+        );
+        assert_eq!(format!("{spec}"), "2d6 ^ (1d4)");
+        let totals = Command::parse("(2d6) ^+ (1d4 - 1)")
+            .unwrap()
+            .roll_totals_with_source(
+                1,
+                &mut IteratorDiceRollSource {
+                    iterator: &mut [3, 3, 4, 5, 6].into_iter(),
+                },
+            )
+            .unwrap();
+        assert_eq!(totals, [Number::from(18)]);
+        let result = Command::parse("(2d6) ^+ (1d4 - 1)")
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [1].into_iter(),
+            })
+            .unwrap();
+        assert!(result.results().is_empty());
+        assert_eq!(result.total(), Some(0.into()));
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "([1] - 1) = 0 times: = 0"
+        );
+        let totals = Command::parse("(2d6) ^+ (1d4 - 1)")
+            .unwrap()
+            .roll_totals_with_source(
+                1,
+                &mut IteratorDiceRollSource {
+                    iterator: &mut [1].into_iter(),
+                },
+            )
+            .unwrap();
+        assert_eq!(totals, [Number::from(0)]);
+        assert_eq!(
+            Command::parse("(2d6) ^ (1d4 - 2)")
+                .unwrap()
+                .roll_with_source(&mut IteratorDiceRollSource {
+                    iterator: &mut [1].into_iter(),
+                })
+                .unwrap_err(),
+            RollError::ParamError("Can't repeat negatively".into())
+        );
+        // With or without a space, `d1` drops the lowest
+        assert_eq!(
+            format!("{}", Command::parse("(2d6) ^ 3 d1").unwrap()),
+            "2d6 ^ 3 d1"
+        );
+        assert_eq!(
+            format!("{}", Command::parse("(2d6) ^ 3d1").unwrap()),
+            "2d6 ^ 3 d1"
+        );
+    }
+
+    #[test]
+    fn command_repeated_names() {
+        let spec = Command::parse("(1d20+4) ^# [Goblin A, Goblin B, Goblin C] K2").unwrap();
+        let result = spec
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [12, 3, 17].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(result.result_name(0), Some("Goblin B"));
+        assert_eq!(
+            result.format(true, Verbosity::Medium),
+            "**Goblin B**: ~~*(\\[3\\] + 4 = **7**)*~~ **Goblin A**: (\\[12\\] + 4 = **16**) **Goblin C**: (\\[17\\] + 4 = **21**)"
+        );
+        assert_eq!(
+            format!("{spec}"),
+            "1d20 + 4 ^# [Goblin A, Goblin B, Goblin C] K2"
+        );
+        assert!(Command::parse("(1d20) ^ [Goblin A] K2").is_err());
+    }
+
+    #[test]
+    fn outputs() {
+        let roll = |spec: &str, rolls: Vec<u64>| {
+            Command::parse(spec)
+                .unwrap()
+                .roll_with_source(&mut IteratorDiceRollSource {
+                    iterator: &mut rolls.into_iter(),
+                })
+                .unwrap()
+        };
+        // Shadowrun: hits and 1s from the same dice
+        let result = roll("6d6 t5 c1 + 2d6 t5 c1", vec![5, 1, 6, 1, 3, 2, 1, 5]);
+        assert_eq!(result.total(), Some(3.into()));
+        assert_eq!(result.outputs(), [("1s".to_string(), 3.into())]);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "[5, 1, 6, 1, 3, 2] + [1, 5] = 3, 1s: 3"
+        );
+
+        // Hero System: STUN and BODY
+        let result = roll("(3d6 body) ^+ 2", vec![1, 6, 3, 6, 6, 2]);
+        assert_eq!(result.total(), Some(24.into()));
+        assert_eq!(result.outputs(), [("BODY".to_string(), 8.into())]);
+        assert_eq!(
+            result.format(true, Verbosity::Medium),
+            "(\\[1, 6, 3\\] = **10**, BODY: **3**) + (\\[6, 6, 2\\] = **14**, BODY: **5**) = **24**, BODY: **8**"
+        );
+        assert!(roll("(3d6 body) ^ 2", vec![1; 6]).outputs().is_empty());
+        // Outputs count what was rolled, whatever the operator
+        let result = roll("6d6 t5 c1 - 2d6 t5 c1", vec![5, 1, 6, 1, 3, 2, 1, 5]);
+        assert_eq!(result.total(), Some(1.into()));
+        assert_eq!(result.outputs(), [("1s".to_string(), 3.into())]);
+        let result = roll("(3d6 body) / 2", vec![1, 6, 3]);
+        assert_eq!(result.outputs(), [("BODY".to_string(), 3.into())]);
+        assert_eq!(
+            format!("{}", Expression::parse("3d6 body").unwrap()),
+            "3d6 body"
+        );
+    }
+
+    #[test]
+    fn roll_totals_matches_roll() {
+        for spec in [
+            "1d20 + 5",
+            "4d6 d1 * 2",
+            "6d6 e5 K3 r2 d2 e6 + 5d6 k4 e",
+            "10d10 ie5 t4 f1",
+            "3d6 ir2 / 2.5",
+            "(2d6 + 6) ^+ 3",
+            "4d6 t5 c1 + 2d6 body",
+        ] {
+            let command = Command::parse(spec).unwrap();
+            let rolls = [3, 6, 1, 2, 5, 4, 6, 6, 1, 3, 2, 2, 5, 1, 4, 3];
+            let expected: Vec<Number> = (0..4)
+                .map(|i| {
+                    let mut iterator = rolls.iter().cycle().skip(i).copied();
+                    command
+                        .roll_with_source(&mut IteratorDiceRollSource {
+                            iterator: &mut iterator,
+                        })
+                        .unwrap()
+                        .total()
+                        .unwrap()
+                })
+                .collect();
+            let totals: Vec<Number> = (0..4)
+                .map(|i| {
+                    let mut iterator = rolls.iter().cycle().skip(i).copied();
+                    command
+                        .roll_totals_with_source(
+                            1,
+                            &mut IteratorDiceRollSource {
+                                iterator: &mut iterator,
+                            },
+                        )
+                        .unwrap()[0]
+                })
+                .collect();
+            assert_eq!(expected, totals, "{spec}");
+        }
+    }
+
+    #[test]
+    fn roll_totals_errors() {
+        let command = Command::parse("(1d6) ^ 2").unwrap();
+        assert!(command.roll_totals(1, &mut rand::rng()).is_err());
+
+        let command = Command::parse("1d6 D2").unwrap();
+        assert_eq!(
+            command.roll_totals(1, &mut rand::rng()).unwrap_err(),
+            RollError::ParamError("Cannot drop 2 dice when there are only 1".into())
+        );
+    }
+
+    #[test]
+    fn flags() {
+        let spec = Command::parse("2d6 + 2")
+            .unwrap()
+            .with_flag("Boxcars", NaturalRule::AllMax)
+            .with_flag("Doubles", NaturalRule::Matching(2));
+        let result = spec
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [6, 6].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(result.flags(), vec!["Boxcars", "Doubles"]);
+        assert_eq!(
+            result.format(true, Verbosity::Medium),
+            "\\[6, 6\\] + 2 = **14** (*Boxcars*, *Doubles*)"
+        );
+
+        let result = spec
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [6, 5].into_iter(),
+            })
+            .unwrap();
+        assert!(result.flags().is_empty());
+        assert_eq!(result.format(false, Verbosity::Medium), "[6, 5] + 2 = 13");
+    }
+
+    #[test]
+    fn flags_repeated() {
+        let spec = Command::parse("(1d20) ^ 3")
+            .unwrap()
+            .with_flag("Crit", NaturalRule::AnyAtLeast(19));
+        let result = spec
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [19, 3, 20].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(result.flags(), vec!["Crit"]);
+        assert_eq!(result.result_flags(1), &[] as &[String]);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "([19] = 19 (Crit)) ([3] = 3) ([20] = 20 (Crit))"
+        );
+    }
+
     #[test]
     fn invalid_reroll_fudge() {
         let spec = Command::parse("1dF ir6").unwrap_err();
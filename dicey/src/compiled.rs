@@ -0,0 +1,299 @@
+//! Expressions compiled into a flat program, to quickly roll many totals.
+
+use rand::Rng;
+
+use crate::{
+    DiceRollSource, Number, Result, RngDiceRollSource,
+    dice_expression::CompiledDice,
+    expression::{BinaryOp, Expression},
+};
+
+/// A step of a [Program], pushing its result onto the stack of totals.
+#[derive(Debug)]
+pub(crate) enum Op {
+    Number(Number),
+    Dice(CompiledDice),
+    /// A total picked from its odds, instead of rolling the dice giving it.
+    Odds(Odds),
+    /// Pops the right, then the left operand.
+    Binary(BinaryOp),
+    /// Replaces the top of the stack with it as the left operand, and a constant as the right.
+    BinaryConstant(BinaryOp, Number),
+    /// Part of the expression which could not be compiled, rolled as a whole.
+    Expression(Expression),
+}
+
+/// How many of a number of equally likely outcomes give each total.
+#[derive(Debug)]
+pub(crate) struct Odds {
+    /// Each total, after how many outcomes give it or an earlier total.
+    totals: Vec<(u64, Number)>,
+}
+
+impl Odds {
+    /// Odds of totals happening in some number of ways, or [None] if there are too many outcomes to count.
+    pub(crate) fn new(ways: impl IntoIterator<Item = (Number, u64)>) -> Option<Odds> {
+        let mut ways: Vec<_> = ways.into_iter().collect();
+        ways.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        let mut outcomes: u64 = 0;
+        let mut totals: Vec<(u64, Number)> = vec![];
+        for (total, ways) in ways {
+            outcomes = outcomes.checked_add(ways)?;
+            match totals.last_mut() {
+                Some((before, last))
+                    if last.total_cmp(&total).is_eq() && last.is_exact() == total.is_exact() =>
+                {
+                    *before = outcomes
+                }
+                _ => totals.push((outcomes, total)),
+            }
+        }
+        Some(Odds { totals })
+    }
+
+    /// Each total with how many outcomes give it.
+    fn ways(&self) -> impl Iterator<Item = (Number, u64)> + '_ {
+        let mut before = 0;
+        self.totals.iter().map(move |(outcomes, total)| {
+            let ways = outcomes - before;
+            before = *outcomes;
+            (*total, ways)
+        })
+    }
+
+    fn outcomes(&self) -> u64 {
+        self.totals.last().map_or(0, |(outcomes, _)| *outcomes)
+    }
+
+    /// The odds of `op` applied to totals from `self` and `right`, unless working them out takes more than
+    /// `budget` steps.
+    pub(crate) fn apply(&self, op: BinaryOp, right: &Odds, budget: usize) -> Option<Odds> {
+        self.outcomes().checked_mul(right.outcomes())?;
+        // Constants always fold
+        if self.totals.len().saturating_mul(right.totals.len()) > budget.max(1) {
+            return None;
+        }
+        Odds::new(self.ways().flat_map(|(left, ways)| {
+            right
+                .ways()
+                .map(move |(right, others)| (op.apply(left, right), ways * others))
+        }))
+    }
+
+    fn pick<S: ProgramRollSource>(&self, rng: &mut S) -> Number {
+        let outcome = rng.random_below(self.outcomes());
+        self.totals[self
+            .totals
+            .partition_point(|(before, _)| *before <= outcome)]
+        .1
+    }
+}
+
+impl Op {
+    /// The odds of the total pushed by this step, if it is known without rolling.
+    fn odds(&self) -> Option<Odds> {
+        match self {
+            Op::Number(number) => Odds::new([(*number, 1)]),
+            Op::Odds(odds) => Odds::new(odds.ways()),
+            _ => None,
+        }
+    }
+
+    /// A step pushing a total with these odds, which is a constant if there is only one.
+    fn from_odds(odds: Odds) -> Op {
+        match odds.totals[..] {
+            [(_, total)] => Op::Number(total),
+            _ => Op::Odds(odds),
+        }
+    }
+}
+
+/// An [Expression] compiled into steps in postfix order, which roll its total without allocating.
+#[derive(Debug)]
+pub(crate) struct Program {
+    ops: Vec<Op>,
+    stack: Vec<Number>,
+    /// Scratch space for rolling dice.
+    counts: Vec<usize>,
+    next: Vec<usize>,
+}
+
+impl Program {
+    /// Compile the sum of `times` totals of `expression`, picking totals from their odds instead of rolling
+    /// where working those out takes at most `budget` steps.
+    pub(crate) fn new(expression: &Expression, times: usize, budget: usize) -> Program {
+        let mut compiled = vec![];
+        expression.compile(&mut compiled);
+        for _ in 1..times {
+            expression.compile(&mut compiled);
+            compiled.push(Op::Binary(BinaryOp::Add));
+        }
+        let mut ops: Vec<Op> = vec![];
+        for op in compiled {
+            match op {
+                Op::Dice(dice) => ops.push(match dice.odds(budget) {
+                    Some(odds) => Op::from_odds(odds),
+                    None => Op::Dice(dice),
+                }),
+                Op::Binary(op) => {
+                    // Operands are only known to be the last two steps if both push a total
+                    let [.., left, right] = &ops[..] else {
+                        unreachable!("binary operations follow their operands")
+                    };
+                    let folded = (left.odds())
+                        .zip(right.odds())
+                        .and_then(|(left, right)| left.apply(op, &right, budget));
+                    if let Some(odds) = folded {
+                        ops.truncate(ops.len() - 2);
+                        ops.push(Op::from_odds(odds));
+                    } else if let Some(&Op::Number(right)) = ops.last() {
+                        ops.pop();
+                        ops.push(Op::BinaryConstant(op, right));
+                    } else {
+                        ops.push(Op::Binary(op));
+                    }
+                }
+                op => ops.push(op),
+            }
+        }
+        Program {
+            ops,
+            stack: vec![],
+            counts: vec![],
+            next: vec![],
+        }
+    }
+
+    /// Roll the total, using `rng` the same way as [Expression::roll_total] unless it picks totals.
+    pub(crate) fn roll_total<S: ProgramRollSource>(&mut self, rng: &mut S) -> Result<Number> {
+        self.stack.clear();
+        // The top of the stack is kept out of it, as most steps replace it
+        let mut top = Number::from(0);
+        for (i, op) in self.ops.iter().enumerate() {
+            let total = match op {
+                Op::Number(number) => *number,
+                Op::Dice(dice) => dice
+                    .roll_total(rng, &mut self.counts, &mut self.next)?
+                    .into(),
+                Op::Odds(odds) => odds.pick(rng),
+                Op::Binary(op) => {
+                    top = op.apply(self.stack.pop().unwrap(), top);
+                    continue;
+                }
+                Op::BinaryConstant(op, right) => {
+                    top = op.apply(top, *right);
+                    continue;
+                }
+                Op::Expression(expression) => expression.roll_total(rng)?,
+            };
+            // Programs start with a total, with nothing below it
+            if i > 0 {
+                self.stack.push(top);
+            }
+            top = total;
+        }
+        Ok(top)
+    }
+}
+
+/// A [DiceRollSource] for [Program::roll_total].
+pub(crate) trait ProgramRollSource: DiceRollSource {
+    /// If totals may be picked from their [Odds], rather than rolling each die as the source may expect.
+    const PICKS_TOTALS: bool;
+
+    /// A random number below `bound`, to pick a total from its [Odds].
+    fn random_below(&mut self, bound: u64) -> u64;
+}
+
+impl<T: Rng> ProgramRollSource for RngDiceRollSource<'_, T> {
+    const PICKS_TOTALS: bool = true;
+
+    fn random_below(&mut self, bound: u64) -> u64 {
+        self.rng.random_range(0..bound)
+    }
+}
+
+/// A `dyn` [DiceRollSource], which rolls each die.
+pub(crate) struct DynDiceRollSource<'a>(pub(crate) &'a mut dyn DiceRollSource);
+
+impl DiceRollSource for DynDiceRollSource<'_> {
+    fn roll_single_die(&mut self, sides: u64) -> u64 {
+        self.0.roll_single_die(sides)
+    }
+}
+
+impl ProgramRollSource for DynDiceRollSource<'_> {
+    const PICKS_TOTALS: bool = false;
+
+    fn random_below(&mut self, _bound: u64) -> u64 {
+        unreachable!("totals are only picked from odds worked out for sources picking them")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::IteratorDiceRollSource;
+
+    /// The odds of `spec` worked out by rolling each of the outcomes of `dice` dice with `sides`.
+    fn rolled_odds(spec: &str, sides: u64, dice: u32) -> Vec<(Number, u64)> {
+        let expression = Expression::parse(spec).unwrap();
+        let totals = (0..sides.pow(dice)).map(|outcome| {
+            let rolls = (0..dice).map(|i| outcome / sides.pow(i) % sides + 1);
+            let total = expression.roll_total(&mut IteratorDiceRollSource {
+                iterator: &mut rolls.collect::<Vec<_>>().into_iter(),
+            });
+            (total.unwrap(), 1)
+        });
+        Odds::new(totals).unwrap().ways().collect()
+    }
+
+    #[test]
+    fn dice_odds() {
+        for (spec, sides, dice) in [
+            ("4d6 d1", 6, 4),
+            ("4d10 t7 f1", 10, 4),
+            ("3d4 k2 + 1", 4, 3),
+        ] {
+            let program = Program::new(&Expression::parse(spec).unwrap(), 1, 10_000);
+            let [Op::Odds(odds)] = &program.ops[..] else {
+                panic!("{:?}", program.ops);
+            };
+            assert_eq!(
+                odds.ways().collect::<Vec<_>>(),
+                rolled_odds(spec, sides, dice)
+            );
+        }
+    }
+
+    #[test]
+    fn folds_odds() {
+        let expression = Expression::parse("(2d6 + 3) * 1d4 - 1").unwrap();
+        let program = Program::new(&expression, 2, 10_000);
+        let [Op::Odds(odds)] = &program.ops[..] else {
+            panic!("{:?}", program.ops);
+        };
+        let mut expected = vec![];
+        for rolls in 0..36 * 4 * 36 * 4 {
+            let roll = |i: usize, sides: usize| (rolls / i % sides + 1) as i64;
+            let first = (roll(1, 6) + roll(6, 6) + 3) * roll(36, 4) - 1;
+            let second = (roll(144, 6) + roll(864, 6) + 3) * roll(5184, 4) - 1;
+            expected.push((Number::from(first + second), 1));
+        }
+        assert_eq!(
+            odds.ways().collect::<Vec<_>>(),
+            Odds::new(expected).unwrap().ways().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn folds_constants_without_budget() {
+        let expression = Expression::parse("1d6 + 2 * 3 / 4").unwrap();
+        let program = Program::new(&expression, 1, 0);
+        assert!(matches!(
+            program.ops[..],
+            [Op::Dice(_), Op::BinaryConstant(BinaryOp::Add, total)]
+                if total == Number::rational(3, 2).unwrap()
+        ));
+    }
+}
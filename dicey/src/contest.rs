@@ -0,0 +1,315 @@
+//! Contests between different expressions, like `1d20+5 vs 1d20+3`.
+
+use std::{cmp::Ordering, fmt::Display};
+
+use crate::{
+    DiceRollSource, EvaluatedExpression, Expression, FancyFormat, Number, Result, Verbosity,
+    dice_expression::format_rolls,
+    expression::{format_bold, format_italic},
+};
+
+/// How to rank participants of a contest with the same total.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TieBreak {
+    /// Leave them tied, so a tie for first place has no winner.
+    #[default]
+    Tie,
+    /// The participant listed first wins, like the status quo in an opposed check.
+    First,
+    /// Roll the tied participants again until they differ, or leave them tied if they never do.
+    Reroll,
+}
+
+impl Display for TieBreak {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TieBreak::Tie => Ok(()),
+            TieBreak::First => f.write_str(" tie=first"),
+            TieBreak::Reroll => f.write_str(" tie=reroll"),
+        }
+    }
+}
+
+/// The other side(s) of a command's expression in a contest.
+#[derive(Debug)]
+pub(crate) struct Contest {
+    pub(crate) opponents: Vec<Expression>,
+    pub(crate) tie_break: TieBreak,
+}
+
+impl Contest {
+    /// Most times tied participants are rerolled, before leaving them tied.
+    const MAX_REROLLS: usize = 100;
+
+    /// Most participants in one contest.
+    pub(crate) const MAX_PARTICIPANTS: usize = 100;
+
+    /// Rank the `results` of rolling `participants`, rerolling ties if needed.
+    pub(crate) fn resolve(
+        &self,
+        participants: &[&Expression],
+        results: &[Box<dyn EvaluatedExpression>],
+        rng: &mut dyn DiceRollSource,
+    ) -> Result<EvaluatedContest> {
+        let totals: Vec<Number> = results.iter().map(|r| r.total()).collect();
+        let mut rerolls: Vec<Vec<Number>> = vec![vec![]; totals.len()];
+        let compare = |rerolls: &[Vec<Number>], a: usize, b: usize| {
+            totals[b].total_cmp(&totals[a]).then_with(|| {
+                rerolls[b]
+                    .iter()
+                    .zip(&rerolls[a])
+                    .map(|(b, a)| b.total_cmp(a))
+                    .find(|o| o.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            })
+        };
+        let still_tied = |rerolls: &[Vec<Number>]| -> Vec<usize> {
+            (0..totals.len())
+                .filter(|a| (0..totals.len()).any(|b| b != *a && compare(rerolls, *a, b).is_eq()))
+                .collect()
+        };
+        if self.tie_break == TieBreak::Reroll {
+            for _ in 0..Contest::MAX_REROLLS {
+                let tied = still_tied(&rerolls);
+                if tied.is_empty() {
+                    break;
+                }
+                for i in tied {
+                    rerolls[i].push(participants[i].roll_total(rng)?);
+                }
+            }
+            // Rerolls which never differ, like `5 vs 5`, leave a tie: only keep as many as
+            // separated the others
+            let tied = still_tied(&rerolls);
+            let needed = (0..totals.len())
+                .filter(|i| !tied.contains(i))
+                .map(|i| rerolls[i].len())
+                .max()
+                .unwrap_or(0);
+            for i in tied {
+                rerolls[i].truncate(needed);
+            }
+        }
+        let mut ranking: Vec<usize> = (0..totals.len()).collect();
+        ranking.sort_by(|a, b| compare(&rerolls, *a, *b));
+        let tied = (0..totals.len())
+            .any(|a| (0..totals.len()).any(|b| b != a && totals[a].total_cmp(&totals[b]).is_eq()));
+        let winner = match self.tie_break {
+            TieBreak::First => Some(ranking[0]),
+            _ if compare(&rerolls, ranking[0], ranking[1]).is_eq() => None,
+            _ => Some(ranking[0]),
+        };
+        let tie_break = match self.tie_break {
+            TieBreak::Tie => None,
+            TieBreak::Reroll if rerolls.iter().all(|r| r.is_empty()) => None,
+            tie_break => tied.then_some(tie_break),
+        };
+        Ok(EvaluatedContest {
+            margin: totals[ranking[0]] - totals[ranking[1]],
+            ranking,
+            winner,
+            tie_break,
+            rerolls,
+        })
+    }
+}
+
+/// Result of a contest: the participants ranked by total.
+///
+/// Participants are indexes in the order written, matching [crate::EvaluatedCommand::results].
+#[derive(Debug)]
+pub struct EvaluatedContest {
+    ranking: Vec<usize>,
+    winner: Option<usize>,
+    margin: Number,
+    tie_break: Option<TieBreak>,
+    rerolls: Vec<Vec<Number>>,
+}
+
+impl EvaluatedContest {
+    /// Participants from highest to lowest.
+    pub fn ranking(&self) -> &[usize] {
+        &self.ranking
+    }
+
+    /// The winning participant, or [None] if first place is tied.
+    pub fn winner(&self) -> Option<usize> {
+        self.winner
+    }
+
+    /// How far first place is ahead of second place.
+    pub fn margin(&self) -> Number {
+        self.margin
+    }
+
+    /// The rule applied to rank tied participants, if any tied.
+    pub fn tie_break(&self) -> Option<TieBreak> {
+        self.tie_break
+    }
+
+    /// Totals of tie-breaking rerolls for participant `index`.
+    pub fn rerolls(&self, index: usize) -> &[Number] {
+        &self.rerolls[index]
+    }
+}
+
+/// Participants are shown as `#1`, `#2` and so on.
+fn format_participant(index: usize) -> String {
+    format!("#{}", index + 1)
+}
+
+impl FancyFormat for EvaluatedContest {
+    fn format(&self, markdown: bool, _verbose: Verbosity) -> String {
+        let mut s = match self.winner {
+            Some(winner) => format!(
+                "{} wins by {}",
+                format_bold(format_participant(winner), markdown),
+                self.margin
+            ),
+            None => format_italic("Tie", markdown),
+        };
+        match self.tie_break {
+            Some(TieBreak::First) => s += " (ties go to the first listed)",
+            Some(TieBreak::Reroll) => {
+                let rerolls: Vec<String> = self
+                    .rerolls
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, rerolls)| !rerolls.is_empty())
+                    .map(|(i, rerolls)| {
+                        format!(
+                            "{} {}",
+                            format_participant(i),
+                            format_rolls(rerolls.iter(), markdown)
+                        )
+                    })
+                    .collect();
+                s += &format!(" (ties rerolled: {})", rerolls.join(", "));
+            }
+            _ => {}
+        }
+        if self.ranking.len() > 2 {
+            let ranking: Vec<String> = self
+                .ranking
+                .iter()
+                .map(|i| format_participant(*i))
+                .collect();
+            s += &format!(", order {}", ranking.join(", "));
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Command, Number, Rollable, Verbosity, expression::FancyFormat,
+        tests::IteratorDiceRollSource,
+    };
+
+    fn roll(command: &str, rolls: Vec<u64>) -> crate::EvaluatedCommand {
+        Command::parse(command)
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut rolls.into_iter(),
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn opposed() {
+        let result = roll("1d20+5 vs 1d20+3", vec![14, 12]);
+        let contest = result.contest().unwrap();
+        assert_eq!(contest.winner(), Some(0));
+        assert_eq!(contest.margin(), 4);
+        assert_eq!(contest.tie_break(), None);
+        assert_eq!(result.total(), None);
+        assert_eq!(
+            result.format(true, Verbosity::Medium),
+            "(\\[14\\] + 5 = **19**) vs (\\[12\\] + 3 = **15**) 🡲 **#1** wins by 4"
+        );
+        let result = roll("1d20+5 vs 1d20+3", vec![10, 12]);
+        assert_eq!(result.contest().unwrap().winner(), None);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "([10] + 5 = 15) vs ([12] + 3 = 15) 🡲 Tie"
+        );
+    }
+
+    #[test]
+    fn tie_breaks() {
+        let result = roll("1d20+5 vs 1d20+3 tie=first", vec![10, 12]);
+        assert_eq!(result.contest().unwrap().winner(), Some(0));
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "([10] + 5 = 15) vs ([12] + 3 = 15) 🡲 #1 wins by 0 (ties go to the first listed)"
+        );
+        // Tied again on the first reroll
+        let result = roll("1d6 vs 1d6 tie=reroll", vec![4, 4, 2, 2, 3, 5]);
+        let contest = result.contest().unwrap();
+        assert_eq!(contest.winner(), Some(1));
+        assert_eq!(contest.rerolls(1), [Number::from(2), Number::from(5)]);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "([4] = 4) vs ([4] = 4) 🡲 #2 wins by 0 (ties rerolled: #1 [2, 3], #2 [2, 5])"
+        );
+        // Rerolling can't break a tie between constants
+        let result = roll("5 vs 5 tie=reroll", vec![]);
+        let contest = result.contest().unwrap();
+        assert_eq!(contest.winner(), None);
+        assert!(contest.rerolls(0).is_empty());
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "(5 = 5) vs (5 = 5) 🡲 Tie"
+        );
+        // Keeping the rerolls which broke the other ties
+        let result = roll("5 vs 5 vs 1d20 vs 1d20 tie=reroll", vec![3, 3, 7, 2]);
+        let contest = result.contest().unwrap();
+        assert_eq!(contest.winner(), None);
+        assert_eq!(contest.ranking(), [0, 1, 2, 3]);
+        assert_eq!(contest.rerolls(2), [Number::from(7)]);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "(5 = 5) vs (5 = 5) vs ([3] = 3) vs ([3] = 3) 🡲 Tie (ties rerolled: #1 [5], #2 [5], #3 [7], #4 [2]), order #1, #2, #3, #4"
+        );
+    }
+
+    #[test]
+    fn group() {
+        // Only the tied participants reroll
+        let result = roll("1d20+2 vs 1d20+1 vs 1d20 tie=reroll", vec![8, 17, 10, 6, 3]);
+        let contest = result.contest().unwrap();
+        assert_eq!(contest.ranking(), [1, 0, 2]);
+        assert_eq!(contest.margin(), 8);
+        assert!(contest.rerolls(1).is_empty());
+        assert_eq!(
+            result.format(false, Verbosity::Short),
+            "([8] + 2 = 10) vs ([17] + 1 = 18) vs ([10] = 10) 🡲 #2 wins by 8 (ties rerolled: #1 [8], #3 [3]), order #2, #1, #3"
+        );
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            format!(
+                "{}",
+                Command::parse("1d20+5 vs (1d20+3) vs 4d6 K3 tie=reroll: initiative").unwrap()
+            ),
+            "1d20 + 5 vs (1d20 + 3) vs 4d6 K3 tie=reroll : initiative"
+        );
+        assert!(Command::parse("(1d20) ^ 2 vs 1d20").is_err());
+        assert!(Command::parse("1d20 vs").is_err());
+        assert!(Command::parse(&vec!["1d20"; 101].join(" vs ")).is_err());
+        assert!(
+            Command::parse("1d20 vs 1d20")
+                .unwrap()
+                .roll_totals_with_source(
+                    1,
+                    &mut IteratorDiceRollSource {
+                        iterator: &mut (1..3),
+                    }
+                )
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,378 @@
+//! Decks of cards, drawn without replacement.
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{DiceRollSource, Result};
+
+/// Suit of a standard playing card, in ascending order for ranking ties (as in Savage Worlds initiative).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Suit {
+    /// ♣
+    Clubs,
+    /// ♦
+    Diamonds,
+    /// ♥
+    Hearts,
+    /// ♠
+    Spades,
+}
+
+/// Suit of a tarot minor arcana card.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum TarotSuit {
+    /// Wands
+    Wands,
+    /// Cups
+    Cups,
+    /// Swords
+    Swords,
+    /// Pentacles
+    Pentacles,
+}
+
+/// Color of a joker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum JokerColor {
+    /// Black joker
+    Black,
+    /// Red joker
+    Red,
+}
+
+/// A card in a [Deck].
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Card {
+    /// A standard playing card, with rank from 2 to 14: 11 is a Jack, 12 a Queen, 13 a King and 14 an Ace.
+    Standard {
+        /// 2 to 14, aces high.
+        rank: u8,
+        /// The suit.
+        suit: Suit,
+    },
+    /// A joker.
+    Joker(JokerColor),
+    /// A tarot major arcana, from 0 (The Fool) to 21 (The World).
+    MajorArcana(u8),
+    /// A tarot minor arcana, with rank from 1 to 14: 1 is an Ace, then 11 Page, 12 Knight, 13 Queen and 14 King.
+    MinorArcana {
+        /// 1 to 14.
+        rank: u8,
+        /// The suit.
+        suit: TarotSuit,
+    },
+    /// A card from a custom deck.
+    Custom(String),
+}
+
+const MAJOR_ARCANA: [&str; 22] = [
+    "The Fool",
+    "The Magician",
+    "The High Priestess",
+    "The Empress",
+    "The Emperor",
+    "The Hierophant",
+    "The Lovers",
+    "The Chariot",
+    "Strength",
+    "The Hermit",
+    "Wheel of Fortune",
+    "Justice",
+    "The Hanged Man",
+    "Death",
+    "Temperance",
+    "The Devil",
+    "The Tower",
+    "The Star",
+    "The Moon",
+    "The Sun",
+    "Judgement",
+    "The World",
+];
+
+impl Card {
+    /// The 52 standard playing cards and two jokers, in order.
+    pub fn standard_deck() -> Vec<Card> {
+        let mut cards = Card::standard_deck_without_jokers();
+        cards.push(Card::Joker(JokerColor::Black));
+        cards.push(Card::Joker(JokerColor::Red));
+        cards
+    }
+
+    /// The 52 standard playing cards, in order.
+    pub fn standard_deck_without_jokers() -> Vec<Card> {
+        [Suit::Clubs, Suit::Diamonds, Suit::Hearts, Suit::Spades]
+            .into_iter()
+            .flat_map(|suit| (2..=14).map(move |rank| Card::Standard { rank, suit }))
+            .collect()
+    }
+
+    /// The 78 tarot cards, in order.
+    pub fn tarot_deck() -> Vec<Card> {
+        (0..22)
+            .map(Card::MajorArcana)
+            .chain(
+                [
+                    TarotSuit::Wands,
+                    TarotSuit::Cups,
+                    TarotSuit::Swords,
+                    TarotSuit::Pentacles,
+                ]
+                .into_iter()
+                .flat_map(|suit| (1..=14).map(move |rank| Card::MinorArcana { rank, suit })),
+            )
+            .collect()
+    }
+}
+
+impl Display for Card {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Card::Standard { rank, suit } => {
+                let rank = match rank {
+                    11 => "J".to_string(),
+                    12 => "Q".to_string(),
+                    13 => "K".to_string(),
+                    14 => "A".to_string(),
+                    n => n.to_string(),
+                };
+                let suit = match suit {
+                    Suit::Clubs => '♣',
+                    Suit::Diamonds => '♦',
+                    Suit::Hearts => '♥',
+                    Suit::Spades => '♠',
+                };
+                write!(f, "{rank}{suit}")
+            }
+            Card::Joker(JokerColor::Black) => write!(f, "Black Joker"),
+            Card::Joker(JokerColor::Red) => write!(f, "Red Joker"),
+            Card::MajorArcana(n) => match MAJOR_ARCANA.get(usize::from(*n)) {
+                Some(name) => write!(f, "{name}"),
+                None => write!(f, "Major Arcana {n}"),
+            },
+            Card::MinorArcana { rank, suit } => {
+                let rank = match rank {
+                    1 => "Ace".to_string(),
+                    11 => "Page".to_string(),
+                    12 => "Knight".to_string(),
+                    13 => "Queen".to_string(),
+                    14 => "King".to_string(),
+                    n => n.to_string(),
+                };
+                write!(f, "{rank} of {suit:?}")
+            }
+            Card::Custom(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// A deck of cards, drawn without replacement.
+///
+/// Drawn cards stay out of the deck until they are discarded, and discarded cards only return to the deck when it is reshuffled.
+/// All of this state can be serialized, so a deck can be kept between sessions.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deck {
+    /// Cards left to draw. The top of the deck is the end.
+    draw_pile: Vec<Card>,
+    /// Cards which have been drawn and not yet discarded.
+    drawn: Vec<Card>,
+    discard_pile: Vec<Card>,
+}
+
+impl Deck {
+    /// A shuffled deck of `cards`.
+    pub fn new(cards: Vec<Card>, rng: &mut dyn DiceRollSource) -> Deck {
+        let mut deck = Deck {
+            draw_pile: cards,
+            drawn: vec![],
+            discard_pile: vec![],
+        };
+        deck.shuffle(rng);
+        deck
+    }
+
+    /// A shuffled 54 card deck: the standard 52 cards and two jokers.
+    pub fn standard(rng: &mut dyn DiceRollSource) -> Deck {
+        Deck::new(Card::standard_deck(), rng)
+    }
+
+    /// A shuffled 78 card tarot deck.
+    pub fn tarot(rng: &mut dyn DiceRollSource) -> Deck {
+        Deck::new(Card::tarot_deck(), rng)
+    }
+
+    /// A shuffled deck of custom cards, like the entries of an oracle.
+    pub fn custom<S: Into<String>>(
+        names: impl IntoIterator<Item = S>,
+        rng: &mut dyn DiceRollSource,
+    ) -> Deck {
+        Deck::new(
+            names.into_iter().map(|n| Card::Custom(n.into())).collect(),
+            rng,
+        )
+    }
+
+    /// Number of cards left to draw.
+    pub fn remaining(&self) -> usize {
+        self.draw_pile.len()
+    }
+
+    /// Cards which have been drawn and not discarded.
+    pub fn drawn(&self) -> &[Card] {
+        &self.drawn
+    }
+
+    /// Cards in the discard pile.
+    pub fn discarded(&self) -> &[Card] {
+        &self.discard_pile
+    }
+
+    /// The next `count` cards which would be drawn, in order, without drawing them.
+    /// Returns fewer if the deck does not have enough.
+    pub fn peek(&self, count: usize) -> Vec<&Card> {
+        self.draw_pile.iter().rev().take(count).collect()
+    }
+
+    /// Draw `count` cards from the top of the deck.
+    ///
+    /// If there are not enough cards left this fails without drawing any: reshuffle first.
+    pub fn draw(&mut self, count: usize) -> Result<Vec<Card>> {
+        if count > self.draw_pile.len() {
+            return Err(format!(
+                "Cannot draw {count} cards when there are only {} left",
+                self.draw_pile.len()
+            )
+            .into());
+        }
+        let cards: Vec<Card> = self
+            .draw_pile
+            .drain(self.draw_pile.len() - count..)
+            .rev()
+            .collect();
+        self.drawn.extend(cards.iter().cloned());
+        Ok(cards)
+    }
+
+    /// Move a drawn card to the discard pile.
+    pub fn discard(&mut self, card: &Card) -> Result<()> {
+        let index = self
+            .drawn
+            .iter()
+            .position(|c| c == card)
+            .ok_or_else(|| format!("{card} has not been drawn"))?;
+        self.discard_pile.push(self.drawn.remove(index));
+        Ok(())
+    }
+
+    /// Move all drawn cards to the discard pile.
+    pub fn discard_all(&mut self) {
+        self.discard_pile.append(&mut self.drawn);
+    }
+
+    /// Shuffle the discard pile back into the deck. Drawn cards which have not been discarded stay out.
+    pub fn reshuffle(&mut self, rng: &mut dyn DiceRollSource) {
+        self.draw_pile.append(&mut self.discard_pile);
+        self.shuffle(rng);
+    }
+
+    /// Shuffle the cards left to draw.
+    pub fn shuffle(&mut self, rng: &mut dyn DiceRollSource) {
+        // Fisher–Yates
+        for i in (1..self.draw_pile.len()).rev() {
+            let j = rng.roll_single_die(i as u64 + 1) as usize - 1;
+            self.draw_pile.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::IteratorDiceRollSource;
+
+    fn unshuffled(names: &[&str]) -> Deck {
+        // Always swapping with the card being placed leaves the order unchanged.
+        let mut rolls = (1..names.len() as u64).rev().map(|i| i + 1);
+        Deck::custom(
+            names.iter().rev().copied(),
+            &mut IteratorDiceRollSource {
+                iterator: &mut rolls,
+            },
+        )
+    }
+
+    #[test]
+    fn draw_discard_reshuffle() {
+        let mut deck = unshuffled(&["a", "b", "c", "d"]);
+        assert_eq!(deck.remaining(), 4);
+        assert_eq!(
+            deck.peek(2),
+            vec![&Card::Custom("a".into()), &Card::Custom("b".into())]
+        );
+        let drawn = deck.draw(3).unwrap();
+        assert_eq!(
+            drawn.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+            ["a", "b", "c"]
+        );
+        assert_eq!(deck.remaining(), 1);
+        assert_eq!(
+            deck.draw(2).unwrap_err(),
+            "Cannot draw 2 cards when there are only 1 left".into()
+        );
+
+        deck.discard(&Card::Custom("b".into())).unwrap();
+        assert!(deck.discard(&Card::Custom("b".into())).is_err());
+        assert_eq!(deck.drawn().len(), 2);
+        assert_eq!(deck.discarded().len(), 1);
+
+        deck.reshuffle(&mut IteratorDiceRollSource {
+            iterator: &mut [1].into_iter(),
+        });
+        assert_eq!(deck.remaining(), 2);
+        assert_eq!(
+            deck.peek(5),
+            vec![&Card::Custom("d".into()), &Card::Custom("b".into())]
+        );
+        assert_eq!(deck.drawn().len(), 2);
+        deck.discard_all();
+        assert_eq!(deck.discarded().len(), 2);
+    }
+
+    #[test]
+    fn standard_decks() {
+        let standard = Card::standard_deck();
+        assert_eq!(standard.len(), 54);
+        assert_eq!(standard[0].to_string(), "2♣");
+        assert_eq!(standard[51].to_string(), "A♠");
+        assert_eq!(standard[53].to_string(), "Red Joker");
+
+        let tarot = Card::tarot_deck();
+        assert_eq!(tarot.len(), 78);
+        assert_eq!(tarot[21].to_string(), "The World");
+        assert_eq!(tarot[22].to_string(), "Ace of Wands");
+        assert_eq!(tarot[77].to_string(), "King of Pentacles");
+    }
+
+    #[test]
+    fn shuffle_uses_every_card() {
+        let mut deck = Deck::standard(&mut IteratorDiceRollSource {
+            iterator: &mut (1..54).rev().map(|i| i / 2 + 1),
+        });
+        let mut cards = deck.draw(54).unwrap();
+        assert_ne!(cards, Card::standard_deck());
+        cards.sort_by_key(|c| c.to_string());
+        let mut expected = Card::standard_deck();
+        expected.sort_by_key(|c| c.to_string());
+        assert_eq!(cards, expected);
+    }
+
+    #[test]
+    fn serialize() {
+        let mut deck = unshuffled(&["a", "b", "c"]);
+        deck.draw(1).unwrap();
+        let json = serde_json::to_string(&deck).unwrap();
+        let restored: Deck = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, deck);
+    }
+}
@@ -1,7 +1,7 @@
 //! Implementation of [Expression] for the `dice` rule in the grammar.
 
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, HashSet},
     fmt::{Debug, Display},
     str::FromStr,
 };
@@ -9,13 +9,15 @@ use std::{
 use pest::iterators::{Pair, Pairs};
 
 use crate::{
-    DiceRollSource, Result, RollError, Rollable,
-    dice_kind::{DiceKind, Roll, basic::BasicDice, fudge::Fudge},
+    DiceRollSource, KeptDie, Number, Result, RollError, Rollable,
+    compiled::{Odds, Op},
+    dice_kind::{DiceKind, Roll, basic::BasicDice, digits::DigitDice, fudge::Fudge},
     expression::{
-        EvaluatedExpression, Expression, ExpressionResult, ExpressionRollable, FancyFormat,
-        Verbosity,
+        BinaryOp, EvaluatedExpression, Expression, ExpressionResult, ExpressionRollable,
+        FancyFormat, MaxDiceRollSource, Verbosity,
     },
-    keep_or_drop::KeepOrDrop,
+    keep_or_drop::{Comparison, KeepOrDrop},
+    ore::Sets,
     parser::Rule,
 };
 
@@ -160,7 +162,7 @@ enum RollModifier<Roll> {
 /// A modifier that can be applied to a RollBatch
 #[derive(Debug, Clone, Copy)]
 enum RollBatchModifier<Roll> {
-    KeepOrDrop(KeepOrDrop),
+    KeepOrDrop(KeepOrDrop<Roll>),
     PerRollModifier(PerRollModifier<Roll>),
 }
 
@@ -175,13 +177,17 @@ impl<TRoll: Roll> Display for RollBatchModifier<TRoll> {
     }
 }
 
-impl Display for KeepOrDrop {
+impl<V: Display> Display for KeepOrDrop<V> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             KeepOrDrop::KeepHi(u) => write!(f, "K{u}"),
             KeepOrDrop::KeepLo(u) => write!(f, "k{u}"),
             KeepOrDrop::DropHi(u) => write!(f, "D{u}"),
             KeepOrDrop::DropLo(u) => write!(f, "d{u}"),
+            KeepOrDrop::Keep(comparison, v) => write!(f, "k{comparison}{v}"),
+            KeepOrDrop::Drop(comparison, v) => write!(f, "d{comparison}{v}"),
+            KeepOrDrop::KeepUnique => f.write_str("ku"),
+            KeepOrDrop::DropDuplicates => f.write_str("dd"),
         }
     }
 }
@@ -217,8 +223,7 @@ impl<TRoll: Roll> PerRollModifier<TRoll> {
         roll: TRoll,
         rng: &mut dyn DiceRollSource,
     ) -> Result<ModifiedRoll<TRoll>> {
-        let max = dice.max();
-        let min = dice.min();
+        self.check(dice.min(), dice.max())?;
         let modifier = match self {
             PerRollModifier::RerollOnce(n) => {
                 if roll <= *n {
@@ -228,12 +233,6 @@ impl<TRoll: Roll> PerRollModifier<TRoll> {
                 }
             }
             PerRollModifier::RerollUnlimited(n) => {
-                if *n >= max {
-                    // TODO: catch this during parse
-                    return Err(RollError::ParamError(format!(
-                        "Cannot infinitely reroll dice of {n} or lower since the maximum roll is {max}: this would go on forever"
-                    )));
-                }
                 let new_rolls = roll_until(dice, roll, |next| next > *n, rng)?;
                 if !new_rolls.is_empty() {
                     RollModifier::Reroll(new_rolls)
@@ -249,12 +248,6 @@ impl<TRoll: Roll> PerRollModifier<TRoll> {
                 }
             }
             PerRollModifier::ExplodeUnlimited(n) => {
-                if *n <= dice.min() {
-                    // TODO: catch this during parse
-                    return Err(RollError::ParamError(format!(
-                        "Cannot infinitely explode dice of {n} or higher since the minimum roll is {min}: this would go on forever"
-                    )));
-                }
                 let new_rolls = roll_until(dice, roll, |next| next < *n, rng)?;
                 if !new_rolls.is_empty() {
                     RollModifier::Explode(new_rolls)
@@ -269,6 +262,133 @@ impl<TRoll: Roll> PerRollModifier<TRoll> {
             before: roll,
         })
     }
+
+    /// Errors if this would reroll or explode forever on dice rolling `min` to `max`.
+    fn check(&self, min: TRoll, max: TRoll) -> Result<()> {
+        match self {
+            // TODO: catch these during parse
+            PerRollModifier::RerollUnlimited(n) if *n >= max => {
+                Err(RollError::ParamError(format!(
+                    "Cannot infinitely reroll dice of {n} or lower since the maximum roll is {max}: this would go on forever"
+                )))
+            }
+            PerRollModifier::ExplodeUnlimited(n) if *n <= min => {
+                Err(RollError::ParamError(format!(
+                    "Cannot infinitely explode dice of {n} or higher since the minimum roll is {min}: this would go on forever"
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// If this rerolls or explodes a die which rolled `roll`.
+    fn applies_to(&self, roll: TRoll) -> bool {
+        match *self {
+            PerRollModifier::RerollOnce(n) | PerRollModifier::RerollUnlimited(n) => roll <= n,
+            PerRollModifier::ExplodeOnce(n) | PerRollModifier::ExplodeUnlimited(n) => roll >= n,
+        }
+    }
+
+    /// Like [PerRollModifier::apply] once checked, but only passes the resulting rolls to `out`,
+    /// rolling new dice with `roll_die`.
+    fn apply_to(
+        &self,
+        roll: TRoll,
+        mut roll_die: impl FnMut() -> TRoll,
+        mut out: impl FnMut(TRoll),
+    ) -> Result<()> {
+        match *self {
+            PerRollModifier::RerollOnce(_) => out(match self.applies_to(roll) {
+                true => roll_die(),
+                false => roll,
+            }),
+            PerRollModifier::RerollUnlimited(_) => {
+                let mut roll = roll;
+                let mut rerolls = 0;
+                while self.applies_to(roll) {
+                    limit_dice(rerolls, "rerolls")?;
+                    roll = roll_die();
+                    rerolls += 1;
+                }
+                out(roll);
+            }
+            PerRollModifier::ExplodeOnce(_) => {
+                out(roll);
+                if self.applies_to(roll) {
+                    out(roll_die());
+                }
+            }
+            PerRollModifier::ExplodeUnlimited(_) => {
+                out(roll);
+                let mut roll = roll;
+                let mut explosions = 0;
+                while self.applies_to(roll) {
+                    limit_dice(explosions, "rerolls")?;
+                    roll = roll_die();
+                    out(roll);
+                    explosions += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The same modifier, with its value converted by `f`.
+    fn map<V>(self, f: impl Fn(TRoll) -> V) -> PerRollModifier<V> {
+        match self {
+            PerRollModifier::RerollOnce(n) => PerRollModifier::RerollOnce(f(n)),
+            PerRollModifier::RerollUnlimited(n) => PerRollModifier::RerollUnlimited(f(n)),
+            PerRollModifier::ExplodeOnce(n) => PerRollModifier::ExplodeOnce(f(n)),
+            PerRollModifier::ExplodeUnlimited(n) => PerRollModifier::ExplodeUnlimited(f(n)),
+        }
+    }
+}
+
+impl<TRoll: Roll> RollBatchModifier<TRoll> {
+    /// The same modifier, with its values converted by `f`.
+    fn map<V: Ord + Copy>(self, f: impl Fn(TRoll) -> V) -> RollBatchModifier<V> {
+        match self {
+            RollBatchModifier::KeepOrDrop(op) => RollBatchModifier::KeepOrDrop(op.map(f)),
+            RollBatchModifier::PerRollModifier(op) => RollBatchModifier::PerRollModifier(op.map(f)),
+        }
+    }
+}
+
+/// Roll `number_of_dice` with `roll_die` and apply `modifiers` to total the `score` of each die, as for
+/// [ExpressionRollable::roll_total] but with `rolls` and `next` as scratch space reused between calls.
+///
+/// Dice are kept or dropped from sorted rolls, as their order does not change the total.
+fn batch_total<TRoll: Roll>(
+    number_of_dice: usize,
+    modifiers: &[RollBatchModifier<TRoll>],
+    (min, max): (TRoll, TRoll),
+    mut roll_die: impl FnMut() -> TRoll,
+    score: impl Fn(TRoll) -> i64,
+    rolls: &mut Vec<TRoll>,
+    next: &mut Vec<TRoll>,
+) -> Result<i64> {
+    if modifiers.is_empty() {
+        return Ok((0..number_of_dice).fold(0, |sum, _| sum + score(roll_die())));
+    }
+    rolls.clear();
+    rolls.extend((0..number_of_dice).map(|_| roll_die()));
+    for modifier in modifiers {
+        match modifier {
+            RollBatchModifier::KeepOrDrop(op) => op.apply_in_place(rolls)?,
+            RollBatchModifier::PerRollModifier(op) => {
+                if !rolls.is_empty() {
+                    op.check(min, max)?;
+                }
+                next.clear();
+                for roll in rolls.iter() {
+                    op.apply_to(*roll, &mut roll_die, |roll| next.push(roll))?;
+                }
+                std::mem::swap(rolls, next);
+            }
+        }
+        limit_dice(rolls.len(), "batch aggregation")?;
+    }
+    Ok(rolls.iter().map(|roll| score(*roll)).sum())
 }
 
 /// Rolls until end_condition is true for a roll value.
@@ -292,7 +412,10 @@ fn roll_until<Dice: DiceKind>(
 }
 
 impl<Dice: DiceKind + Clone> RollBatch<Dice> {
-    pub fn keep_or_drop(&self, op: KeepOrDrop) -> Result<ModifiedRollBatch<Dice::Roll>> {
+    pub fn keep_or_drop(
+        &self,
+        op: KeepOrDrop<Dice::Roll>,
+    ) -> Result<ModifiedRollBatch<Dice::Roll>> {
         let rolls = op.apply(&self.rolls, |d| *d)?;
 
         Ok(ModifiedRollBatch {
@@ -317,6 +440,8 @@ struct RollSpec<Dice: DiceKind> {
     number_of_dice: usize,
     modifiers: Vec<RollBatchModifier<Dice::Roll>>,
     aggregator: Aggregator<Dice::Roll>,
+    /// Named results computed from the final dice, alongside the total.
+    outputs: Vec<DiceOutput<Dice::Roll>>,
 }
 
 impl<Dice: DiceKind> FancyFormat for RollSpec<Dice> {
@@ -355,10 +480,26 @@ impl<Dice: DiceKind> FancyFormat for RollSpec<Dice> {
                         .join(", ")
                 )
             }
+            Aggregator::Sets { hard, wiggle } => {
+                let mut s = " sets".to_string();
+                if *hard > 0 {
+                    s += &format!(" hd{hard}");
+                }
+                if *wiggle > 0 {
+                    s += &format!(" wd{wiggle}");
+                }
+                s
+            }
             Aggregator::Sum => "".to_string(),
         };
+        let outputs = self
+            .outputs
+            .iter()
+            .map(|o| format!(" {o}"))
+            .collect::<Vec<_>>()
+            .join("");
         format!(
-            "{}d{}{modifiers}{aggregator}",
+            "{}d{}{modifiers}{aggregator}{outputs}",
             self.number_of_dice, self.dice,
         )
     }
@@ -366,10 +507,238 @@ impl<Dice: DiceKind> FancyFormat for RollSpec<Dice> {
 
 impl<Dice: DiceKind> ExpressionRollable for RollSpec<Dice> {
     fn expression_roll(&self, rng: &mut dyn DiceRollSource) -> ExpressionResult {
-        let x = self.dyn_roll(rng)?;
+        let x = self.dyn_roll(rng, false)?;
         let boxed: Box<dyn EvaluatedExpression> = Box::new(x);
         Ok(boxed)
     }
+
+    fn expression_roll_max(&self) -> ExpressionResult {
+        Ok(Box::new(self.dyn_roll(&mut MaxDiceRollSource, true)?))
+    }
+
+    fn roll_total(&self, rng: &mut dyn DiceRollSource) -> Result<Number> {
+        if let Aggregator::Sets { .. } = self.aggregator {
+            return Ok(self.dyn_roll(rng, false)?.total.into());
+        }
+        let total = batch_total(
+            self.number_of_dice,
+            &self.modifiers,
+            (self.dice.min(), self.dice.max()),
+            || self.dice.roll(rng),
+            |roll| self.aggregator.apply_single(roll),
+            &mut vec![],
+            &mut vec![],
+        )?;
+        Ok(total.into())
+    }
+
+    fn compile(&self, ops: &mut Vec<Op>) -> bool {
+        let Some((sides, offset)) = self.dice.as_single_die() else {
+            return false;
+        };
+        // Sets need the dice themselves, and errors from modifiers show their values
+        let valid = self.modifiers.iter().all(|modifier| match modifier {
+            RollBatchModifier::PerRollModifier(op) => {
+                op.check(self.dice.min(), self.dice.max()).is_ok()
+            }
+            RollBatchModifier::KeepOrDrop(_) => true,
+        });
+        // Modified dice are counted per face
+        if !valid || (!self.modifiers.is_empty() && sides > MAX_COUNTED_SIDES) {
+            return false;
+        }
+        let scores = match &self.aggregator {
+            Aggregator::Sum => vec![],
+            Aggregator::Sets { .. } => return false,
+            // Too many faces to score each one
+            _ if sides > 1000 => return false,
+            aggregator => {
+                let aggregator = aggregator.map(Into::into);
+                (1..=sides as i64)
+                    .map(|face| aggregator.apply_single(face + offset))
+                    .collect()
+            }
+        };
+        ops.push(Op::Dice(CompiledDice {
+            sides,
+            offset,
+            number_of_dice: self.number_of_dice,
+            modifiers: self.modifiers.iter().map(|m| m.map(Into::into)).collect(),
+            scores,
+        }));
+        true
+    }
+}
+
+/// Most faces of dice with modifiers for [CompiledDice], which counts how many dice rolled each face.
+const MAX_COUNTED_SIDES: u64 = 100;
+
+/// A [RollSpec] compiled into a [crate::compiled::Program], rolling each die as one [DiceRollSource::roll_single_die]
+/// of `sides` plus `offset` with integer values.
+///
+/// Modifiers apply to how many dice rolled each face rather than to a list of rolls, as their order does not
+/// change the total.
+#[derive(Debug)]
+pub(crate) struct CompiledDice {
+    sides: u64,
+    offset: i64,
+    number_of_dice: usize,
+    modifiers: Vec<RollBatchModifier<i64>>,
+    /// The score of each face when not summing them, as looking it up is faster than checking targets.
+    scores: Vec<i64>,
+}
+
+impl CompiledDice {
+    /// The value of a die rolling the `face`th face, from 0.
+    fn value(&self, face: usize) -> i64 {
+        face as i64 + self.offset + 1
+    }
+
+    fn score(&self, face: usize) -> i64 {
+        match self.scores.is_empty() {
+            true => self.value(face),
+            false => self.scores[face],
+        }
+    }
+
+    /// The total of dice rolling each face `counts[face]` times.
+    fn total_of(&self, counts: &[usize]) -> i64 {
+        counts
+            .iter()
+            .enumerate()
+            .map(|(face, count)| *count as i64 * self.score(face))
+            .sum()
+    }
+
+    /// The odds of each total, unless working them out takes more than `budget` steps.
+    ///
+    /// Dice which may be rolled again are always rolled, as they have no end to their outcomes.
+    pub(crate) fn odds(&self, budget: usize) -> Option<Odds> {
+        if self.modifiers.is_empty() {
+            // Each die adds its score regardless of the others, so they can be added one at a time
+            if self.sides > budget as u64 {
+                return None;
+            }
+            let die = Odds::new((0..self.sides as usize).map(|face| (self.score(face).into(), 1)))?;
+            let mut odds = Odds::new([(0.into(), 1)])?;
+            for _ in 0..self.number_of_dice {
+                odds = odds.apply(BinaryOp::Add, &die, budget)?;
+            }
+            return Some(odds);
+        }
+        let rolled_again = self
+            .modifiers
+            .iter()
+            .any(|modifier| matches!(modifier, RollBatchModifier::PerRollModifier(_)));
+        let outcomes = u32::try_from(self.number_of_dice)
+            .ok()
+            .and_then(|n| self.sides.checked_pow(n));
+        // Each step is a way to roll the dice regardless of their order
+        let steps = binomial(
+            self.number_of_dice as u64 + self.sides - 1,
+            self.number_of_dice as u64,
+        );
+        if rolled_again
+            || outcomes.is_none()
+            || self.sides > MAX_COUNTED_SIDES
+            || steps > budget as u128
+        {
+            return None;
+        }
+        let mut totals = BTreeMap::new();
+        let mut counts = vec![0; self.sides as usize];
+        // Keeping or dropping too many dice fails for every outcome, and is reported when rolling
+        self.tabulate_from(0, self.number_of_dice, 1, &mut counts, &mut totals)
+            .ok()?;
+        Odds::new(totals.into_iter().map(|(total, ways)| (total.into(), ways)))
+    }
+
+    /// Count the outcomes of each total in `totals`, for `counts` of the faces before `face`, with `left` dice
+    /// rolling the others, in `ways` orders.
+    fn tabulate_from(
+        &self,
+        face: usize,
+        left: usize,
+        ways: u64,
+        counts: &mut [usize],
+        totals: &mut BTreeMap<i64, u64>,
+    ) -> Result<()> {
+        if face + 1 == counts.len() {
+            counts[face] = left;
+            let mut kept = counts.to_vec();
+            for modifier in &self.modifiers {
+                if let RollBatchModifier::KeepOrDrop(op) = modifier {
+                    op.apply_to_counts(|face| self.value(face), &mut kept)?;
+                }
+            }
+            *totals.entry(self.total_of(&kept)).or_default() += ways;
+            return Ok(());
+        }
+        // The number of ways to pick which of the `left` dice roll this face
+        let mut orders: u64 = 1;
+        for count in 0..=left {
+            counts[face] = count;
+            self.tabulate_from(face + 1, left - count, ways * orders, counts, totals)?;
+            orders = (u128::from(orders) * (left - count) as u128 / (count + 1) as u128) as u64;
+        }
+        Ok(())
+    }
+
+    /// Roll the total, with `counts` and `next` as scratch space reused between calls.
+    pub(crate) fn roll_total(
+        &self,
+        rng: &mut impl DiceRollSource,
+        counts: &mut Vec<usize>,
+        next: &mut Vec<usize>,
+    ) -> Result<i64> {
+        let sides = self.sides;
+        let mut roll_face = || rng.roll_single_die(sides) as usize - 1;
+        if self.modifiers.is_empty() {
+            return Ok((0..self.number_of_dice).fold(0, |total, _| total + self.score(roll_face())));
+        }
+        counts.clear();
+        counts.resize(sides as usize, 0);
+        for _ in 0..self.number_of_dice {
+            counts[roll_face()] += 1;
+        }
+        for modifier in &self.modifiers {
+            let len = match modifier {
+                RollBatchModifier::KeepOrDrop(op) => {
+                    op.apply_to_counts(|face| self.value(face), counts)?
+                }
+                RollBatchModifier::PerRollModifier(op) => {
+                    next.clear();
+                    next.resize(sides as usize, 0);
+                    for (face, count) in counts.iter().enumerate() {
+                        if !op.applies_to(self.value(face)) {
+                            next[face] += count;
+                            continue;
+                        }
+                        for _ in 0..*count {
+                            op.apply_to(
+                                self.value(face),
+                                || self.value(roll_face()),
+                                |roll| next[(roll - self.value(0)) as usize] += 1,
+                            )?;
+                        }
+                    }
+                    std::mem::swap(counts, next);
+                    counts.iter().sum()
+                }
+            };
+            limit_dice(len, "batch aggregation")?;
+        }
+        Ok(self.total_of(counts))
+    }
+}
+
+/// The number of ways to pick `k` of `n` things, saturating.
+fn binomial(n: u64, k: u64) -> u128 {
+    (0..k.min(n - k))
+        .try_fold(1u128, |ways, i| {
+            Some(ways.checked_mul(u128::from(n - i))? / u128::from(i + 1))
+        })
+        .unwrap_or(u128::MAX)
 }
 
 // Arbitrary limits to avoid OOM and hangs
@@ -387,7 +756,12 @@ pub(crate) fn limit_dice(number_of_dice: usize, during: &str) -> Result<()> {
 }
 
 impl<Dice: DiceKind> RollSpec<Dice> {
-    fn dyn_roll(&self, rng: &mut dyn DiceRollSource) -> Result<EvaluatedRollSpec<Dice>> {
+    /// Roll the dice, or with `maximize` take the maximum of each without exploding or rerolling.
+    fn dyn_roll(
+        &self,
+        rng: &mut dyn DiceRollSource,
+        maximize: bool,
+    ) -> Result<EvaluatedRollSpec<Dice>> {
         let mut rolls = RollBatch {
             rolls: (0..self.number_of_dice)
                 .map(|_| self.dice.roll(rng))
@@ -397,17 +771,53 @@ impl<Dice: DiceKind> RollSpec<Dice> {
 
         let mut history: History<Dice::Roll> = vec![];
 
-        for modifier in &self.modifiers {
+        let modifiers = self.modifiers.iter().filter(|m| {
+            // Maximized dice would explode forever
+            !(maximize && matches!(m, RollBatchModifier::PerRollModifier(_)))
+        });
+        for modifier in modifiers {
             let next = ModifiedRollBatch::new(&rolls, *modifier, rng)?;
             rolls.rolls = next.after();
             limit_dice(rolls.rolls.len(), "batch aggregation")?;
             history.push((*modifier, next));
         }
 
+        let sets = match self.aggregator {
+            Aggregator::Sets { hard, wiggle } => {
+                limit_dice(
+                    rolls
+                        .rolls
+                        .len()
+                        .checked_add(hard)
+                        .and_then(|n| n.checked_add(wiggle))
+                        .unwrap_or(usize::MAX),
+                    "sets",
+                )?;
+                let hard = vec![self.dice.max(); hard];
+                let sets = Sets::new(rolls.rolls.iter().chain(&hard).copied());
+                let wiggle = sets.wiggle_values(wiggle, self.dice.max());
+                Some(EvaluatedSets {
+                    sets: sets.with_wiggle(wiggle.len(), self.dice.max()),
+                    hard,
+                    wiggle,
+                })
+            }
+            _ => None,
+        };
+
         Ok(EvaluatedRollSpec {
-            total: self.aggregator.total(&rolls.rolls),
+            total: match &sets {
+                Some(sets) => sets.sets.sets().len() as i64,
+                None => self.aggregator.total(&rolls.rolls),
+            },
+            outputs: self
+                .outputs
+                .iter()
+                .map(|o| (o.name(), o.apply(self.dice, &rolls.rolls)))
+                .collect(),
             history,
             final_rolls: rolls,
+            sets,
         })
     }
 }
@@ -416,7 +826,7 @@ impl<Dice: DiceKind> Rollable for RollSpec<Dice> {
     type Roll = Result<EvaluatedRollSpec<Dice>>;
 
     fn roll_with_source(&self, rng: &mut dyn DiceRollSource) -> Result<EvaluatedRollSpec<Dice>> {
-        self.dyn_roll(rng)
+        self.dyn_roll(rng, false)
     }
 }
 
@@ -431,14 +841,35 @@ struct EvaluatedRollSpec<Dice: DiceKind> {
     ///
     /// Same as `.after()` for last entry in history (when history is not empty).
     final_rolls: RollBatch<Dice>,
+    /// Sets the final dice were grouped into, for the sets aggregator.
+    sets: Option<EvaluatedSets<Dice::Roll>>,
+    outputs: Vec<(String, Number)>,
 }
 
-impl<Dice: DiceKind> EvaluatedExpression for EvaluatedRollSpec<Dice> {
-    fn total(&self) -> f64 {
-        self.total as f64
+/// Result of [Aggregator::Sets], with the values of the extra dice it added.
+#[derive(Debug)]
+struct EvaluatedSets<TRoll> {
+    sets: Sets<TRoll>,
+    hard: Vec<TRoll>,
+    wiggle: Vec<TRoll>,
+}
+
+impl<TRoll: Roll> EvaluatedSets<TRoll> {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        let mut s = String::new();
+        if !self.hard.is_empty() {
+            s += &format!(" hd{}", format_rolls(self.hard.iter(), markdown));
+        }
+        if !self.wiggle.is_empty() {
+            s += &format!(" wd{}", format_rolls(self.wiggle.iter(), markdown));
+        }
+        format!("{s} 🡲 {}", self.sets.format(markdown, verbose))
     }
+}
 
-    fn format_history(&self, markdown: bool, verbose: Verbosity) -> String {
+impl<Dice: DiceKind> EvaluatedRollSpec<Dice> {
+    /// The rolled dice, through each modifier.
+    fn format_rolls(&self, markdown: bool, verbose: Verbosity) -> String {
         if let Some(first) = self.history.first() {
             if matches!(verbose, Verbosity::Short) {
                 let original = first.1.rolls.iter().map(|m| m.before);
@@ -468,7 +899,38 @@ impl<Dice: DiceKind> EvaluatedExpression for EvaluatedRollSpec<Dice> {
     }
 }
 
-fn format_rolls<I: Iterator>(rolls: I, markdown: bool) -> String
+impl<Dice: DiceKind> EvaluatedExpression for EvaluatedRollSpec<Dice> {
+    fn total(&self) -> Number {
+        self.total.into()
+    }
+
+    fn format_history(&self, markdown: bool, verbose: Verbosity) -> String {
+        let rolls = self.format_rolls(markdown, verbose);
+        match &self.sets {
+            Some(sets) => rolls + &sets.format(markdown, verbose),
+            None => rolls,
+        }
+    }
+
+    fn dice(&self) -> Vec<KeptDie> {
+        let (min, max) = (self.final_rolls.dice.min(), self.final_rolls.dice.max());
+        self.final_rolls
+            .rolls
+            .iter()
+            .map(|roll| KeptDie {
+                value: (*roll).into(),
+                min: min.into(),
+                max: max.into(),
+            })
+            .collect()
+    }
+
+    fn outputs(&self) -> Vec<(String, Number)> {
+        self.outputs.clone()
+    }
+}
+
+pub(crate) fn format_rolls<I: Iterator>(rolls: I, markdown: bool) -> String
 where
     I::Item: Display,
 {
@@ -499,6 +961,12 @@ enum Aggregator<TRoll> {
     TargetFailureDouble(Option<TRoll>, Option<TRoll>, Option<TRoll>),
     // List of specific values which count as success
     TargetEnum(HashSet<TRoll>),
+    /// Group matching dice into sets (as in the One-Roll Engine), counting the sets.
+    /// Adds `hard` dice showing the maximum, and `wiggle` dice set to the best value.
+    Sets {
+        hard: usize,
+        wiggle: usize,
+    },
     Sum,
 }
 
@@ -534,9 +1002,81 @@ impl<TRoll: Roll> Aggregator<TRoll> {
                     0
                 }
             }
+            Aggregator::Sets { .. } => unreachable!("sets are aggregated from all the dice"),
             Aggregator::Sum => Into::<i64>::into(roll),
         }
     }
+
+    /// The same aggregator, with its values converted by `f`.
+    fn map<V: Roll>(&self, f: impl Fn(TRoll) -> V) -> Aggregator<V> {
+        match self {
+            Aggregator::TargetFailureDouble(target, failure, double) => {
+                Aggregator::TargetFailureDouble(target.map(&f), failure.map(&f), double.map(&f))
+            }
+            Aggregator::TargetEnum(items) => {
+                Aggregator::TargetEnum(items.iter().map(|item| f(*item)).collect())
+            }
+            Aggregator::Sets { hard, wiggle } => Aggregator::Sets {
+                hard: *hard,
+                wiggle: *wiggle,
+            },
+            Aggregator::Sum => Aggregator::Sum,
+        }
+    }
+}
+
+/// A named result computed from the final dice of a [RollSpec], alongside its total.
+#[derive(Clone, Copy, Debug)]
+enum DiceOutput<TRoll> {
+    /// Number of dice showing this value, like Shadowrun's 1s.
+    Count(TRoll),
+    /// Hero System BODY: 0 for the lowest roll, 2 for the highest, and 1 otherwise.
+    Body,
+}
+
+impl<TRoll: Roll> DiceOutput<TRoll> {
+    fn name(&self) -> String {
+        match self {
+            DiceOutput::Count(value) => format!("{value}s"),
+            DiceOutput::Body => "BODY".to_string(),
+        }
+    }
+
+    fn apply<Dice: DiceKind<Roll = TRoll>>(&self, dice: Dice, rolls: &[TRoll]) -> Number {
+        let value = match self {
+            DiceOutput::Count(value) => rolls.iter().filter(|r| *r == value).count() as i64,
+            DiceOutput::Body => rolls
+                .iter()
+                .map(|r| match r {
+                    r if *r == dice.min() => 0,
+                    r if *r == dice.max() => 2,
+                    _ => 1,
+                })
+                .sum(),
+        };
+        value.into()
+    }
+}
+
+impl<TRoll: Roll> Display for DiceOutput<TRoll> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiceOutput::Count(value) => write!(f, "c{value}"),
+            DiceOutput::Body => write!(f, "body"),
+        }
+    }
+}
+
+/// `number_of_dice` dice of `sides`, summed.
+pub(crate) fn basic_dice(number_of_dice: usize, sides: BasicDice) -> Result<Expression> {
+    limit_dice(number_of_dice, "parse")?;
+    Ok(Expression::new(RollSpec {
+        dice: sides,
+        number_of_dice,
+        modifiers: vec![],
+        aggregator: Aggregator::Sum,
+        outputs: vec![],
+    }))
 }
 
 pub(crate) fn parse_dice<Dice: DiceKind>(mut dice: Pairs<Rule>) -> Result<Expression> {
@@ -558,6 +1098,9 @@ pub(crate) fn parse_dice<Dice: DiceKind>(mut dice: Pairs<Rule>) -> Result<Expres
             parse_dice_inner::<BasicDice>(pair.as_str().parse::<BasicDice>()?, number_of_dice, dice)
         }
         Rule::fudge => parse_dice_inner::<Fudge>(Fudge, number_of_dice, dice),
+        Rule::digit_dice => {
+            parse_dice_inner::<DigitDice>(pair.as_str().parse::<DigitDice>()?, number_of_dice, dice)
+        }
         _ => unreachable!("{:?}", pair),
     }
 }
@@ -588,6 +1131,7 @@ where
     let mut modifiers: Vec<RollBatchModifier<Dice::Roll>> = vec![];
 
     let mut aggregator: Aggregator<Dice::Roll> = Aggregator::Sum;
+    let mut outputs = vec![];
     let mut next_option = dice.next();
 
     while next_option.is_some() {
@@ -634,6 +1178,29 @@ where
                 let value = extract_option_value::<usize>(option)?.unwrap();
                 modifiers.push(RollBatchModifier::KeepOrDrop(KeepOrDrop::DropLo(value)));
             }
+            Rule::keep_filter | Rule::drop_filter => {
+                let keep = option.as_rule() == Rule::keep_filter;
+                let mut inner = option.into_inner();
+                let comparison = match inner.next().unwrap().as_str() {
+                    "=" => Comparison::Equal,
+                    "<" => Comparison::Less,
+                    "<=" => Comparison::LessOrEqual,
+                    ">" => Comparison::Greater,
+                    _ => Comparison::GreaterOrEqual,
+                };
+                let value = inner.next().unwrap().as_str().parse::<Dice::Roll>()?;
+                modifiers.push(RollBatchModifier::KeepOrDrop(if keep {
+                    KeepOrDrop::Keep(comparison, value)
+                } else {
+                    KeepOrDrop::Drop(comparison, value)
+                }));
+            }
+            Rule::keep_unique => {
+                modifiers.push(RollBatchModifier::KeepOrDrop(KeepOrDrop::KeepUnique))
+            }
+            Rule::drop_duplicates => {
+                modifiers.push(RollBatchModifier::KeepOrDrop(KeepOrDrop::DropDuplicates))
+            }
             Rule::target => {
                 let value_or_enum = option.into_inner().next().unwrap();
                 match value_or_enum.as_rule() {
@@ -677,6 +1244,25 @@ where
                 };
                 aggregator = Aggregator::TargetFailureDouble(target, Some(value), double_target)
             }
+            Rule::sets => {
+                let mut hard = 0;
+                let mut wiggle = 0;
+                for pair in option.into_inner() {
+                    let value = extract_option_value::<usize>(pair.clone())?.unwrap();
+                    limit_dice(value, "parse")?;
+                    match pair.as_rule() {
+                        Rule::hard_dice => hard = value,
+                        Rule::wiggle_dice => wiggle = value,
+                        _ => unreachable!("{:#?}", pair),
+                    }
+                }
+                aggregator = Aggregator::Sets { hard, wiggle };
+            }
+            Rule::count => {
+                let value = extract_option_value(option)?.unwrap();
+                outputs.push(DiceOutput::Count(value));
+            }
+            Rule::body => outputs.push(DiceOutput::Body),
             _ => unreachable!("{:#?}", option),
         }
 
@@ -688,6 +1274,7 @@ where
         number_of_dice,
         modifiers,
         aggregator,
+        outputs,
     }))
 }
 
@@ -706,6 +1293,7 @@ mod tests {
             number_of_dice: 2,
             modifiers: vec![],
             aggregator: Aggregator::Sum,
+            outputs: vec![],
         };
         let result = spec
             .roll_with_source(&mut IteratorDiceRollSource {
@@ -722,6 +1310,7 @@ mod tests {
             number_of_dice: 4,
             modifiers: vec![RollBatchModifier::KeepOrDrop(KeepOrDrop::KeepHi(2))],
             aggregator: Aggregator::Sum,
+            outputs: vec![],
         };
         let result = spec
             .roll_with_source(&mut IteratorDiceRollSource {
@@ -742,6 +1331,7 @@ mod tests {
                 RollBatchModifier::KeepOrDrop(KeepOrDrop::DropLo(1)),
             ],
             aggregator: Aggregator::Sum,
+            outputs: vec![],
         };
         let result = spec
             .roll_with_source(&mut IteratorDiceRollSource {
@@ -774,4 +1364,48 @@ mod tests {
             "\\[~~*1*~~, ~~*2*~~, 3, 4\\]K2 🡲 \\[**3**&#x200B;🡵5, **4**&#x200B;🡵6\\]e1 🡲 \\[~~*3*~~, 5, 4, 6\\]d1 🡲 \\[5, 4, 6\\]"
         );
     }
+
+    #[test]
+    fn filters() {
+        let roll = |expression: &str| {
+            let expression = Expression::parse(expression).unwrap();
+            let rolls = [1, 4, 1, 6, 4, 3];
+            let total = expression
+                .roll_total(&mut IteratorDiceRollSource {
+                    iterator: &mut rolls.into_iter(),
+                })
+                .unwrap();
+            let result = expression
+                .roll_with_source(&mut IteratorDiceRollSource {
+                    iterator: &mut rolls.into_iter(),
+                })
+                .unwrap();
+            assert_eq!(result.total(), total);
+            (
+                format!("{expression}"),
+                result.format(false, Verbosity::Medium),
+            )
+        };
+        assert_eq!(
+            roll("6d6 d=1"),
+            (
+                "6d6 d=1".to_string(),
+                "[Drop(1), 4, Drop(1), 6, 4, 3]d=1 = 17".to_string()
+            )
+        );
+        assert_eq!(
+            roll("6d6 k >= 4 t5").1,
+            "[Drop(1), 4, Drop(1), 6, 4, Drop(3)]k>=4 = 1"
+        );
+        assert_eq!(
+            roll("6d6 ku").1,
+            "[Drop(1), Drop(4), Drop(1), 6, Drop(4), 3]ku = 9"
+        );
+        assert_eq!(roll("6d6 dd").1, "[1, 4, Drop(1), 6, Drop(4), 3]dd = 14");
+        // Filters combine with the other options, in order
+        assert_eq!(
+            roll("6d6 d<2 K2").1,
+            "[Drop(1), 4, Drop(1), 6, 4, 3]d<2 🡲 [4, 6, Drop(4), Drop(3)]K2 = 10"
+        );
+    }
 }
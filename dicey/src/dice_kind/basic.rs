@@ -23,4 +23,7 @@ impl DiceKind for BasicDice {
     fn min(&self) -> Self::Roll {
         1
     }
+    fn as_single_die(&self) -> Option<(u64, i64)> {
+        Some((self.get().into(), 0))
+    }
 }
@@ -0,0 +1,75 @@
+use std::{
+    fmt::{self, Display},
+    num::IntErrorKind,
+    str::FromStr,
+};
+
+use crate::{
+    DiceRollSource,
+    dice_kind::{DiceKind, ParseDiceError},
+};
+
+/// Dice like d66 or d666, where each die gives one digit of the result instead of being summed.
+///
+/// Written `dd66` (`1dd66`), as `d66` is a single 66 sided die.
+/// Each digit of `sides` is the number of sides of the die for that digit,
+/// so `dd66` rolls two d6 for 11 to 66, and `dd36` rolls a d3 for the tens and a d6 for the ones.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct DigitDice {
+    /// Always between 1 and 9 digits, none of them 0.
+    sides: u32,
+}
+
+/// Parses the digits part, with or without the leading `d`: `d66` or `66`.
+impl FromStr for DigitDice {
+    type Err = ParseDiceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.strip_prefix(['d', 'D']).unwrap_or(s);
+        let kind = if digits.is_empty() {
+            IntErrorKind::Empty
+        } else if digits.len() > 9 {
+            IntErrorKind::PosOverflow
+        } else if digits.bytes().any(|d| d == b'0') {
+            IntErrorKind::Zero
+        } else {
+            return Ok(DigitDice {
+                sides: digits.parse::<u32>()?,
+            });
+        };
+        Err(ParseDiceError { kind })
+    }
+}
+
+impl Display for DigitDice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "d{}", self.sides)
+    }
+}
+
+impl DigitDice {
+    /// Sides of the die for each digit, most significant first.
+    fn digits(&self) -> impl Iterator<Item = u32> {
+        self.sides
+            .to_string()
+            .into_bytes()
+            .into_iter()
+            .map(|d| u32::from(d - b'0'))
+    }
+}
+
+impl DiceKind for DigitDice {
+    type Roll = u32;
+
+    fn roll(&self, rng: &mut dyn DiceRollSource) -> Self::Roll {
+        self.digits().fold(0, |value, sides| {
+            value * 10 + rng.roll_single_die(sides.into()) as u32
+        })
+    }
+    fn max(&self) -> Self::Roll {
+        self.sides
+    }
+    fn min(&self) -> Self::Roll {
+        self.digits().fold(0, |value, _| value * 10 + 1)
+    }
+}
@@ -108,4 +108,7 @@ impl DiceKind for Fudge {
     fn min(&self) -> Self::Roll {
         FudgeRoll { value: -1 }
     }
+    fn as_single_die(&self) -> Option<(u64, i64)> {
+        Some((3, -2))
+    }
 }
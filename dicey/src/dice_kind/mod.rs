@@ -20,6 +20,11 @@ pub(crate) trait DiceKind:
     fn roll(&self, rng: &mut dyn DiceRollSource) -> Self::Roll;
     fn max(&self) -> Self::Roll;
     fn min(&self) -> Self::Roll;
+
+    /// `(sides, offset)` if each roll is a single die of `sides` plus `offset`, as its [Roll] converts to an integer.
+    fn as_single_die(&self) -> Option<(u64, i64)> {
+        None
+    }
 }
 
 pub(crate) trait Roll:
@@ -27,6 +32,9 @@ pub(crate) trait Roll:
 {
 }
 
+/// Values of dice compiled for fast totals.
+impl Roll for i64 {}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct ParseDiceError {
     kind: IntErrorKind,
@@ -75,4 +83,6 @@ impl From<ParseFloatError> for RollError {
 
 // Implementations of DiceKind
 pub(crate) mod basic;
+pub(crate) mod digits;
 pub(crate) mod fudge;
+pub(crate) mod narrative;
@@ -0,0 +1,213 @@
+use std::{
+    fmt::{self, Display},
+    num::IntErrorKind,
+    str::FromStr,
+};
+
+use crate::{
+    DiceRollSource,
+    dice_kind::{DiceKind, ParseDiceError, Roll},
+};
+
+/// A Genesys (and Star Wars FFG) narrative die, whose faces show symbols instead of numbers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum NarrativeDie {
+    Ability,
+    Proficiency,
+    Boost,
+    Difficulty,
+    Challenge,
+    Setback,
+    Force,
+}
+
+/// Symbols on one face of a [NarrativeDie].
+///
+/// Triumph also counts as a success, and despair as a failure.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Face {
+    pub(crate) success: u8,
+    pub(crate) advantage: u8,
+    pub(crate) triumph: u8,
+    pub(crate) failure: u8,
+    pub(crate) threat: u8,
+    pub(crate) despair: u8,
+    pub(crate) light: u8,
+    pub(crate) dark: u8,
+}
+
+impl NarrativeDie {
+    pub(crate) const ALL: [NarrativeDie; 7] = [
+        NarrativeDie::Ability,
+        NarrativeDie::Proficiency,
+        NarrativeDie::Boost,
+        NarrativeDie::Difficulty,
+        NarrativeDie::Challenge,
+        NarrativeDie::Setback,
+        NarrativeDie::Force,
+    ];
+
+    /// Letter used for this die in pool notation.
+    fn letter(&self) -> char {
+        match self {
+            NarrativeDie::Ability => 'A',
+            NarrativeDie::Proficiency => 'P',
+            NarrativeDie::Boost => 'B',
+            NarrativeDie::Difficulty => 'D',
+            NarrativeDie::Challenge => 'C',
+            NarrativeDie::Setback => 'S',
+            NarrativeDie::Force => 'F',
+        }
+    }
+
+    /// Faces of the die, as symbol letters (see [Face]'s [FromStr]).
+    fn faces(&self) -> &'static [&'static str] {
+        match self {
+            NarrativeDie::Ability => &["", "S", "S", "SS", "A", "A", "SA", "AA"],
+            NarrativeDie::Proficiency => &[
+                "", "S", "S", "SS", "SS", "A", "SA", "SA", "SA", "AA", "AA", "T",
+            ],
+            NarrativeDie::Boost => &["", "", "S", "SA", "AA", "A"],
+            NarrativeDie::Difficulty => &["", "F", "FF", "H", "H", "H", "HH", "FH"],
+            NarrativeDie::Challenge => &[
+                "", "F", "F", "FF", "FF", "H", "H", "FH", "FH", "HH", "HH", "D",
+            ],
+            NarrativeDie::Setback => &["", "", "F", "F", "H", "H"],
+            NarrativeDie::Force => &[
+                "B", "B", "B", "B", "B", "B", "BB", "W", "W", "WW", "WW", "WW",
+            ],
+        }
+    }
+
+    fn face(&self, index: usize) -> Face {
+        self.faces()[index].parse().unwrap()
+    }
+}
+
+impl FromStr for NarrativeDie {
+    type Err = ParseDiceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => NarrativeDie::ALL
+                .into_iter()
+                .find(|die| die.letter() == c.to_ascii_uppercase())
+                .ok_or(ParseDiceError {
+                    kind: IntErrorKind::InvalidDigit,
+                }),
+            _ => Err(ParseDiceError {
+                kind: IntErrorKind::InvalidDigit,
+            }),
+        }
+    }
+}
+
+impl Display for NarrativeDie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.letter())
+    }
+}
+
+impl DiceKind for NarrativeDie {
+    type Roll = Face;
+
+    fn roll(&self, rng: &mut dyn DiceRollSource) -> Self::Roll {
+        let sides = self.faces().len();
+        self.face(rng.roll_single_die(sides as u64) as usize - 1)
+    }
+    fn max(&self) -> Self::Roll {
+        self.faces()
+            .iter()
+            .map(|f| f.parse::<Face>().unwrap())
+            .max_by_key(|f| i64::from(*f))
+            .unwrap()
+    }
+    fn min(&self) -> Self::Roll {
+        self.faces()
+            .iter()
+            .map(|f| f.parse::<Face>().unwrap())
+            .min_by_key(|f| i64::from(*f))
+            .unwrap()
+    }
+}
+
+impl Face {
+    /// Symbols on this face, in display order, with their letter and markdown glyph.
+    fn symbols(&self) -> impl Iterator<Item = (char, &'static str)> {
+        [
+            (self.triumph, 'T', "☀"),
+            (self.success, 'S', "✹"),
+            (self.advantage, 'A', "⬆"),
+            (self.despair, 'D', "☠"),
+            (self.failure, 'F', "✖"),
+            (self.threat, 'H', "⬇"),
+            (self.light, 'W', "○"),
+            (self.dark, 'B', "●"),
+        ]
+        .into_iter()
+        .flat_map(|(count, letter, glyph)| std::iter::repeat_n((letter, glyph), count.into()))
+    }
+
+    /// The symbols as letters, or as glyphs for markdown. Blank faces are `-`.
+    pub(crate) fn format(&self, markdown: bool) -> String {
+        let s: String = self
+            .symbols()
+            .map(|(letter, glyph)| {
+                if markdown {
+                    glyph.to_string()
+                } else {
+                    letter.to_string()
+                }
+            })
+            .collect();
+        if s.is_empty() { "-".to_string() } else { s }
+    }
+}
+
+/// Letters: `S` success, `A` advantage, `T` triumph, `F` failure, `H` threat, `D` despair,
+/// `W` light side and `B` dark side. `-` or nothing is a blank face.
+impl FromStr for Face {
+    type Err = ParseDiceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut face = Face::default();
+        for c in s.chars() {
+            let count = match c.to_ascii_uppercase() {
+                'S' => &mut face.success,
+                'A' => &mut face.advantage,
+                'T' => &mut face.triumph,
+                'F' => &mut face.failure,
+                'H' => &mut face.threat,
+                'D' => &mut face.despair,
+                'W' => &mut face.light,
+                'B' => &mut face.dark,
+                '-' => continue,
+                _ => {
+                    return Err(ParseDiceError {
+                        kind: IntErrorKind::InvalidDigit,
+                    });
+                }
+            };
+            *count += 1;
+        }
+        Ok(face)
+    }
+}
+
+impl Display for Face {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.format(false))
+    }
+}
+
+/// Net successes on this face.
+impl From<Face> for i64 {
+    fn from(face: Face) -> Self {
+        i64::from(face.success) + i64::from(face.triumph)
+            - i64::from(face.failure)
+            - i64::from(face.despair)
+    }
+}
+
+impl Roll for Face {}
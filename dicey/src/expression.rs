@@ -1,4 +1,5 @@
 use std::{
+    cell::RefCell,
     collections::HashMap,
     fmt::{Debug, Display},
     rc::Rc,
@@ -7,10 +8,16 @@ use std::{
 use pest::iterators::{Pair, Pairs};
 
 use crate::{
-    DiceRollSource, Result, RollError, Rollable,
+    Advantage, Bag, DiceRollSource, KeptDie, Number, Result, RollError, Rollable,
+    bag::parse_pull,
+    compiled::Op,
     dice_expression::parse_dice,
     dice_kind::basic::BasicDice,
+    narrative::parse_narrative_pool,
     parser::{Rule, climb},
+    step::parse_stepped_dice,
+    systems::{cthulhu, daggerheart::DualityDice, ironsworn, savage_worlds},
+    tag::Tagged,
 };
 
 /// A parsed dice expression.
@@ -34,6 +41,28 @@ pub type ExpressionResult = Result<Box<dyn EvaluatedExpression>>;
 pub(crate) trait ExpressionRollable: Debug + FancyFormat {
     /// Evaluate and roll the dice with provided dice roll source
     fn expression_roll(&self, rng: &mut dyn DiceRollSource) -> ExpressionResult;
+
+    /// Evaluate and roll the dice, computing only the total.
+    ///
+    /// Must consume `rng` the same way as [ExpressionRollable::expression_roll] and produce the same total,
+    /// but skips building the history needed for formatting.
+    fn roll_total(&self, rng: &mut dyn DiceRollSource) -> Result<Number>;
+
+    /// Evaluate with every die rolling its maximum, and without exploding or rerolling dice.
+    fn expression_roll_max(&self) -> ExpressionResult {
+        self.expression_roll(&mut MaxDiceRollSource)
+    }
+
+    /// The bag, if this is a variable holding a [Bag].
+    fn bag(&self) -> Option<&Rc<RefCell<Bag>>> {
+        None
+    }
+
+    /// Push operations computing the total onto `ops` as [ExpressionRollable::roll_total] would,
+    /// or return false to have the whole expression rolled that way.
+    fn compile(&self, _ops: &mut Vec<Op>) -> bool {
+        false
+    }
 }
 
 impl Rollable for Expression {
@@ -45,14 +74,59 @@ impl Rollable for Expression {
     }
 }
 
+/// Rolls every die as its maximum value.
+pub(crate) struct MaxDiceRollSource;
+
+impl DiceRollSource for MaxDiceRollSource {
+    fn roll_single_die(&mut self, sides: u64) -> u64 {
+        sides
+    }
+}
+
 impl Expression {
+    /// Evaluate with every die rolling its maximum, as for a critical hit.
+    pub(crate) fn roll_max(&self) -> ExpressionResult {
+        self.0.expression_roll_max()
+    }
+
     pub(crate) fn new<T: ExpressionRollable + 'static>(expression: T) -> Expression {
         Expression(Rc::new(expression))
     }
+
+    /// `left + right`
+    pub(crate) fn add(left: Expression, right: Expression) -> Expression {
+        Expression::new(BinaryExpression {
+            left,
+            op: BinaryOp::Add,
+            right,
+        })
+    }
+
+    /// `(inner)`
+    pub(crate) fn block(inner: Expression) -> Expression {
+        Expression::new(BlockExpression { inner })
+    }
+
+    /// Roll the expression, computing only the total.
+    pub(crate) fn roll_total(&self, rng: &mut dyn DiceRollSource) -> Result<Number> {
+        self.0.roll_total(rng)
+    }
+
+    /// The bag, if this expression holds a [Bag].
+    pub(crate) fn as_bag(&self) -> Option<&Rc<RefCell<Bag>>> {
+        self.0.bag()
+    }
+
+    /// Push operations computing the total onto `ops`, falling back to rolling parts which can't be compiled.
+    pub(crate) fn compile(&self, ops: &mut Vec<Op>) {
+        if !self.0.compile(ops) {
+            ops.push(Op::Expression(self.clone()));
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
-enum BinaryOp {
+pub(crate) enum BinaryOp {
     Add,
     Sub,
     Mul,
@@ -60,7 +134,7 @@ enum BinaryOp {
 }
 
 impl BinaryOp {
-    fn apply(&self, left: f64, right: f64) -> f64 {
+    pub(crate) fn apply(&self, left: Number, right: Number) -> Number {
         match self {
             BinaryOp::Add => left + right,
             BinaryOp::Sub => left - right,
@@ -124,10 +198,31 @@ impl ExpressionRollable for BinaryExpression<Expression> {
             right,
         }))
     }
+
+    fn expression_roll_max(&self) -> ExpressionResult {
+        Ok(Box::new(BinaryExpression {
+            left: self.left.roll_max()?,
+            op: self.op,
+            right: self.right.roll_max()?,
+        }))
+    }
+
+    fn roll_total(&self, rng: &mut dyn DiceRollSource) -> Result<Number> {
+        let left = self.left.roll_total(rng)?;
+        let right = self.right.roll_total(rng)?;
+        Ok(self.op.apply(left, right))
+    }
+
+    fn compile(&self, ops: &mut Vec<Op>) -> bool {
+        self.left.compile(ops);
+        self.right.compile(ops);
+        ops.push(Op::Binary(self.op));
+        true
+    }
 }
 
 impl EvaluatedExpression for BinaryExpression<Box<dyn EvaluatedExpression>> {
-    fn total(&self) -> f64 {
+    fn total(&self) -> Number {
         self.op.apply(self.left.total(), self.right.total())
     }
 
@@ -138,6 +233,47 @@ impl EvaluatedExpression for BinaryExpression<Box<dyn EvaluatedExpression>> {
             markdown,
         )
     }
+
+    fn dice(&self) -> Vec<KeptDie> {
+        let mut dice = self.left.dice();
+        dice.extend(self.right.dice());
+        dice
+    }
+
+    fn outputs(&self) -> Vec<(String, Number)> {
+        // Counts of what was rolled, whatever the operator
+        merge_outputs(self.left.outputs(), self.right.outputs())
+    }
+
+    fn tags(&self) -> Vec<(String, Number)> {
+        let (left, right) = (self.left.tags(), self.right.tags());
+        let scale = |tags: Vec<(String, Number)>, by: Number, tagged_left: bool| {
+            tags.into_iter()
+                .map(|(tag, total)| {
+                    let total = if tagged_left {
+                        self.op.apply(total, by)
+                    } else {
+                        self.op.apply(by, total)
+                    };
+                    (tag, total)
+                })
+                .collect()
+        };
+        match self.op {
+            BinaryOp::Add => merge_outputs(left, right),
+            BinaryOp::Sub => merge_outputs(
+                left,
+                right
+                    .into_iter()
+                    .map(|(tag, total)| (tag, -total))
+                    .collect(),
+            ),
+            // Scale the tagged side by the untagged side, as a product of tagged parts has no one tag
+            _ if right.is_empty() => scale(left, self.right.total(), true),
+            BinaryOp::Mul if left.is_empty() => scale(right, self.left.total(), false),
+            _ => vec![],
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -146,12 +282,21 @@ struct RollableFloat(f64);
 
 impl ExpressionRollable for RollableFloat {
     fn expression_roll(&self, _rng: &mut dyn DiceRollSource) -> ExpressionResult {
-        Ok(Box::new(RollabledNumber(self.0)))
+        Ok(Box::new(RollabledNumber(self.0.into())))
+    }
+
+    fn roll_total(&self, _rng: &mut dyn DiceRollSource) -> Result<Number> {
+        Ok(self.0.into())
+    }
+
+    fn compile(&self, ops: &mut Vec<Op>) -> bool {
+        ops.push(Op::Number(self.0.into()));
+        true
     }
 }
 
 #[derive(Debug, Clone)]
-struct RollabledNumber(f64);
+struct RollabledNumber(Number);
 
 impl FancyFormat for RollableFloat {
     fn format(&self, _markdown: bool, _verbose: Verbosity) -> String {
@@ -165,7 +310,7 @@ impl FancyFormat for RollableFloat {
 }
 
 impl EvaluatedExpression for RollabledNumber {
-    fn total(&self) -> f64 {
+    fn total(&self) -> Number {
         self.0
     }
 
@@ -176,7 +321,16 @@ impl EvaluatedExpression for RollabledNumber {
 
 impl ExpressionRollable for i64 {
     fn expression_roll(&self, _rng: &mut dyn DiceRollSource) -> ExpressionResult {
-        Ok(Box::new(RollabledNumber(*self as f64)))
+        Ok(Box::new(RollabledNumber((*self).into())))
+    }
+
+    fn roll_total(&self, _rng: &mut dyn DiceRollSource) -> Result<Number> {
+        Ok((*self).into())
+    }
+
+    fn compile(&self, ops: &mut Vec<Op>) -> bool {
+        ops.push(Op::Number((*self).into()));
+        true
     }
 }
 
@@ -204,16 +358,43 @@ impl ExpressionRollable for BlockExpression<Expression> {
             inner: self.inner.roll_with_source(rng)?,
         }))
     }
+
+    fn expression_roll_max(&self) -> ExpressionResult {
+        Ok(Box::new(BlockExpression {
+            inner: self.inner.roll_max()?,
+        }))
+    }
+
+    fn roll_total(&self, rng: &mut dyn DiceRollSource) -> Result<Number> {
+        self.inner.roll_total(rng)
+    }
+
+    fn compile(&self, ops: &mut Vec<Op>) -> bool {
+        self.inner.compile(ops);
+        true
+    }
 }
 
 impl EvaluatedExpression for BlockExpression<Box<dyn EvaluatedExpression>> {
-    fn total(&self) -> f64 {
+    fn total(&self) -> Number {
         self.inner.total()
     }
 
     fn format_history(&self, markdown: bool, verbose: Verbosity) -> String {
         format!("({})", self.inner.format_history(markdown, verbose))
     }
+
+    fn dice(&self) -> Vec<KeptDie> {
+        self.inner.dice()
+    }
+
+    fn outputs(&self) -> Vec<(String, Number)> {
+        self.inner.outputs()
+    }
+
+    fn tags(&self) -> Vec<(String, Number)> {
+        self.inner.tags()
+    }
 }
 
 #[derive(Debug)]
@@ -235,6 +416,22 @@ impl ExpressionRollable for VariableReference {
             identifier: self.identifier.clone(),
         }))
     }
+
+    fn expression_roll_max(&self) -> ExpressionResult {
+        Ok(Box::new(VariableReferenceRolled {
+            inner: self.inner.roll_max()?,
+            identifier: self.identifier.clone(),
+        }))
+    }
+
+    fn roll_total(&self, rng: &mut dyn DiceRollSource) -> Result<Number> {
+        self.inner.roll_total(rng)
+    }
+
+    fn compile(&self, ops: &mut Vec<Op>) -> bool {
+        self.inner.compile(ops);
+        true
+    }
 }
 
 impl FancyFormat for VariableReference {
@@ -253,7 +450,7 @@ impl FancyFormat for VariableReference {
 }
 
 impl EvaluatedExpression for VariableReferenceRolled<Box<dyn EvaluatedExpression>> {
-    fn total(&self) -> f64 {
+    fn total(&self) -> Number {
         self.inner.total()
     }
 
@@ -268,6 +465,18 @@ impl EvaluatedExpression for VariableReferenceRolled<Box<dyn EvaluatedExpression
             )
         }
     }
+
+    fn dice(&self) -> Vec<KeptDie> {
+        self.inner.dice()
+    }
+
+    fn outputs(&self) -> Vec<(String, Number)> {
+        self.inner.outputs()
+    }
+
+    fn tags(&self) -> Vec<(String, Number)> {
+        self.inner.tags()
+    }
 }
 
 /// Formatter with adjustable verbosity and support for markdown.
@@ -279,11 +488,50 @@ pub trait FancyFormat {
 /// Result of evaluating an [Expression].
 pub trait EvaluatedExpression: Debug {
     /// Numeric result.
-    /// Unless division or floats are involved, this will be an integer.
-    fn total(&self) -> f64;
+    /// This is exact unless float literals are involved, and is an integer unless division or floats are involved.
+    fn total(&self) -> Number;
 
     /// Pretty print the rolls and adjustments to them which produced the result.
     fn format_history(&self, markdown: bool, verbose: Verbosity) -> String;
+
+    /// The dice which were kept in the result, after dropping, rerolling and exploding.
+    fn dice(&self) -> Vec<KeptDie> {
+        vec![]
+    }
+
+    /// Named results computed from the same dice as the total, like a count of 1s or BODY damage.
+    ///
+    /// Outputs with the same name from different parts of an expression are summed.
+    fn outputs(&self) -> Vec<(String, Number)> {
+        vec![]
+    }
+
+    /// Parts of the total from tagged leaves, like `1d8[fire]`, summed by tag.
+    fn tags(&self) -> Vec<(String, Number)> {
+        vec![]
+    }
+}
+
+/// Combine named outputs, summing those with the same name and keeping the order they first appear in.
+pub(crate) fn merge_outputs(
+    mut left: Vec<(String, Number)>,
+    right: Vec<(String, Number)>,
+) -> Vec<(String, Number)> {
+    for (name, value) in right {
+        match left.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, total)) => *total = *total + value,
+            None => left.push((name, value)),
+        }
+    }
+    left
+}
+
+/// Like `, 1s: **2**` for each output.
+pub(crate) fn format_outputs(outputs: &[(String, Number)], markdown: bool) -> String {
+    outputs
+        .iter()
+        .map(|(name, value)| format!(", {name}: {}", format_bold(value, markdown)))
+        .collect()
 }
 
 impl<T: EvaluatedExpression + ?Sized> FancyFormat for T {
@@ -291,7 +539,8 @@ impl<T: EvaluatedExpression + ?Sized> FancyFormat for T {
     fn format(&self, markdown: bool, verbose: Verbosity) -> String {
         let history = self.format_history(markdown, verbose);
         let total = format_bold(self.total(), markdown);
-        format!("{history} = {total}",)
+        let outputs = format_outputs(&self.outputs(), markdown);
+        format!("{history} = {total}{outputs}",)
     }
 }
 
@@ -314,11 +563,22 @@ pub(crate) fn format_bold<V: Display>(value: V, markdown: bool) -> String {
     }
 }
 
+pub(crate) fn format_italic<V: Display>(value: V, markdown: bool) -> String {
+    if markdown {
+        format!("*{value}*")
+    } else {
+        format!("{value}")
+    }
+}
+
 pub(crate) fn parse_expression(
     expr: Pairs<Rule>,
     variables: &HashMap<String, Expression>,
 ) -> Result<Expression> {
     let _ = variables;
+    if expr.clone().count() > 1 && expr.clone().any(|pair| savage_worlds::is_trait(&pair)) {
+        return Err("sw() can't be combined with other terms, use mod= instead".into());
+    }
     climb(
         expr,
         |pair: Pair<Rule>| {
@@ -337,6 +597,7 @@ pub(crate) fn parse_expression(
                     let expr = pair.into_inner();
                     parse_dice::<BasicDice>(expr)?
                 }
+                Rule::stepped_dice => parse_stepped_dice(pair.into_inner())?,
                 Rule::variable => {
                     let identifier = pair.into_inner().as_str();
                     match variables.get(identifier) {
@@ -351,6 +612,52 @@ pub(crate) fn parse_expression(
                         }
                     }
                 }
+                Rule::bag_pull => {
+                    let mut inner = pair.into_inner();
+                    let remove = inner.next().unwrap().as_rule() == Rule::take;
+                    let identifier = inner.next().unwrap().into_inner().as_str();
+                    parse_pull(identifier, remove, variables)?
+                }
+                Rule::duality => Expression::new(DualityDice {
+                    advantage: match pair.into_inner().next().map(|p| p.as_rule()) {
+                        Some(Rule::adv) => Advantage::Advantage,
+                        Some(Rule::dis) => Advantage::Disadvantage,
+                        _ => Advantage::Normal,
+                    },
+                }),
+                Rule::cthulhu => {
+                    let mut inner = pair.into_inner();
+                    let skill = parse_expression(inner.next().unwrap().into_inner(), variables)?;
+                    let bonus = match inner.next() {
+                        Some(dice) => {
+                            let rule = dice.as_rule();
+                            let count = dice.into_inner().as_str().parse::<i64>()?;
+                            if rule == Rule::penalty { -count } else { count }
+                        }
+                        None => 0,
+                    };
+                    Expression::new(cthulhu::Check { skill, bonus })
+                }
+                Rule::sw => savage_worlds::parse_trait(pair.into_inner(), variables)?,
+                Rule::ironsworn => {
+                    let mut inner = pair.into_inner();
+                    let modifier = parse_expression(inner.next().unwrap().into_inner(), variables)?;
+                    let momentum = match inner.next() {
+                        Some(momentum) => Some(
+                            momentum
+                                .into_inner()
+                                .as_str()
+                                .replace(' ', "")
+                                .parse::<i64>()?,
+                        ),
+                        None => None,
+                    };
+                    Expression::new(ironsworn::Action {
+                        modifier: Some(modifier),
+                        momentum,
+                    })
+                }
+                Rule::narrative_pool => parse_narrative_pool(pair.into_inner())?,
                 _ => unreachable!("{:#?}", pair),
             })
         },
@@ -381,6 +688,12 @@ pub(crate) fn parse_expression(
             (Err(e), _) => Err(e),
             (_, Err(e)) => Err(e),
         },
+        |inner: Result<Expression>, tag: Pair<Rule>| {
+            Ok(Expression::new(Tagged {
+                inner: inner?,
+                tag: tag.into_inner().as_str().to_string(),
+            }))
+        },
     )
 }
 
@@ -437,6 +750,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn digit_dice() {
+        let spec = Expression::parse("2dd66 + d66").unwrap();
+        assert_eq!(format!("{spec}"), "2dd66 + 1d66");
+        let result = spec
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [3, 5, 6, 1, 40].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "[35, 61] + [40] = 136"
+        );
+        assert_eq!(result.dice()[0].min, 11);
+        assert_eq!(result.dice()[0].max, 66);
+
+        let spec = Expression::parse("dd36 e").unwrap();
+        let result = spec
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [3, 6, 1, 2].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "[36(Exploded)🡵12]e36 = 48"
+        );
+
+        assert!(Expression::parse("dd60").is_err());
+        assert!(Expression::parse("dd1234567891").is_err());
+    }
+
     #[test]
     fn mixed() {
         let spec = Expression::parse("2dF + 1d6").unwrap();
@@ -1,3 +1,9 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    ops::Range,
+};
+
 use crate::error::Result;
 
 /// Copy `v`, but with the top (as defined by `f`) `to_drop` entries flagged with false and the rest with true.
@@ -26,21 +32,65 @@ fn keep_low<T: Clone, Key: Ord + Copy>(
         .collect())
 }
 
-/// Number of dice to keep or drop.
+/// How a value is compared in a filter like `d=1` or `k>3`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub(crate) enum Comparison {
+    Equal,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn matches<V: Ord>(self, value: V, than: V) -> bool {
+        match self {
+            Comparison::Equal => value == than,
+            Comparison::Less => value < than,
+            Comparison::LessOrEqual => value <= than,
+            Comparison::Greater => value > than,
+            Comparison::GreaterOrEqual => value >= than,
+        }
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Comparison::Equal => "=",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+        })
+    }
+}
+
+/// Number of dice to keep or drop, or which values of `V` to keep or drop.
 #[derive(Copy, Clone, PartialEq, Debug)]
-pub(crate) enum KeepOrDrop {
+pub(crate) enum KeepOrDrop<V> {
     KeepHi(usize),
     KeepLo(usize),
     DropHi(usize),
     DropLo(usize),
+    /// Keep dice whose value matches, like `k>3`.
+    Keep(Comparison, V),
+    /// Drop dice whose value matches, like `d=1`.
+    Drop(Comparison, V),
+    /// Keep only values rolled once.
+    KeepUnique,
+    /// Drop all but the first of each value.
+    DropDuplicates,
 }
 
-impl KeepOrDrop {
-    pub fn apply<T: Clone, Key: Ord + Copy>(
-        &self,
-        v: &[T],
-        get_number: impl Fn(&T) -> Key,
-    ) -> Result<Vec<(bool, T)>> {
+impl<V: Ord + Copy> KeepOrDrop<V> {
+    pub fn apply<T: Clone>(&self, v: &[T], get_number: impl Fn(&T) -> V) -> Result<Vec<(bool, T)>> {
+        let filter = |keep: &dyn Fn(usize, V) -> bool| {
+            v.iter()
+                .enumerate()
+                .map(|(i, t)| (keep(i, get_number(t)), t.clone()))
+                .collect()
+        };
         let res = match self {
             KeepOrDrop::KeepHi(n) => {
                 keep_low(v, *n, |result| std::cmp::Reverse(get_number(result)))?
@@ -60,14 +110,136 @@ impl KeepOrDrop {
                 })?,
                 |result| std::cmp::Reverse(get_number(result)),
             )?,
+            KeepOrDrop::Keep(comparison, than) => {
+                filter(&|_, value| comparison.matches(value, *than))
+            }
+            KeepOrDrop::Drop(comparison, than) => {
+                filter(&|_, value| !comparison.matches(value, *than))
+            }
+            KeepOrDrop::KeepUnique => {
+                let mut counts: BTreeMap<V, usize> = BTreeMap::new();
+                for t in v {
+                    *counts.entry(get_number(t)).or_default() += 1;
+                }
+                filter(&|_, value| counts[&value] == 1)
+            }
+            KeepOrDrop::DropDuplicates => {
+                let mut seen = BTreeSet::new();
+                let first: Vec<bool> = v.iter().map(|t| seen.insert(get_number(t))).collect();
+                filter(&|i, _| first[i])
+            }
         };
         Ok(res)
     }
+
+    /// Like [KeepOrDrop::apply], but removing the dropped values from `values`, which may be reordered.
+    pub fn apply_in_place(&self, values: &mut Vec<V>) -> Result<()> {
+        if let Some(range) = self.sorted_range(values.len())? {
+            values.sort_unstable();
+            values.truncate(range.end);
+            values.drain(..range.start);
+            return Ok(());
+        }
+        match *self {
+            KeepOrDrop::Keep(comparison, than) => values.retain(|v| comparison.matches(*v, than)),
+            KeepOrDrop::Drop(comparison, than) => values.retain(|v| !comparison.matches(*v, than)),
+            KeepOrDrop::KeepUnique => {
+                values.sort_unstable();
+                let (mut read, mut kept) = (0, 0);
+                while read < values.len() {
+                    let end = read + values[read..].partition_point(|v| *v == values[read]);
+                    if end - read == 1 {
+                        values[kept] = values[read];
+                        kept += 1;
+                    }
+                    read = end;
+                }
+                values.truncate(kept);
+            }
+            KeepOrDrop::DropDuplicates => {
+                values.sort_unstable();
+                values.dedup();
+            }
+            _ => unreachable!("kept by rank"),
+        }
+        Ok(())
+    }
+
+    /// Like [KeepOrDrop::apply_in_place], but with `counts[i]` holding how many values equal `value(i)`,
+    /// which must be ascending. Returns how many values are kept.
+    pub fn apply_to_counts(
+        &self,
+        value: impl Fn(usize) -> V,
+        counts: &mut [usize],
+    ) -> Result<usize> {
+        if let Some(range) = self.sorted_range(counts.iter().sum())? {
+            let mut start = 0;
+            for count in counts.iter_mut() {
+                let end = start + *count;
+                *count = end.min(range.end).saturating_sub(start.max(range.start));
+                start = end;
+            }
+            return Ok(range.len());
+        }
+        for (i, count) in counts.iter_mut().enumerate() {
+            let kept = match *self {
+                KeepOrDrop::Keep(comparison, than) => comparison.matches(value(i), than),
+                KeepOrDrop::Drop(comparison, than) => !comparison.matches(value(i), than),
+                KeepOrDrop::KeepUnique => *count == 1,
+                KeepOrDrop::DropDuplicates => {
+                    *count = (*count).min(1);
+                    true
+                }
+                _ => unreachable!("kept by rank"),
+            };
+            if !kept {
+                *count = 0;
+            }
+        }
+        Ok(counts.iter().sum())
+    }
+
+    /// The same operation, with its value converted by `f`.
+    pub fn map<W>(self, f: impl Fn(V) -> W) -> KeepOrDrop<W> {
+        match self {
+            KeepOrDrop::KeepHi(n) => KeepOrDrop::KeepHi(n),
+            KeepOrDrop::KeepLo(n) => KeepOrDrop::KeepLo(n),
+            KeepOrDrop::DropHi(n) => KeepOrDrop::DropHi(n),
+            KeepOrDrop::DropLo(n) => KeepOrDrop::DropLo(n),
+            KeepOrDrop::Keep(comparison, than) => KeepOrDrop::Keep(comparison, f(than)),
+            KeepOrDrop::Drop(comparison, than) => KeepOrDrop::Drop(comparison, f(than)),
+            KeepOrDrop::KeepUnique => KeepOrDrop::KeepUnique,
+            KeepOrDrop::DropDuplicates => KeepOrDrop::DropDuplicates,
+        }
+    }
+
+    /// Range of entries kept by this operation when applied to `len` entries sorted from lowest to highest,
+    /// or [None] if it keeps or drops by value rather than by rank.
+    ///
+    /// Reports the same errors as [KeepOrDrop::apply].
+    pub fn sorted_range(&self, len: usize) -> Result<Option<Range<usize>>> {
+        let to_keep = match self {
+            KeepOrDrop::KeepHi(n) | KeepOrDrop::KeepLo(n) => {
+                if *n > len {
+                    return Err("Not enough dice to keep or drop".into());
+                }
+                *n
+            }
+            KeepOrDrop::DropHi(n) | KeepOrDrop::DropLo(n) => len
+                .checked_sub(*n)
+                .ok_or_else(|| format!("Cannot drop {n} dice when there are only {len}"))?,
+            _ => return Ok(None),
+        };
+        Ok(Some(match self {
+            KeepOrDrop::KeepLo(_) | KeepOrDrop::DropHi(_) => 0..to_keep,
+            _ => len - to_keep..len,
+        }))
+    }
 }
 
 #[cfg(test)]
 pub(crate) mod tests {
-    use super::keep_low;
+    use super::{Comparison, KeepOrDrop, keep_low};
 
     #[test]
     fn keep_low_test() {
@@ -89,4 +261,65 @@ pub(crate) mod tests {
         );
         assert_eq!(keep_low(&[4], 1, |x| *x).unwrap(), vec![(true, 4)]);
     }
+
+    #[test]
+    fn filters() {
+        let kept = |op: KeepOrDrop<i32>| -> Vec<bool> {
+            op.apply(&[1, 4, 1, 6, 4, 3], |x| *x)
+                .unwrap()
+                .into_iter()
+                .map(|(keep, _)| keep)
+                .collect()
+        };
+        assert_eq!(
+            kept(KeepOrDrop::Drop(Comparison::Equal, 1)),
+            [false, true, false, true, true, true]
+        );
+        assert_eq!(
+            kept(KeepOrDrop::Keep(Comparison::GreaterOrEqual, 4)),
+            [false, true, false, true, true, false]
+        );
+        assert_eq!(
+            kept(KeepOrDrop::KeepUnique),
+            [false, false, false, true, false, true]
+        );
+        assert_eq!(
+            kept(KeepOrDrop::DropDuplicates),
+            [true, true, false, true, false, true]
+        );
+        assert_eq!(KeepOrDrop::<i32>::KeepUnique.sorted_range(6).unwrap(), None);
+    }
+
+    #[test]
+    fn in_place() {
+        for op in [
+            KeepOrDrop::KeepHi(2),
+            KeepOrDrop::DropLo(1),
+            KeepOrDrop::Drop(Comparison::Equal, 1),
+            KeepOrDrop::Keep(Comparison::GreaterOrEqual, 4),
+            KeepOrDrop::KeepUnique,
+            KeepOrDrop::DropDuplicates,
+        ] {
+            let values = [1, 4, 1, 6, 4, 3];
+            let mut expected: Vec<i32> = op
+                .apply(&values, |x| *x)
+                .unwrap()
+                .into_iter()
+                .filter_map(|(keep, value)| keep.then_some(value))
+                .collect();
+            expected.sort();
+            let mut kept = values.to_vec();
+            op.apply_in_place(&mut kept).unwrap();
+            kept.sort();
+            assert_eq!(kept, expected, "{op:?}");
+
+            let mut counts = [2, 0, 1, 2, 0, 1];
+            let len = op.apply_to_counts(|i| i as i32 + 1, &mut counts).unwrap();
+            let counted: Vec<i32> = (0..6)
+                .flat_map(|i| std::iter::repeat_n(i as i32 + 1, counts[i]))
+                .collect();
+            assert_eq!(counted, expected, "{op:?}");
+            assert_eq!(len, expected.len());
+        }
+    }
 }
@@ -7,16 +7,41 @@
 mod dice_expression;
 mod expression;
 
+mod attack;
+mod bag;
+mod band;
+mod check;
 mod command;
+mod compiled;
+mod contest;
+mod deck;
 mod dice_kind;
 mod error;
 mod keep_or_drop;
+mod narrative;
+mod natural;
+mod number;
+mod ore;
 mod parser;
+mod step;
+pub mod systems;
+mod table;
+mod tag;
 mod variable;
 
 pub use expression::{EvaluatedExpression, Expression, FancyFormat, Verbosity};
 
+pub use attack::{Advantage, AttackOutcome, AttackTemplate, CritDamage, EvaluatedAttack};
+pub use bag::{Bag, EvaluatedPull, Token};
+pub use check::{Degree, Degrees, EvaluatedCheck};
 pub use command::{Command, EvaluatedCommand};
+pub use contest::{EvaluatedContest, TieBreak};
+pub use deck::{Card, Deck, JokerColor, Suit, TarotSuit};
+pub use natural::{KeptDie, NaturalRule};
+pub use number::{Number, Rounding};
+pub use ore::{Set, Sets};
+pub use step::{AboveTop, EvaluatedUsage, StepLadder, UsageDie};
+pub use table::{EvaluatedTable, Table, TableRoller, Tables};
 pub use variable::Variable;
 
 pub use error::*;
@@ -29,19 +54,35 @@ pub trait DiceRollSource {
     fn roll_single_die(&mut self, sides: u64) -> u64;
 }
 
-struct RngDiceRollSource<'a, T>
+/// A [DiceRollSource] using a random number generator.
+pub struct RngDiceRollSource<'a, T>
 where
     T: Rng,
 {
     rng: &'a mut T,
 }
 
+impl<'a, T> RngDiceRollSource<'a, T>
+where
+    T: Rng,
+{
+    /// Roll dice with `rng`.
+    pub fn new(rng: &'a mut T) -> Self {
+        RngDiceRollSource { rng }
+    }
+}
+
 impl<T> DiceRollSource for RngDiceRollSource<'_, T>
 where
     T: Rng,
 {
+    #[inline]
     fn roll_single_die(&mut self, sides: u64) -> u64 {
-        self.rng.random_range(1..1 + sides)
+        // Sampling a smaller range is faster, and dice rarely need more
+        match u32::try_from(sides) {
+            Ok(sides) => self.rng.random_range(1..=sides).into(),
+            Err(_) => self.rng.random_range(1..=sides),
+        }
     }
 }
 
@@ -219,6 +260,37 @@ mod tests {
         assert_eq!(-30.0, res.total().unwrap());
     }
 
+    #[test]
+    fn exact_division_test() {
+        let r = command::Command::parse("3d6 / 2 * 3 / 9").unwrap();
+        let res = r
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut vec![3, 6, 3].into_iter(),
+            })
+            .unwrap();
+        let total = res.total().unwrap();
+        assert!(total.is_exact());
+        assert_eq!(total.as_rational(), Some((2, 1)));
+        assert_eq!(total.as_integer(), Some(2));
+
+        let r = command::Command::parse("7 / 2").unwrap();
+        let total = r.roll().unwrap().total().unwrap();
+        assert_eq!(total.as_rational(), Some((7, 2)));
+        assert_eq!(total.to_integer(Rounding::Down), 3);
+        assert_eq!(
+            r.roll().unwrap().format(false, Verbosity::Short),
+            "7/2 = 3.5"
+        );
+    }
+
+    #[test]
+    fn float_literal_test() {
+        let r = command::Command::parse("7 * 1.5").unwrap();
+        let total = r.roll().unwrap().total().unwrap();
+        assert!(!total.is_exact());
+        assert_eq!(total, 10.5);
+    }
+
     #[test]
     fn float_add_test() {
         let r = command::Command::parse("20 + 1.5").unwrap();
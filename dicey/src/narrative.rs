@@ -0,0 +1,198 @@
+//! Genesys (and Star Wars FFG) narrative dice pools, like `genesys(2A P 2D C)`.
+
+use pest::iterators::Pairs;
+
+use crate::{
+    DiceRollSource, FancyFormat, Number, Result, Verbosity,
+    dice_expression::{format_rolls, limit_dice},
+    dice_kind::{
+        DiceKind,
+        narrative::{Face, NarrativeDie},
+    },
+    expression::{EvaluatedExpression, Expression, ExpressionResult, ExpressionRollable},
+    parser::Rule,
+};
+
+/// A pool of narrative dice, each kind in the order given.
+#[derive(Debug)]
+struct NarrativePool {
+    dice: Vec<(NarrativeDie, usize)>,
+}
+
+impl FancyFormat for NarrativePool {
+    fn format(&self, _markdown: bool, _verbose: Verbosity) -> String {
+        let dice: Vec<String> = self
+            .dice
+            .iter()
+            .map(|(die, count)| format!("{count}{die}"))
+            .collect();
+        format!("genesys({})", dice.join(" "))
+    }
+}
+
+impl NarrativePool {
+    fn roll_faces(&self, rng: &mut dyn DiceRollSource) -> Vec<(NarrativeDie, Vec<Face>)> {
+        self.dice
+            .iter()
+            .map(|(die, count)| (*die, (0..*count).map(|_| die.roll(rng)).collect()))
+            .collect()
+    }
+}
+
+impl ExpressionRollable for NarrativePool {
+    fn expression_roll(&self, rng: &mut dyn DiceRollSource) -> ExpressionResult {
+        Ok(Box::new(EvaluatedNarrativePool {
+            rolls: self.roll_faces(rng),
+        }))
+    }
+
+    fn roll_total(&self, rng: &mut dyn DiceRollSource) -> Result<Number> {
+        Ok(EvaluatedNarrativePool {
+            rolls: self.roll_faces(rng),
+        }
+        .total())
+    }
+}
+
+/// Rolled faces of a [NarrativePool].
+///
+/// The total is net successes (failures if negative), with advantage and threat cancelled
+/// into a single `advantage` output.
+#[derive(Debug)]
+struct EvaluatedNarrativePool {
+    rolls: Vec<(NarrativeDie, Vec<Face>)>,
+}
+
+impl EvaluatedNarrativePool {
+    fn faces(&self) -> impl Iterator<Item = &Face> {
+        self.rolls.iter().flat_map(|(_, faces)| faces)
+    }
+
+    fn count(&self, symbol: impl Fn(&Face) -> u8) -> i64 {
+        self.faces().map(|face| i64::from(symbol(face))).sum()
+    }
+}
+
+impl EvaluatedExpression for EvaluatedNarrativePool {
+    fn total(&self) -> Number {
+        self.faces()
+            .map(|face| i64::from(*face))
+            .sum::<i64>()
+            .into()
+    }
+
+    fn format_history(&self, markdown: bool, _verbose: Verbosity) -> String {
+        self.rolls
+            .iter()
+            .map(|(die, faces)| {
+                let faces = faces.iter().map(|face| face.format(markdown));
+                format!("{die}{}", format_rolls(faces, markdown))
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn outputs(&self) -> Vec<(String, Number)> {
+        let mut outputs = vec![
+            (
+                "advantage".to_string(),
+                (self.count(|f| f.advantage) - self.count(|f| f.threat)).into(),
+            ),
+            ("triumph".to_string(), self.count(|f| f.triumph).into()),
+            ("despair".to_string(), self.count(|f| f.despair).into()),
+        ];
+        if self
+            .rolls
+            .iter()
+            .any(|(die, _)| *die == NarrativeDie::Force)
+        {
+            outputs.push(("light".to_string(), self.count(|f| f.light).into()));
+            outputs.push(("dark".to_string(), self.count(|f| f.dark).into()));
+        }
+        outputs
+    }
+}
+
+/// Parse the dice in `genesys(2A P 2D C)`.
+pub(crate) fn parse_narrative_pool(pairs: Pairs<Rule>) -> Result<Expression> {
+    let mut dice: Vec<(NarrativeDie, usize)> = vec![];
+    for pair in pairs {
+        let mut inner = pair.into_inner();
+        let mut next = inner.next().unwrap();
+        let count = if next.as_rule() == Rule::number {
+            let count = next.as_str().parse::<usize>()?;
+            next = inner.next().unwrap();
+            count
+        } else {
+            1
+        };
+        let die = next.as_str().parse::<NarrativeDie>()?;
+        match dice.iter_mut().find(|(d, _)| *d == die) {
+            Some((_, n)) => *n += count,
+            None => dice.push((die, count)),
+        }
+        limit_dice(dice.iter().map(|(_, n)| n).sum(), "parse")?;
+    }
+    Ok(Expression::new(NarrativePool { dice }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Rollable, tests::IteratorDiceRollSource};
+
+    fn roll(pool: &str, rolls: Vec<u64>) -> Box<dyn EvaluatedExpression> {
+        Expression::parse(pool)
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut rolls.into_iter(),
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn cancellation() {
+        // Ability SA, SS; proficiency triumph; difficulty FH, HH; challenge FF
+        let result = roll("genesys(2A P 2D C)", vec![7, 4, 12, 8, 7, 4]);
+        assert_eq!(result.total(), Number::from(1));
+        assert_eq!(
+            result.outputs(),
+            [
+                ("advantage".to_string(), Number::from(-2)),
+                ("triumph".to_string(), Number::from(1)),
+                ("despair".to_string(), Number::from(0)),
+            ]
+        );
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "A[SA, SS] P[T] D[FH, HH] C[FF] = 1, advantage: -2, triumph: 1, despair: 0"
+        );
+        assert_eq!(
+            result.format_history(true, Verbosity::Medium),
+            "A\\[✹⬆, ✹✹\\] P\\[☀\\] D\\[✖⬇, ⬇⬇\\] C\\[✖✖\\]"
+        );
+    }
+
+    #[test]
+    fn force() {
+        let result = roll("genesys(2f)", vec![7, 10]);
+        assert_eq!(result.total(), Number::from(0));
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "F[BB, WW] = 0, advantage: 0, triumph: 0, despair: 0, light: 2, dark: 2"
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let pool = Expression::parse("genesys(A 2b A s) + 1").unwrap();
+        assert_eq!(format!("{pool}"), "genesys(2A 2B 1S) + 1");
+        assert!(Expression::parse("genesys(2X)").is_err());
+        assert!(Expression::parse("genesys()").is_err());
+        let result = roll("genesys(B S)", vec![1, 1]);
+        assert_eq!(
+            result.format(false, Verbosity::Short),
+            "B[-] S[-] = 0, advantage: 0, triumph: 0, despair: 0"
+        );
+    }
+}
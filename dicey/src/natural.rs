@@ -0,0 +1,101 @@
+//! Flags computed from the natural values of the kept dice, rather than the total.
+
+use std::collections::HashMap;
+
+use crate::EvaluatedExpression;
+
+/// A single die which was kept in the final result of a roll.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeptDie {
+    /// The value rolled.
+    pub value: i64,
+    /// The lowest value this die can roll.
+    pub min: i64,
+    /// The highest value this die can roll.
+    pub max: i64,
+}
+
+/// A rule for flagging a roll based on its kept dice, like a critical hit, a fumble, or doubles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NaturalRule {
+    /// Any die rolled its maximum value.
+    AnyMax,
+    /// Any die rolled its minimum value.
+    AnyMin,
+    /// There was at least one die, and every die rolled its maximum value.
+    AllMax,
+    /// There was at least one die, and every die rolled its minimum value.
+    AllMin,
+    /// Any die rolled this value or higher.
+    AnyAtLeast(i64),
+    /// At least this many dice rolled the same value: 2 for doubles, 3 for triples.
+    Matching(usize),
+    /// More than half of the dice rolled this value.
+    MajorityShowing(i64),
+}
+
+impl NaturalRule {
+    /// Check if this rule applies to some dice.
+    pub fn matches(&self, dice: &[KeptDie]) -> bool {
+        match self {
+            NaturalRule::AnyMax => dice.iter().any(|d| d.value == d.max),
+            NaturalRule::AnyMin => dice.iter().any(|d| d.value == d.min),
+            NaturalRule::AllMax => !dice.is_empty() && dice.iter().all(|d| d.value == d.max),
+            NaturalRule::AllMin => !dice.is_empty() && dice.iter().all(|d| d.value == d.min),
+            NaturalRule::AnyAtLeast(n) => dice.iter().any(|d| d.value >= *n),
+            NaturalRule::Matching(n) => {
+                let mut counts: HashMap<i64, usize> = HashMap::new();
+                for d in dice {
+                    *counts.entry(d.value).or_default() += 1;
+                }
+                counts.values().any(|count| count >= n)
+            }
+            NaturalRule::MajorityShowing(n) => {
+                dice.iter().filter(|d| d.value == *n).count() * 2 > dice.len()
+            }
+        }
+    }
+
+    /// Check if this rule applies to the kept dice of an evaluated expression.
+    pub fn matches_expression(&self, expression: &dyn EvaluatedExpression) -> bool {
+        self.matches(&expression.dice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d6(values: &[i64]) -> Vec<KeptDie> {
+        values
+            .iter()
+            .map(|&value| KeptDie {
+                value,
+                min: 1,
+                max: 6,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rules() {
+        let cases = [
+            (NaturalRule::AnyMax, &[1, 6][..], true),
+            (NaturalRule::AnyMax, &[1, 5], false),
+            (NaturalRule::AllMax, &[6, 6], true),
+            (NaturalRule::AllMax, &[6, 5], false),
+            (NaturalRule::AllMax, &[], false),
+            (NaturalRule::AllMin, &[1, 1], true),
+            (NaturalRule::AnyMin, &[3, 1], true),
+            (NaturalRule::AnyAtLeast(5), &[3, 5], true),
+            (NaturalRule::AnyAtLeast(5), &[3, 4], false),
+            (NaturalRule::Matching(2), &[3, 4, 3], true),
+            (NaturalRule::Matching(3), &[3, 4, 3], false),
+            (NaturalRule::MajorityShowing(1), &[1, 1, 4], true),
+            (NaturalRule::MajorityShowing(1), &[1, 1, 4, 5], false),
+        ];
+        for (rule, values, expected) in cases {
+            assert_eq!(rule.matches(&d6(values)), expected, "{rule:?} {values:?}");
+        }
+    }
+}
@@ -0,0 +1,418 @@
+//! Exact numeric results.
+
+use std::{
+    cmp::Ordering,
+    fmt::Display,
+    iter::Sum,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+
+/// A numeric result of evaluating an expression.
+///
+/// Results are kept as exact rationals: only float literals (like `1.5`) produce floating point values.
+/// Arithmetic which would overflow, or divides by zero, also falls back to floating point.
+#[derive(Clone, Copy, Debug)]
+pub struct Number(Repr);
+
+#[derive(Clone, Copy, Debug)]
+enum Repr {
+    /// In lowest terms, with a positive denominator.
+    Rational {
+        numerator: i64,
+        denominator: i64,
+    },
+    Float(f64),
+}
+
+/// How to convert a non-integer [Number] to an integer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    /// Round toward negative infinity.
+    Down,
+    /// Round toward positive infinity.
+    Up,
+    /// Round toward zero, discarding the fractional part.
+    TowardZero,
+    /// Round to the nearest integer, with halves rounded away from zero.
+    Nearest,
+}
+
+impl Number {
+    /// An exact rational number.
+    /// Returns [None] if `denominator` is zero.
+    pub fn rational(numerator: i64, denominator: i64) -> Option<Number> {
+        if denominator == 0 {
+            None
+        } else {
+            Some(Self::reduce(numerator.into(), denominator.into()))
+        }
+    }
+
+    /// Build an exact number from an `i128` ratio with a non-zero denominator,
+    /// falling back to floating point if it does not fit.
+    fn reduce(numerator: i128, denominator: i128) -> Number {
+        let divisor = gcd(numerator, denominator) * denominator.signum();
+        let (n, d) = (numerator / divisor, denominator / divisor);
+        match (i64::try_from(n), i64::try_from(d)) {
+            (Ok(numerator), Ok(denominator)) => Number(Repr::Rational {
+                numerator,
+                denominator,
+            }),
+            _ => Number(Repr::Float(n as f64 / d as f64)),
+        }
+    }
+
+    /// True unless this value came from a float literal, or from arithmetic which could not be done exactly.
+    pub fn is_exact(&self) -> bool {
+        matches!(self.0, Repr::Rational { .. })
+    }
+
+    /// `(numerator, denominator)` in lowest terms with a positive denominator, if exact.
+    pub fn as_rational(&self) -> Option<(i64, i64)> {
+        match self.0 {
+            Repr::Rational {
+                numerator,
+                denominator,
+            } => Some((numerator, denominator)),
+            Repr::Float(_) => None,
+        }
+    }
+
+    /// The value as an integer, if it is exactly an integer.
+    pub fn as_integer(&self) -> Option<i64> {
+        match self.0 {
+            Repr::Rational {
+                numerator,
+                denominator: 1,
+            } => Some(numerator),
+            _ => None,
+        }
+    }
+
+    /// The value as an integer, rounding as requested.
+    ///
+    /// Floating point values which are out of range saturate, and NaN gives 0.
+    pub fn to_integer(&self, rounding: Rounding) -> i64 {
+        match self.0 {
+            Repr::Rational {
+                numerator,
+                denominator,
+            } => {
+                let down = numerator.div_euclid(denominator);
+                let remainder = numerator.rem_euclid(denominator);
+                if remainder == 0 {
+                    return down;
+                }
+                match rounding {
+                    Rounding::Down => down,
+                    Rounding::Up => down + 1,
+                    Rounding::TowardZero => numerator / denominator,
+                    Rounding::Nearest => {
+                        let twice = i128::from(remainder) * 2;
+                        let denominator = i128::from(denominator);
+                        if twice > denominator || (twice == denominator && numerator > 0) {
+                            down + 1
+                        } else {
+                            down
+                        }
+                    }
+                }
+            }
+            Repr::Float(f) => match rounding {
+                Rounding::Down => f.floor() as i64,
+                Rounding::Up => f.ceil() as i64,
+                Rounding::TowardZero => f.trunc() as i64,
+                Rounding::Nearest => f.round() as i64,
+            },
+        }
+    }
+
+    /// The value as a float, rounding if needed.
+    pub fn to_f64(&self) -> f64 {
+        match self.0 {
+            Repr::Rational {
+                numerator,
+                denominator,
+            } => numerator as f64 / denominator as f64,
+            Repr::Float(f) => f,
+        }
+    }
+
+    /// Total ordering, comparing exactly when both values are exact.
+    ///
+    /// See [f64::total_cmp] for how non-exact values are ordered.
+    pub fn total_cmp(&self, other: &Number) -> Ordering {
+        match (self.0, other.0) {
+            (
+                Repr::Rational {
+                    numerator: a,
+                    denominator: b,
+                },
+                Repr::Rational {
+                    numerator: c,
+                    denominator: d,
+                },
+            ) => (i128::from(a) * i128::from(d)).cmp(&(i128::from(c) * i128::from(b))),
+            _ => self.to_f64().total_cmp(&other.to_f64()),
+        }
+    }
+
+    /// Apply an operation exactly if both sides are exact, otherwise in floating point.
+    ///
+    /// `integer` is a shortcut for integers, avoiding reducing the result.
+    fn combine(
+        self,
+        other: Number,
+        integer: impl FnOnce(i64, i64) -> Option<i64>,
+        exact: impl FnOnce((i128, i128), (i128, i128)) -> Option<(i128, i128)>,
+        float: impl FnOnce(f64, f64) -> f64,
+    ) -> Number {
+        if let (Some(a), Some(b)) = (self.as_integer(), other.as_integer())
+            && let Some(value) = integer(a, b)
+        {
+            return value.into();
+        }
+        if let (Some((a, b)), Some((c, d))) = (self.as_rational(), other.as_rational())
+            && let Some((numerator, denominator)) =
+                exact((a.into(), b.into()), (c.into(), d.into()))
+            && denominator != 0
+        {
+            return Self::reduce(numerator, denominator);
+        }
+        Number(Repr::Float(float(self.to_f64(), other.to_f64())))
+    }
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.abs()
+}
+
+impl From<i64> for Number {
+    fn from(value: i64) -> Self {
+        Number(Repr::Rational {
+            numerator: value,
+            denominator: 1,
+        })
+    }
+}
+
+impl From<f64> for Number {
+    fn from(value: f64) -> Self {
+        Number(Repr::Float(value))
+    }
+}
+
+impl From<Number> for f64 {
+    fn from(value: Number) -> Self {
+        value.to_f64()
+    }
+}
+
+impl Add for Number {
+    type Output = Number;
+
+    fn add(self, rhs: Number) -> Number {
+        self.combine(
+            rhs,
+            i64::checked_add,
+            |(a, b), (c, d)| Some((a.checked_mul(d)?.checked_add(c.checked_mul(b)?)?, b * d)),
+            |l, r| l + r,
+        )
+    }
+}
+
+impl Sub for Number {
+    type Output = Number;
+
+    fn sub(self, rhs: Number) -> Number {
+        self.combine(
+            rhs,
+            i64::checked_sub,
+            |(a, b), (c, d)| Some((a.checked_mul(d)?.checked_sub(c.checked_mul(b)?)?, b * d)),
+            |l, r| l - r,
+        )
+    }
+}
+
+impl Mul for Number {
+    type Output = Number;
+
+    fn mul(self, rhs: Number) -> Number {
+        self.combine(
+            rhs,
+            i64::checked_mul,
+            |(a, b), (c, d)| Some((a.checked_mul(c)?, b * d)),
+            |l, r| l * r,
+        )
+    }
+}
+
+impl Div for Number {
+    type Output = Number;
+
+    fn div(self, rhs: Number) -> Number {
+        self.combine(
+            rhs,
+            |a, b| (a.checked_rem(b)? == 0).then(|| a / b),
+            |(a, b), (c, d)| Some((a.checked_mul(d)?, b.checked_mul(c)?)),
+            |l, r| l / r,
+        )
+    }
+}
+
+impl Neg for Number {
+    type Output = Number;
+
+    fn neg(self) -> Number {
+        Number::from(0) - self
+    }
+}
+
+impl Sum for Number {
+    fn sum<I: Iterator<Item = Number>>(iter: I) -> Number {
+        iter.fold(Number::from(0), Add::add)
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Number) -> bool {
+        match (self.as_rational(), other.as_rational()) {
+            (Some(a), Some(b)) => a == b,
+            _ => self.to_f64() == other.to_f64(),
+        }
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        match (self.0, other.0) {
+            (Repr::Rational { .. }, Repr::Rational { .. }) => Some(self.total_cmp(other)),
+            _ => self.to_f64().partial_cmp(&other.to_f64()),
+        }
+    }
+}
+
+impl PartialEq<f64> for Number {
+    fn eq(&self, other: &f64) -> bool {
+        self.to_f64() == *other
+    }
+}
+
+impl PartialEq<Number> for f64 {
+    fn eq(&self, other: &Number) -> bool {
+        *self == other.to_f64()
+    }
+}
+
+impl PartialEq<i64> for Number {
+    fn eq(&self, other: &i64) -> bool {
+        match self.0 {
+            Repr::Float(value) => value == *other as f64,
+            Repr::Rational { .. } => self.as_integer() == Some(*other),
+        }
+    }
+}
+
+/// Most decimal places shown for a value which is not an integer.
+const DISPLAY_DECIMALS: usize = 4;
+
+impl Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.as_integer() {
+            Some(i) => write!(f, "{i}"),
+            None => {
+                let rounded = format!("{:.DISPLAY_DECIMALS$}", self.to_f64());
+                let rounded = match rounded.contains('.') {
+                    true => rounded.trim_end_matches('0').trim_end_matches('.'),
+                    false => &rounded,
+                };
+                f.write_str(if rounded == "-0" { "0" } else { rounded })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn n(value: i64) -> Number {
+        Number::from(value)
+    }
+
+    #[test]
+    fn exact_division() {
+        let third = n(1) / n(3);
+        assert!(third.is_exact());
+        assert_eq!(third.as_rational(), Some((1, 3)));
+        assert_eq!(third * n(3), n(1));
+        assert_eq!((third * n(3)).as_integer(), Some(1));
+        assert_eq!(n(21) / n(2), 10.5);
+        assert_eq!(format!("{}", n(21) / n(2)), "10.5");
+        assert_eq!(format!("{}", n(-6) / n(-2)), "3");
+        assert_eq!(format!("{}", n(10) / n(3)), "3.3333");
+        assert_eq!(format!("{}", n(-2) / n(3)), "-0.6667");
+        assert_eq!(format!("{}", n(1) / n(16)), "0.0625");
+        assert_eq!(format!("{}", Number::from(0.1) + Number::from(0.2)), "0.3");
+        assert_eq!(format!("{}", n(-1) / n(100_000)), "0");
+    }
+
+    #[test]
+    fn float_is_contagious() {
+        let value = n(7) * Number::from(1.5);
+        assert!(!value.is_exact());
+        assert_eq!(value, 10.5);
+        let value = n(4) * Number::from(2.5);
+        assert_eq!(value, 10);
+        assert_eq!(value, 10.0);
+        assert_ne!(value, 11);
+        assert_eq!(format!("{value}"), "10");
+    }
+
+    #[test]
+    fn divide_by_zero() {
+        assert_eq!(Number::rational(1, 0), None);
+        assert_eq!((n(1) / n(0)).to_f64(), f64::INFINITY);
+    }
+
+    #[test]
+    fn overflow() {
+        let value = n(i64::MAX) + n(i64::MAX);
+        assert!(!value.is_exact());
+        assert_eq!(value, 2.0 * i64::MAX as f64);
+    }
+
+    #[test]
+    fn rounding() {
+        let cases = [
+            (n(7) / n(2), [3, 4, 3, 4]),
+            (n(-7) / n(2), [-4, -3, -3, -4]),
+            (n(10) / n(3), [3, 4, 3, 3]),
+            (n(-10) / n(3), [-4, -3, -3, -3]),
+            (n(5), [5, 5, 5, 5]),
+            (Number::from(-2.5), [-3, -2, -2, -3]),
+        ];
+        for (value, expected) in cases {
+            let rounded = [
+                Rounding::Down,
+                Rounding::Up,
+                Rounding::TowardZero,
+                Rounding::Nearest,
+            ]
+            .map(|r| value.to_integer(r));
+            assert_eq!(rounded, expected, "{value}");
+        }
+    }
+
+    #[test]
+    fn ordering() {
+        assert!(n(1) / n(3) < n(1) / n(2));
+        assert_eq!(
+            (n(2) / n(3)).total_cmp(&Number::from(0.5)),
+            Ordering::Greater
+        );
+    }
+}
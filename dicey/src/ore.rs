@@ -0,0 +1,175 @@
+//! One-Roll Engine sets: dice grouped by matching value, like `3x7` for three 7s.
+
+use std::fmt::{self, Display};
+
+use crate::{FancyFormat, Verbosity, dice_expression::format_rolls};
+
+/// Matching dice in a roll.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Set<T> {
+    /// Number of matching dice.
+    pub width: usize,
+    /// Value of the dice.
+    pub height: T,
+}
+
+impl<T: Display> Display for Set<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+/// Rolled dice grouped into [Set]s, widest first, and waste dice which match no other die.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sets<T> {
+    sets: Vec<Set<T>>,
+    waste: Vec<T>,
+}
+
+impl<T: Ord + Copy> Sets<T> {
+    /// Group `rolls` into sets.
+    pub fn new(rolls: impl IntoIterator<Item = T>) -> Sets<T> {
+        let mut rolls: Vec<T> = rolls.into_iter().collect();
+        rolls.sort_unstable_by(|a, b| b.cmp(a));
+        let mut sets = vec![];
+        let mut waste = vec![];
+        for group in rolls.chunk_by(|a, b| a == b) {
+            if group.len() > 1 {
+                sets.push(Set {
+                    width: group.len(),
+                    height: group[0],
+                });
+            } else {
+                waste.push(group[0]);
+            }
+        }
+        // Stable, so sets of the same width stay highest first.
+        sets.sort_by_key(|set| std::cmp::Reverse(set.width));
+        Sets { sets, waste }
+    }
+
+    /// Add `wiggle` dice, each set to the best value: widening the highest set,
+    /// else matching the highest waste die, else `max`.
+    pub fn with_wiggle(&self, wiggle: usize, max: T) -> Sets<T> {
+        let mut values = self.values();
+        values.extend(self.wiggle_values(wiggle, max));
+        Sets::new(values)
+    }
+
+    /// Values chosen for `wiggle` dice by [Sets::with_wiggle].
+    pub(crate) fn wiggle_values(&self, wiggle: usize, max: T) -> Vec<T> {
+        let mut values = self.values();
+        for _ in 0..wiggle {
+            let sets = Sets::new(values.iter().copied());
+            let value = sets
+                .sets
+                .iter()
+                .map(|set| set.height)
+                .max()
+                .or_else(|| sets.waste.first().copied())
+                .unwrap_or(max);
+            values.push(value);
+        }
+        values.split_off(values.len() - wiggle)
+    }
+
+    /// All the dice, in sets or not.
+    fn values(&self) -> Vec<T> {
+        self.sets
+            .iter()
+            .flat_map(|set| std::iter::repeat_n(set.height, set.width))
+            .chain(self.waste.iter().copied())
+            .collect()
+    }
+
+    /// Sets of two or more matching dice, widest first, then highest first.
+    pub fn sets(&self) -> &[Set<T>] {
+        &self.sets
+    }
+
+    /// Dice which are not part of any set, highest first.
+    pub fn waste(&self) -> &[T] {
+        &self.waste
+    }
+}
+
+/// Like `3x7, 2x2 waste [4, 9]`.
+impl<T: Display> FancyFormat for Sets<T> {
+    fn format(&self, markdown: bool, _verbose: Verbosity) -> String {
+        let mut s = if self.sets.is_empty() {
+            "no sets".to_string()
+        } else {
+            let sets: Vec<String> = self.sets.iter().map(|set| set.to_string()).collect();
+            sets.join(", ")
+        };
+        if !self.waste.is_empty() {
+            s += &format!(" waste {}", format_rolls(self.waste.iter(), markdown));
+        }
+        s
+    }
+}
+
+impl<T: Display> Display for Sets<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format(false, Verbosity::Medium))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Expression, Number, Rollable, tests::IteratorDiceRollSource};
+
+    #[test]
+    fn sets() {
+        let sets = Sets::new([2, 7, 4, 7, 9, 2, 7]);
+        assert_eq!(sets.to_string(), "3x7, 2x2 waste [9, 4]");
+        assert_eq!(
+            sets.sets()[0],
+            Set {
+                width: 3,
+                height: 7
+            }
+        );
+        assert_eq!(Sets::new([1, 2, 3]).to_string(), "no sets waste [3, 2, 1]");
+        assert_eq!(Sets::new([5, 5, 3, 3]).to_string(), "2x5, 2x3");
+    }
+
+    #[test]
+    fn wiggle() {
+        let sets = Sets::new([2, 2, 7, 7, 4]);
+        assert_eq!(sets.with_wiggle(1, 10).to_string(), "3x7, 2x2 waste [4]");
+        assert_eq!(
+            Sets::new([3, 8]).with_wiggle(2, 10).to_string(),
+            "3x8 waste [3]"
+        );
+        assert_eq!(Sets::new([]).with_wiggle(2, 10).to_string(), "2x10");
+    }
+
+    #[test]
+    fn expression() {
+        let roll = |expression: &str, rolls: Vec<u64>| {
+            Expression::parse(expression)
+                .unwrap()
+                .roll_with_source(&mut IteratorDiceRollSource {
+                    iterator: &mut rolls.into_iter(),
+                })
+                .unwrap()
+        };
+        let result = roll("6d10 sets", vec![7, 2, 7, 9, 2, 7]);
+        assert_eq!(result.total(), Number::from(2i64));
+        assert_eq!(
+            result.format_history(false, Verbosity::Medium),
+            "[7, 2, 7, 9, 2, 7] 🡲 3x7, 2x2 waste [9]"
+        );
+        let expression = Expression::parse("3d10 sets hd1 wd1").unwrap();
+        assert_eq!(format!("{expression}"), "3d10 sets hd1 wd1");
+        let result = roll("3d10 sets hd1 wd1", vec![4, 10, 3]);
+        assert_eq!(result.total(), Number::from(1i64));
+        assert_eq!(
+            result.format_history(true, Verbosity::Medium),
+            "\\[4, 10, 3\\] hd\\[10\\] wd\\[10\\] 🡲 3x10 waste \\[4, 3\\]"
+        );
+        assert!(Expression::parse("1d10 sets hd18446744073709551615 wd1").is_err());
+    }
+}
@@ -7,17 +7,23 @@ use pest_derive::Parser;
 #[grammar = "dicey.pest"]
 pub(crate) struct RollParser;
 
-pub(crate) fn climb<'i, P, F, G, T>(pairs: P, primary: F, infix: G) -> T
+pub(crate) fn climb<'i, P, F, G, H, T>(pairs: P, primary: F, infix: G, postfix: H) -> T
 where
     P: Iterator<Item = Pair<'i, Rule>>,
     F: FnMut(Pair<'i, Rule>) -> T,
     G: FnMut(T, Pair<'i, Rule>, T) -> T + 'i,
+    H: FnMut(T, Pair<'i, Rule>) -> T + 'i,
 {
     static PARSER: LazyLock<PrattParser<Rule>> = LazyLock::new(|| {
         use pest::pratt_parser::{Assoc, Op};
         PrattParser::new()
             .op(Op::infix(Rule::add, Assoc::Left) | Op::infix(Rule::sub, Assoc::Left))
             .op(Op::infix(Rule::mul, Assoc::Left) | Op::infix(Rule::div, Assoc::Left))
+            .op(Op::postfix(Rule::tag))
     });
-    PARSER.map_primary(primary).map_infix(infix).parse(pairs)
+    PARSER
+        .map_primary(primary)
+        .map_infix(infix)
+        .map_postfix(postfix)
+        .parse(pairs)
 }
@@ -0,0 +1,294 @@
+//! Die steps: moving a die up or down a ladder of sizes, like `d8-1step` for a d6.
+
+use pest::iterators::Pairs;
+
+use crate::{
+    DiceRollSource, FancyFormat, Result, Rollable, Verbosity,
+    dice_expression::{basic_dice, format_rolls, limit_dice},
+    dice_kind::{DiceKind, basic::BasicDice},
+    expression::{Expression, format_bold},
+    parser::Rule,
+};
+
+/// What stepping up the largest die on a [StepLadder] does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AboveTop {
+    /// The die stays the largest size (as in The Black Hack).
+    Cap,
+    /// Each extra step adds dice from the bottom of the ladder: a d12 stepped up once is d12 + d4, then d12 + d6 (as in Cortex Prime).
+    AddDie,
+}
+
+/// Die sizes in order, for stepping dice up and down.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StepLadder {
+    sizes: Vec<BasicDice>,
+    above_top: AboveTop,
+}
+
+impl Default for StepLadder {
+    /// d4, d6, d8, d10, d12, adding dice above d12.
+    fn default() -> Self {
+        StepLadder::new([4, 6, 8, 10, 12], AboveTop::AddDie).unwrap()
+    }
+}
+
+impl StepLadder {
+    /// A ladder of die sizes, from smallest to largest.
+    pub fn new(sizes: impl IntoIterator<Item = u32>, above_top: AboveTop) -> Result<StepLadder> {
+        let sizes = sizes
+            .into_iter()
+            .map(|sides| BasicDice::new(sides).ok_or("Die sizes must not be zero"))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        if sizes.is_empty() || !sizes.windows(2).all(|w| w[0] < w[1]) {
+            return Err("Die sizes on a step ladder must be increasing".into());
+        }
+        Ok(StepLadder { sizes, above_top })
+    }
+
+    /// The dice a die of `sides` becomes after moving `steps` up (or down if negative) the ladder.
+    ///
+    /// Returns no dice if it steps down below the smallest size.
+    pub fn step(&self, sides: u32, steps: i64) -> Result<Vec<u32>> {
+        let index = self
+            .sizes
+            .iter()
+            .position(|size| size.get() == sides)
+            .ok_or_else(|| format!("d{sides} is not on the step ladder"))?;
+        let top = self.sizes.len() as i64 - 1;
+        let target = (index as i64).checked_add(steps).ok_or("Too many steps")?;
+        if target < 0 {
+            return Ok(vec![]);
+        }
+        if target <= top || self.above_top == AboveTop::Cap {
+            return Ok(vec![self.sizes[target.min(top) as usize].get()]);
+        }
+        // Each step beyond the top walks an extra die up from the bottom.
+        let extra = (target / (top + 1)) as usize;
+        limit_dice(extra + 1, "stepping")?;
+        let mut dice = vec![self.sizes[top as usize].get(); extra];
+        dice.push(self.sizes[(target % (top + 1)) as usize].get());
+        Ok(dice)
+    }
+}
+
+/// Parse `d8-1step`, `2d12+1step` or `d6+2steps`, using the default [StepLadder].
+pub(crate) fn parse_stepped_dice(mut pairs: Pairs<Rule>) -> Result<Expression> {
+    let mut pair = pairs.next().unwrap();
+    let number_of_dice = if pair.as_rule() == Rule::number_of_dice {
+        let n = pair.as_str().parse::<usize>()?;
+        pairs.next(); // skip `d` token
+        n
+    } else {
+        1
+    };
+    pair = pairs.next().unwrap();
+    let sides = pair.as_str().parse::<u32>()?;
+
+    let mut step = pairs.next().unwrap().into_inner();
+    let sign = if step.next().unwrap().as_rule() == Rule::sub {
+        -1
+    } else {
+        1
+    };
+    let steps = sign * step.next().unwrap().as_str().parse::<i64>()?;
+
+    let dice = StepLadder::default().step(sides, steps)?;
+    if dice.is_empty() {
+        return Err(format!("d{sides} stepped down {} is smaller than any die", -steps).into());
+    }
+    limit_dice(dice.len().saturating_mul(number_of_dice), "parse")?;
+    // One roll per die size, like `4d12 + 2d4` for `2d12+6steps`.
+    let mut sizes: Vec<(u32, usize)> = vec![];
+    for sides in dice {
+        match sizes.iter_mut().find(|(size, _)| *size == sides) {
+            Some((_, count)) => *count += number_of_dice,
+            None => sizes.push((sides, number_of_dice)),
+        }
+    }
+    let mut sizes = sizes.into_iter();
+    let (first, count) = sizes.next().unwrap();
+    let mut expression = basic_dice(count, BasicDice::new(first).unwrap())?;
+    let mut compound = false;
+    for (sides, count) in sizes {
+        expression = Expression::add(
+            expression,
+            basic_dice(count, BasicDice::new(sides).unwrap())?,
+        );
+        compound = true;
+    }
+    Ok(if compound {
+        Expression::block(expression)
+    } else {
+        expression
+    })
+}
+
+/// A usage die (as in The Black Hack): roll it, and on a low roll it steps down for next time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsageDie {
+    /// Current size of the die.
+    pub sides: u32,
+    /// Rolls of this or lower step the die down.
+    pub degrade_on: u32,
+    /// Die sizes to step down through.
+    pub ladder: StepLadder,
+}
+
+impl UsageDie {
+    /// A usage die which steps down on a 1 or 2, through d4 to d12.
+    pub fn new(sides: u32) -> UsageDie {
+        UsageDie {
+            sides,
+            degrade_on: 2,
+            ladder: StepLadder::new([4, 6, 8, 10, 12], AboveTop::Cap).unwrap(),
+        }
+    }
+}
+
+impl FancyFormat for UsageDie {
+    fn format(&self, _markdown: bool, _verbose: Verbosity) -> String {
+        format!("Usage d{}", self.sides)
+    }
+}
+
+impl Rollable for UsageDie {
+    type Roll = Result<EvaluatedUsage>;
+
+    fn roll_with_source(&self, rng: &mut dyn DiceRollSource) -> Self::Roll {
+        let die = BasicDice::new(self.sides).ok_or("Usage die must have sides")?;
+        let roll = die.roll(rng);
+        let next = if roll <= self.degrade_on {
+            self.ladder.step(self.sides, -1)?.first().copied()
+        } else {
+            Some(self.sides)
+        };
+        Ok(EvaluatedUsage {
+            sides: self.sides,
+            roll,
+            next,
+        })
+    }
+}
+
+/// Result of rolling a [UsageDie].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EvaluatedUsage {
+    sides: u32,
+    roll: u32,
+    next: Option<u32>,
+}
+
+impl EvaluatedUsage {
+    /// The number rolled.
+    pub fn roll(&self) -> u32 {
+        self.roll
+    }
+
+    /// If the die stepped down.
+    pub fn degraded(&self) -> bool {
+        self.next != Some(self.sides)
+    }
+
+    /// Size of the die for the next roll, or [None] if it is used up.
+    pub fn next(&self) -> Option<u32> {
+        self.next
+    }
+}
+
+impl FancyFormat for EvaluatedUsage {
+    fn format(&self, markdown: bool, _verbose: Verbosity) -> String {
+        let result = match self.next {
+            None => "used up".to_string(),
+            Some(next) if self.degraded() => format!("steps down to d{next}"),
+            Some(_) => "holds".to_string(),
+        };
+        format!(
+            "Usage d{} {}: {}",
+            self.sides,
+            format_rolls([self.roll].iter(), markdown),
+            format_bold(result, markdown)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::IteratorDiceRollSource;
+
+    #[test]
+    fn ladder() {
+        let ladder = StepLadder::default();
+        assert_eq!(ladder.step(8, -1).unwrap(), [6]);
+        assert_eq!(ladder.step(8, 2).unwrap(), [12]);
+        assert_eq!(ladder.step(12, 1).unwrap(), [12, 4]);
+        assert_eq!(ladder.step(12, 3).unwrap(), [12, 8]);
+        assert_eq!(ladder.step(12, 5).unwrap(), [12, 12]);
+        assert_eq!(ladder.step(12, 6).unwrap(), [12, 12, 4]);
+        assert!(ladder.step(12, 100_000).is_err());
+        assert!(ladder.step(12, i64::MAX).is_err());
+        assert!(ladder.step(4, -1).unwrap().is_empty());
+        assert!(ladder.step(20, 1).is_err());
+
+        let capped = StepLadder::new([4, 6, 8, 10, 12], AboveTop::Cap).unwrap();
+        assert_eq!(capped.step(10, 3).unwrap(), [12]);
+        assert!(StepLadder::new([6, 4], AboveTop::Cap).is_err());
+    }
+
+    #[test]
+    fn expression() {
+        let spec = Expression::parse("d8-1step + 2d12+1step").unwrap();
+        assert_eq!(format!("{spec}"), "1d6 + (2d12 + 2d4)");
+        let result = spec
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [5, 12, 11, 4, 1].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(result.total(), 33);
+        assert_eq!(
+            format!("{}", Expression::parse("d6 +2steps").unwrap()),
+            "1d10"
+        );
+        assert!(Expression::parse("d4-1step").is_err());
+        assert!(Expression::parse("d20+1step").is_err());
+        assert_eq!(
+            format!("{}", Expression::parse("2d12+6steps").unwrap()),
+            "(4d12 + 2d4)"
+        );
+        assert!(Expression::parse("d12+100000step").is_err());
+        assert!(Expression::parse("d12+9223372036854775807step").is_err());
+        assert!(Expression::parse("3000d12+5steps").is_err());
+        // Still subtraction when not followed by `step`
+        assert_eq!(format!("{}", Expression::parse("d8-1").unwrap()), "1d8 - 1");
+    }
+
+    #[test]
+    fn usage() {
+        let roll = |die: &UsageDie, value: u64| {
+            die.roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [value].into_iter(),
+            })
+            .unwrap()
+        };
+        let result = roll(&UsageDie::new(8), 2);
+        assert!(result.degraded());
+        assert_eq!(result.next(), Some(6));
+        assert_eq!(
+            result.format(true, Verbosity::Medium),
+            "Usage d8 \\[2\\]: **steps down to d6**"
+        );
+        let result = roll(&UsageDie::new(8), 3);
+        assert!(!result.degraded());
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "Usage d8 [3]: holds"
+        );
+        let result = roll(&UsageDie::new(4), 1);
+        assert_eq!(result.next(), None);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "Usage d4 [1]: used up"
+        );
+    }
+}
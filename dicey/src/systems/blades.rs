@@ -0,0 +1,162 @@
+//! Blades in the Dark: a pool of d6 read by its highest die.
+
+use crate::{
+    DiceRollSource, EvaluatedExpression, Expression, FancyFormat, Result, Rollable, Verbosity,
+    expression::format_bold,
+};
+
+use super::{Outcome, Preset};
+
+/// An action roll, fortune roll or resistance roll: a pool of d6.
+#[derive(Clone, Copy, Debug)]
+pub struct Action {
+    /// Number of dice in the pool.
+    /// With zero dice, two are rolled and the lowest is used, and there can not be a critical.
+    pub dice: u32,
+}
+
+/// Result of an [Action].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActionResult {
+    /// Highest die is 1 to 3.
+    Failure,
+    /// Highest die is 4 or 5.
+    PartialSuccess,
+    /// Highest die is a 6.
+    Success,
+    /// More than one 6.
+    Critical,
+}
+
+impl ActionResult {
+    /// Human readable name of the result.
+    pub fn label(&self) -> &'static str {
+        match self {
+            ActionResult::Failure => "Failure",
+            ActionResult::PartialSuccess => "Partial success",
+            ActionResult::Success => "Success",
+            ActionResult::Critical => "Critical",
+        }
+    }
+}
+
+impl Action {
+    fn notation(&self) -> String {
+        match self.dice {
+            0 => "2d6k1".to_string(),
+            n => format!("{n}d6"),
+        }
+    }
+}
+
+impl FancyFormat for Action {
+    fn format(&self, _markdown: bool, _verbose: Verbosity) -> String {
+        format!("{}d", self.dice)
+    }
+}
+
+impl Rollable for Action {
+    type Roll = Result<EvaluatedAction>;
+
+    fn roll_with_source(&self, rng: &mut dyn DiceRollSource) -> Self::Roll {
+        let roll = Expression::parse(&self.notation())?.roll_with_source(rng)?;
+        let dice = roll.dice();
+        let highest = dice.iter().map(|d| d.value).max().unwrap_or(0);
+        let sixes = dice.iter().filter(|d| d.value == 6).count();
+        let result = match highest {
+            6 if sixes > 1 => ActionResult::Critical,
+            6 => ActionResult::Success,
+            4 | 5 => ActionResult::PartialSuccess,
+            _ => ActionResult::Failure,
+        };
+        Ok(EvaluatedAction {
+            roll,
+            highest,
+            result,
+        })
+    }
+}
+
+impl Preset for Action {
+    fn roll_outcome(&self, rng: &mut dyn DiceRollSource) -> Result<Box<dyn Outcome>> {
+        Ok(Box::new(self.roll_with_source(rng)?))
+    }
+}
+
+/// Result of rolling an [Action].
+#[derive(Debug)]
+pub struct EvaluatedAction {
+    roll: Box<dyn EvaluatedExpression>,
+    highest: i64,
+    result: ActionResult,
+}
+
+impl EvaluatedAction {
+    /// The result, based on the highest die.
+    pub fn result(&self) -> ActionResult {
+        self.result
+    }
+
+    /// The die the result was read from.
+    pub fn highest(&self) -> i64 {
+        self.highest
+    }
+}
+
+impl FancyFormat for EvaluatedAction {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        format!(
+            "{} {}",
+            format_bold(self.result.label(), markdown),
+            self.roll.format_history(markdown, verbose)
+        )
+    }
+}
+
+impl Outcome for EvaluatedAction {
+    fn label(&self) -> Option<String> {
+        Some(self.result.label().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::IteratorDiceRollSource;
+
+    fn roll(dice: u32, rolls: Vec<u64>) -> EvaluatedAction {
+        Action { dice }
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut rolls.into_iter(),
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn results() {
+        assert_eq!(roll(3, vec![1, 2, 3]).result(), ActionResult::Failure);
+        assert_eq!(
+            roll(3, vec![1, 5, 3]).result(),
+            ActionResult::PartialSuccess
+        );
+        assert_eq!(roll(2, vec![6, 3]).result(), ActionResult::Success);
+        assert_eq!(roll(2, vec![6, 6]).result(), ActionResult::Critical);
+        assert_eq!(
+            roll(2, vec![6, 6]).format(true, Verbosity::Medium),
+            "**Critical** \\[6, 6\\]"
+        );
+    }
+
+    #[test]
+    fn zero_dice() {
+        let result = roll(0, vec![6, 6]);
+        assert_eq!(result.result(), ActionResult::Success);
+        let result = roll(0, vec![6, 2]);
+        assert_eq!(result.result(), ActionResult::Failure);
+        assert_eq!(result.highest(), 2);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "Failure [Drop(6), 2]k1"
+        );
+    }
+}
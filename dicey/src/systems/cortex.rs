@@ -0,0 +1,281 @@
+//! Cortex Prime: a pool of mixed dice, with two added for the total and another kept as the effect die.
+
+use crate::{
+    DiceRollSource, FancyFormat, Result, Rollable, Verbosity, dice_expression::limit_dice,
+    expression::format_bold,
+};
+
+use super::{Outcome, Preset};
+
+/// A dice pool, like `d8 d6 d10 d4`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pool {
+    /// Sides of each die in the pool.
+    pub dice: Vec<u32>,
+}
+
+impl Pool {
+    /// Parse a pool of dice separated by spaces, like `d8 d6 2d10`.
+    pub fn parse(s: &str) -> Result<Pool> {
+        let mut dice = vec![];
+        for part in s.split_whitespace() {
+            let (count, sides) = part
+                .split_once(['d', 'D'])
+                .ok_or_else(|| format!("Invalid die \"{part}\""))?;
+            let count = if count.is_empty() {
+                1
+            } else {
+                count.parse::<usize>()?
+            };
+            let sides = sides.parse::<u32>()?;
+            if sides < 2 {
+                return Err(format!("Invalid die \"{part}\"").into());
+            }
+            limit_dice(count, "Cortex pool")?;
+            limit_dice(dice.len() + count, "Cortex pool")?;
+            dice.extend(std::iter::repeat_n(sides, count));
+        }
+        if dice.is_empty() {
+            return Err("Empty dice pool".into());
+        }
+        Ok(Pool { dice })
+    }
+}
+
+impl FancyFormat for Pool {
+    fn format(&self, _markdown: bool, _verbose: Verbosity) -> String {
+        self.dice
+            .iter()
+            .map(|sides| format!("d{sides}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl Rollable for Pool {
+    type Roll = Result<EvaluatedPool>;
+
+    fn roll_with_source(&self, rng: &mut dyn DiceRollSource) -> Self::Roll {
+        let rolls = self
+            .dice
+            .iter()
+            .map(|sides| PoolDie {
+                sides: *sides,
+                value: rng.roll_single_die((*sides).into()) as u32,
+            })
+            .collect();
+        Ok(EvaluatedPool::resolve(rolls))
+    }
+}
+
+impl Preset for Pool {
+    fn roll_outcome(&self, rng: &mut dyn DiceRollSource) -> Result<Box<dyn Outcome>> {
+        Ok(Box::new(self.roll_with_source(rng)?))
+    }
+}
+
+/// A rolled die in an [EvaluatedPool].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolDie {
+    /// Sides of the die.
+    pub sides: u32,
+    /// Number rolled.
+    pub value: u32,
+}
+
+impl PoolDie {
+    /// A 1, which can not be used for the total or effect.
+    pub fn hitch(&self) -> bool {
+        self.value == 1
+    }
+}
+
+/// Result of rolling a [Pool]: which dice make the total, and which is the effect die.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvaluatedPool {
+    rolls: Vec<PoolDie>,
+    /// Indexes into `rolls`.
+    total_dice: Vec<usize>,
+    effect: Option<usize>,
+}
+
+/// Size of the effect die when there is no die left to use.
+const DEFAULT_EFFECT: u32 = 4;
+
+impl EvaluatedPool {
+    /// Pick the two highest dice for the total (preferring smaller dice on ties),
+    /// then the largest remaining die for the effect.
+    fn resolve(rolls: Vec<PoolDie>) -> EvaluatedPool {
+        let mut usable: Vec<usize> = (0..rolls.len()).filter(|i| !rolls[*i].hitch()).collect();
+        usable.sort_by_key(|i| (std::cmp::Reverse(rolls[*i].value), rolls[*i].sides));
+        let total_dice: Vec<usize> = usable.iter().take(2).copied().collect();
+        let effect = usable
+            .iter()
+            .skip(2)
+            .copied()
+            .max_by_key(|i| (rolls[*i].sides, std::cmp::Reverse(*i)));
+        EvaluatedPool {
+            rolls,
+            total_dice,
+            effect,
+        }
+    }
+
+    /// Choose the dice for the total and the effect die instead of the automatic choice.
+    /// Dice are indexes in the order they were rolled.
+    pub fn choose(&self, total_dice: &[usize], effect: Option<usize>) -> Result<EvaluatedPool> {
+        let mut chosen: Vec<usize> = total_dice.to_vec();
+        chosen.extend(effect);
+        for (n, i) in chosen.iter().enumerate() {
+            let die = self
+                .rolls
+                .get(*i)
+                .ok_or_else(|| format!("There is no die {i} in the pool"))?;
+            if die.hitch() {
+                return Err(format!("Die {i} is a hitch, so can not be used").into());
+            }
+            if chosen[..n].contains(i) {
+                return Err(format!("Die {i} can only be used once").into());
+            }
+        }
+        Ok(EvaluatedPool {
+            rolls: self.rolls.clone(),
+            total_dice: total_dice.to_vec(),
+            effect,
+        })
+    }
+
+    /// All the dice rolled.
+    pub fn rolls(&self) -> &[PoolDie] {
+        &self.rolls
+    }
+
+    /// Sum of the dice chosen for the total.
+    pub fn total(&self) -> u32 {
+        self.total_dice.iter().map(|i| self.rolls[*i].value).sum()
+    }
+
+    /// Size of the effect die: the chosen die, or a d4 if no die was left.
+    pub fn effect_die(&self) -> u32 {
+        self.effect.map_or(DEFAULT_EFFECT, |i| self.rolls[i].sides)
+    }
+
+    /// Number of 1s rolled.
+    pub fn hitches(&self) -> usize {
+        self.rolls.iter().filter(|d| d.hitch()).count()
+    }
+
+    /// If every die was a 1.
+    pub fn botch(&self) -> bool {
+        self.hitches() == self.rolls.len()
+    }
+}
+
+impl FancyFormat for EvaluatedPool {
+    fn format(&self, markdown: bool, _verbose: Verbosity) -> String {
+        let dice = self
+            .rolls
+            .iter()
+            .enumerate()
+            .map(|(i, die)| {
+                let value = if self.total_dice.contains(&i) {
+                    format_bold(die.value, markdown)
+                } else if die.hitch() && markdown {
+                    format!("~~{}~~", die.value)
+                } else {
+                    die.value.to_string()
+                };
+                let effect = if self.effect == Some(i) {
+                    " effect"
+                } else {
+                    ""
+                };
+                format!("d{}: {value}{effect}", die.sides)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut s = format!("{} [{dice}]", format_bold(self.label().unwrap(), markdown));
+        if markdown {
+            s = s.replace('[', "\\[").replace(']', "\\]");
+        }
+        match self.hitches() {
+            0 => s,
+            1 => s + " 1 hitch",
+            n => s + &format!(" {n} hitches"),
+        }
+    }
+}
+
+impl Outcome for EvaluatedPool {
+    fn label(&self) -> Option<String> {
+        Some(if self.botch() {
+            "Botch".to_string()
+        } else {
+            format!("Total {}, effect d{}", self.total(), self.effect_die())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::IteratorDiceRollSource;
+
+    fn roll(pool: &str, rolls: Vec<u64>) -> EvaluatedPool {
+        Pool::parse(pool)
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut rolls.into_iter(),
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn resolve() {
+        let result = roll("d8 d6 d10 d4", vec![5, 5, 1, 3]);
+        assert_eq!(result.total(), 10);
+        // The d10 is a hitch, so the d4 is the only die left for the effect.
+        assert_eq!(result.effect_die(), 4);
+        assert_eq!(result.hitches(), 1);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "Total 10, effect d4 [d8: 5, d6: 5, d10: 1, d4: 3 effect] 1 hitch"
+        );
+
+        let result = roll("d8 2d6 d12", vec![2, 6, 4, 3]);
+        assert_eq!(result.total(), 10);
+        assert_eq!(result.effect_die(), 12);
+        assert_eq!(
+            result.format(true, Verbosity::Medium),
+            "**Total 10, effect d12** \\[d8: 2, d6: **6**, d6: **4**, d12: 3 effect\\]"
+        );
+    }
+
+    #[test]
+    fn botch() {
+        let result = roll("d8 d6", vec![1, 1]);
+        assert!(result.botch());
+        assert_eq!(result.total(), 0);
+        assert_eq!(result.label().unwrap(), "Botch");
+    }
+
+    #[test]
+    fn choose() {
+        let result = roll("d8 2d6 d12", vec![2, 6, 4, 3]);
+        let chosen = result.choose(&[1, 3], Some(0)).unwrap();
+        assert_eq!(chosen.total(), 9);
+        assert_eq!(chosen.effect_die(), 8);
+        assert!(result.choose(&[1, 1], None).is_err());
+        assert!(result.choose(&[1, 7], None).is_err());
+        assert!(roll("d8 d6", vec![1, 4]).choose(&[0], None).is_err());
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(Pool::parse("d8 2d6").unwrap().dice, [8, 6, 6]);
+        assert!(Pool::parse("").is_err());
+        assert!(Pool::parse("d1").is_err());
+        assert!(Pool::parse("8").is_err());
+        assert!(Pool::parse("d6 18446744073709551615d6").is_err());
+    }
+}
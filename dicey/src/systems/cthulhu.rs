@@ -0,0 +1,292 @@
+//! Call of Cthulhu: percentile checks against a skill, with bonus and penalty dice.
+
+use crate::{
+    DiceRollSource, EvaluatedExpression, Expression, FancyFormat, KeptDie, Number, Result,
+    Rollable, Rounding, Verbosity,
+    dice_expression::{format_rolls, limit_dice},
+    expression::{ExpressionResult, ExpressionRollable, format_italic},
+};
+
+use super::{Outcome, Preset};
+
+/// How well a [Check] went, from worst to best.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SuccessLevel {
+    /// 100 whatever the skill, or 96 and over for skills under 50.
+    Fumble,
+    /// Over the skill.
+    Failure,
+    /// At most the skill.
+    Regular,
+    /// At most half the skill.
+    Hard,
+    /// At most a fifth of the skill.
+    Extreme,
+    /// A roll of 1.
+    Critical,
+}
+
+impl SuccessLevel {
+    /// The level of a percentile `roll` against `skill`.
+    pub fn of(roll: u32, skill: i64) -> SuccessLevel {
+        let roll = i64::from(roll);
+        if roll == 1 {
+            SuccessLevel::Critical
+        } else if roll == 100 {
+            SuccessLevel::Fumble
+        } else if roll <= skill / 5 {
+            SuccessLevel::Extreme
+        } else if roll <= skill / 2 {
+            SuccessLevel::Hard
+        } else if roll <= skill {
+            SuccessLevel::Regular
+        } else if skill < 50 && roll >= 96 {
+            SuccessLevel::Fumble
+        } else {
+            SuccessLevel::Failure
+        }
+    }
+
+    /// Like "Hard success".
+    pub fn label(&self) -> &'static str {
+        match self {
+            SuccessLevel::Fumble => "Fumble",
+            SuccessLevel::Failure => "Failure",
+            SuccessLevel::Regular => "Regular success",
+            SuccessLevel::Hard => "Hard success",
+            SuccessLevel::Extreme => "Extreme success",
+            SuccessLevel::Critical => "Critical success",
+        }
+    }
+}
+
+/// A percentile check, like `cthulhu(65, bonus=1)`.
+#[derive(Clone, Debug)]
+pub struct Check {
+    /// The skill or characteristic to roll under.
+    pub skill: Expression,
+    /// Number of bonus dice, or penalty dice if negative.
+    pub bonus: i64,
+}
+
+impl FancyFormat for Check {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        let skill = self.skill.format(markdown, verbose);
+        match self.bonus {
+            0 => format!("cthulhu({skill})"),
+            n if n > 0 => format!("cthulhu({skill}, bonus={n})"),
+            n => format!("cthulhu({skill}, penalty={})", -n),
+        }
+    }
+}
+
+impl Rollable for Check {
+    type Roll = Result<EvaluatedCheck>;
+
+    fn roll_with_source(&self, rng: &mut dyn DiceRollSource) -> Self::Roll {
+        let skill = self.skill.roll_with_source(rng)?;
+        let extra = self.bonus.unsigned_abs() as usize;
+        limit_dice(extra + 2, "bonus dice")?;
+        let units = rng.roll_single_die(10) as u32 - 1;
+        let tens: Vec<u32> = (0..=extra)
+            .map(|_| (rng.roll_single_die(10) as u32 - 1) * 10)
+            .collect();
+        let value = |tens: u32| match tens + units {
+            0 => 100,
+            n => n,
+        };
+        let chosen = (0..tens.len())
+            .min_by_key(|i| {
+                let value = i64::from(value(tens[*i]));
+                if self.bonus < 0 { -value } else { value }
+            })
+            .unwrap();
+        Ok(EvaluatedCheck {
+            skill,
+            tens,
+            chosen,
+            units,
+        })
+    }
+}
+
+impl ExpressionRollable for Check {
+    fn expression_roll(&self, rng: &mut dyn DiceRollSource) -> ExpressionResult {
+        Ok(Box::new(self.roll_with_source(rng)?))
+    }
+
+    fn roll_total(&self, rng: &mut dyn DiceRollSource) -> Result<Number> {
+        Ok(self.roll_with_source(rng)?.total())
+    }
+}
+
+impl Preset for Check {
+    fn roll_outcome(&self, rng: &mut dyn DiceRollSource) -> Result<Box<dyn Outcome>> {
+        Ok(Box::new(self.roll_with_source(rng)?))
+    }
+}
+
+/// Result of rolling a [Check].
+#[derive(Debug)]
+pub struct EvaluatedCheck {
+    skill: Box<dyn EvaluatedExpression>,
+    /// Each tens die, as 0 to 90.
+    tens: Vec<u32>,
+    /// Index of the tens die used.
+    chosen: usize,
+    units: u32,
+}
+
+impl EvaluatedCheck {
+    /// The percentile rolled, from 1 to 100.
+    pub fn roll(&self) -> u32 {
+        match self.chosen_tens() + self.units {
+            0 => 100,
+            n => n,
+        }
+    }
+
+    /// All the tens dice rolled, as 0 to 90.
+    pub fn tens(&self) -> &[u32] {
+        &self.tens
+    }
+
+    /// The tens die used for the roll: the best with bonus dice, or the worst with penalty dice.
+    pub fn chosen_tens(&self) -> u32 {
+        self.tens[self.chosen]
+    }
+
+    /// The units die, from 0 to 9.
+    pub fn units(&self) -> u32 {
+        self.units
+    }
+
+    /// The skill rolled under, rounded down.
+    pub fn skill(&self) -> i64 {
+        self.skill.total().to_integer(Rounding::Down)
+    }
+
+    /// How well the check went.
+    pub fn level(&self) -> SuccessLevel {
+        SuccessLevel::of(self.roll(), self.skill())
+    }
+}
+
+impl EvaluatedExpression for EvaluatedCheck {
+    fn total(&self) -> Number {
+        i64::from(self.roll()).into()
+    }
+
+    fn format_history(&self, markdown: bool, verbose: Verbosity) -> String {
+        let tens = self.tens.iter().enumerate().map(|(i, tens)| {
+            let tens = format!("{tens:02}");
+            match (i == self.chosen, markdown) {
+                (true, _) => tens,
+                (false, true) => format!("~~*{tens}*~~"),
+                (false, false) => format!("Drop({tens})"),
+            }
+        });
+        let mut skill = self.skill.format_history(markdown, verbose);
+        if skill != self.skill().to_string() {
+            skill += &format!(" = {}", self.skill());
+        }
+        format!(
+            "{} + {} ({} vs {skill})",
+            format_rolls(tens, markdown),
+            format_rolls([self.units].iter(), markdown),
+            format_italic(self.level().label(), markdown),
+        )
+    }
+
+    fn dice(&self) -> Vec<KeptDie> {
+        let mut dice = self.skill.dice();
+        dice.push(KeptDie {
+            value: self.roll().into(),
+            min: 1,
+            max: 100,
+        });
+        dice
+    }
+}
+
+impl Outcome for EvaluatedCheck {
+    fn label(&self) -> Option<String> {
+        Some(self.level().label().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::IteratorDiceRollSource;
+
+    fn roll(expression: &str, rolls: Vec<u64>) -> Box<dyn EvaluatedExpression> {
+        Expression::parse(expression)
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut rolls.into_iter(),
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn levels() {
+        assert_eq!(SuccessLevel::of(1, 10), SuccessLevel::Critical);
+        assert_eq!(SuccessLevel::of(13, 65), SuccessLevel::Extreme);
+        assert_eq!(SuccessLevel::of(32, 65), SuccessLevel::Hard);
+        assert_eq!(SuccessLevel::of(65, 65), SuccessLevel::Regular);
+        assert_eq!(SuccessLevel::of(66, 65), SuccessLevel::Failure);
+        assert_eq!(SuccessLevel::of(96, 65), SuccessLevel::Failure);
+        assert_eq!(SuccessLevel::of(96, 45), SuccessLevel::Fumble);
+        assert_eq!(SuccessLevel::of(100, 65), SuccessLevel::Fumble);
+        assert_eq!(SuccessLevel::of(100, 100), SuccessLevel::Fumble);
+        assert_eq!(SuccessLevel::of(100, 120), SuccessLevel::Fumble);
+        assert_eq!(SuccessLevel::of(99, 100), SuccessLevel::Regular);
+    }
+
+    #[test]
+    fn bonus_and_penalty() {
+        // Units 4, then tens 60 and 30
+        let result = roll("cthulhu(70, bonus=1)", vec![5, 7, 4]);
+        assert_eq!(result.total(), Number::from(34));
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "[Drop(60), 30] + [4] (Hard success vs 70) = 34"
+        );
+        let result = roll("cthulhu(65, penalty=1)", vec![5, 7, 4]);
+        assert_eq!(
+            result.format(true, Verbosity::Medium),
+            "\\[60, ~~*30*~~\\] + \\[4\\] (*Regular success* vs 65) = **64**"
+        );
+        // 00 and 0 is 100, the worst roll, so a bonus die picks 90 over it
+        let result = roll("cthulhu(40, bonus=1)", vec![1, 1, 10]);
+        assert_eq!(result.total(), Number::from(90));
+        let result = roll("cthulhu(40)", vec![1, 1]);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "[00] + [0] (Fumble vs 40) = 100"
+        );
+        // A rolled skill shows its dice
+        let result = roll("cthulhu(1d20 + 40)", vec![15, 6, 6]);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "[50] + [5] (Regular success vs [15] + 40 = 55) = 55"
+        );
+        assert_eq!(result.dice().len(), 2);
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(
+            format!(
+                "{}",
+                Expression::parse("cthulhu(50 + 5, penalty=2)").unwrap()
+            ),
+            "cthulhu(50 + 5, penalty=2)"
+        );
+        assert_eq!(
+            format!("{}", Expression::parse("cthulhu(40) + 1").unwrap()),
+            "cthulhu(40) + 1"
+        );
+    }
+}
@@ -0,0 +1,305 @@
+//! Daggerheart: a Hope d12 and a Fear d12 added together, where the higher die colors the result.
+
+use crate::{
+    Advantage, DiceRollSource, EvaluatedExpression, Expression, FancyFormat, KeptDie, Number,
+    Result, Rollable, Verbosity,
+    dice_expression::format_rolls,
+    expression::{ExpressionResult, ExpressionRollable, format_bold, format_italic},
+};
+
+use super::{Outcome, Preset};
+
+/// Which die came out on top in an [EvaluatedDuality].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Duality {
+    /// The Hope die is higher.
+    Hope,
+    /// The Fear die is higher.
+    Fear,
+    /// Both dice match.
+    Critical,
+}
+
+impl Duality {
+    /// Like "with Hope".
+    pub fn label(&self) -> &'static str {
+        match self {
+            Duality::Hope => "with Hope",
+            Duality::Fear => "with Fear",
+            Duality::Critical => "Critical",
+        }
+    }
+}
+
+/// The `duality` dice: Hope and Fear d12s, with a d6 added for advantage or subtracted for disadvantage.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct DualityDice {
+    pub(crate) advantage: Advantage,
+}
+
+impl FancyFormat for DualityDice {
+    fn format(&self, _markdown: bool, _verbose: Verbosity) -> String {
+        match self.advantage {
+            Advantage::Normal => "duality",
+            Advantage::Advantage => "duality(adv)",
+            Advantage::Disadvantage => "duality(dis)",
+        }
+        .to_string()
+    }
+}
+
+impl Rollable for DualityDice {
+    type Roll = EvaluatedDuality;
+
+    fn roll_with_source(&self, rng: &mut dyn DiceRollSource) -> Self::Roll {
+        let hope = rng.roll_single_die(12) as u32;
+        let fear = rng.roll_single_die(12) as u32;
+        let advantage = match self.advantage {
+            Advantage::Normal => None,
+            _ => Some(rng.roll_single_die(6) as u32),
+        };
+        EvaluatedDuality {
+            hope,
+            fear,
+            advantage: self.advantage,
+            advantage_die: advantage,
+        }
+    }
+}
+
+impl ExpressionRollable for DualityDice {
+    fn expression_roll(&self, rng: &mut dyn DiceRollSource) -> ExpressionResult {
+        Ok(Box::new(self.roll_with_source(rng)))
+    }
+
+    fn roll_total(&self, rng: &mut dyn DiceRollSource) -> Result<Number> {
+        Ok(self.roll_with_source(rng).total())
+    }
+}
+
+/// Result of rolling Hope and Fear dice.
+#[derive(Clone, Copy, Debug)]
+pub struct EvaluatedDuality {
+    hope: u32,
+    fear: u32,
+    advantage: Advantage,
+    advantage_die: Option<u32>,
+}
+
+impl EvaluatedDuality {
+    /// The Hope die.
+    pub fn hope(&self) -> u32 {
+        self.hope
+    }
+
+    /// The Fear die.
+    pub fn fear(&self) -> u32 {
+        self.fear
+    }
+
+    /// The advantage or disadvantage d6, if there was one.
+    pub fn advantage_die(&self) -> Option<u32> {
+        self.advantage_die
+    }
+
+    /// Which die is higher.
+    pub fn duality(&self) -> Duality {
+        match self.hope.cmp(&self.fear) {
+            std::cmp::Ordering::Greater => Duality::Hope,
+            std::cmp::Ordering::Less => Duality::Fear,
+            std::cmp::Ordering::Equal => Duality::Critical,
+        }
+    }
+}
+
+impl EvaluatedExpression for EvaluatedDuality {
+    fn total(&self) -> Number {
+        let d6 = i64::from(self.advantage_die.unwrap_or(0));
+        let d6 = if self.advantage == Advantage::Disadvantage {
+            -d6
+        } else {
+            d6
+        };
+        (i64::from(self.hope) + i64::from(self.fear) + d6).into()
+    }
+
+    fn format_history(&self, markdown: bool, _verbose: Verbosity) -> String {
+        let mut s = format!(
+            "Hope {} + Fear {}",
+            format_rolls([self.hope].iter(), markdown),
+            format_rolls([self.fear].iter(), markdown)
+        );
+        if let Some(d6) = self.advantage_die {
+            s += match self.advantage {
+                Advantage::Disadvantage => " - Disadvantage ",
+                _ => " + Advantage ",
+            };
+            s += &format_rolls([d6].iter(), markdown);
+        }
+        format!("{s} ({})", format_italic(self.duality().label(), markdown))
+    }
+
+    fn dice(&self) -> Vec<KeptDie> {
+        let mut dice = vec![
+            KeptDie {
+                value: self.hope.into(),
+                min: 1,
+                max: 12,
+            },
+            KeptDie {
+                value: self.fear.into(),
+                min: 1,
+                max: 12,
+            },
+        ];
+        dice.extend(self.advantage_die.map(|d6| KeptDie {
+            value: d6.into(),
+            min: 1,
+            max: 6,
+        }));
+        dice
+    }
+}
+
+/// An action roll: duality dice plus a modifier.
+#[derive(Clone, Debug, Default)]
+pub struct Action {
+    /// Added to the dice.
+    pub modifier: Option<Expression>,
+    /// Adds a d6 for advantage, or subtracts one for disadvantage.
+    pub advantage: Advantage,
+}
+
+impl FancyFormat for Action {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        let dice = DualityDice {
+            advantage: self.advantage,
+        }
+        .format(markdown, verbose);
+        match &self.modifier {
+            Some(modifier) => format!("{dice} + {}", modifier.format(markdown, verbose)),
+            None => dice,
+        }
+    }
+}
+
+impl Rollable for Action {
+    type Roll = Result<EvaluatedAction>;
+
+    fn roll_with_source(&self, rng: &mut dyn DiceRollSource) -> Self::Roll {
+        let duality = DualityDice {
+            advantage: self.advantage,
+        }
+        .roll_with_source(rng);
+        let modifier = match &self.modifier {
+            Some(modifier) => Some(modifier.roll_with_source(rng)?),
+            None => None,
+        };
+        Ok(EvaluatedAction { duality, modifier })
+    }
+}
+
+impl Preset for Action {
+    fn roll_outcome(&self, rng: &mut dyn DiceRollSource) -> Result<Box<dyn Outcome>> {
+        Ok(Box::new(self.roll_with_source(rng)?))
+    }
+}
+
+/// Result of rolling an [Action].
+#[derive(Debug)]
+pub struct EvaluatedAction {
+    duality: EvaluatedDuality,
+    modifier: Option<Box<dyn EvaluatedExpression>>,
+}
+
+impl EvaluatedAction {
+    /// The rolled dice.
+    pub fn duality(&self) -> &EvaluatedDuality {
+        &self.duality
+    }
+
+    /// Dice plus the modifier.
+    pub fn total(&self) -> Number {
+        self.duality.total()
+            + self
+                .modifier
+                .as_ref()
+                .map_or(Number::from(0), |m| m.total())
+    }
+}
+
+impl FancyFormat for EvaluatedAction {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        let mut s = self.duality.format_history(markdown, verbose);
+        if let Some(modifier) = &self.modifier {
+            s += &format!(" + {}", modifier.format_history(markdown, verbose));
+        }
+        s + &format!(" = {}", format_bold(self.total(), markdown))
+    }
+}
+
+impl Outcome for EvaluatedAction {
+    fn label(&self) -> Option<String> {
+        Some(self.duality.duality().label().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::IteratorDiceRollSource;
+
+    fn roll(expression: &str, rolls: Vec<u64>) -> Box<dyn EvaluatedExpression> {
+        Expression::parse(expression)
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut rolls.into_iter(),
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn expression() {
+        let result = roll("duality + 3", vec![7, 4]);
+        assert_eq!(result.total(), Number::from(14));
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "Hope [7] + Fear [4] (with Hope) + 3 = 14"
+        );
+        let result = roll("duality(dis) + 1", vec![2, 9, 5]);
+        assert_eq!(
+            result.format(true, Verbosity::Medium),
+            "Hope \\[2\\] + Fear \\[9\\] - Disadvantage \\[5\\] (*with Fear*) + 1 = **7**"
+        );
+        let result = roll("duality(adv)", vec![6, 6, 3]);
+        assert_eq!(result.total(), Number::from(15));
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "Hope [6] + Fear [6] + Advantage [3] (Critical) = 15"
+        );
+        assert_eq!(
+            format!("{}", Expression::parse("duality(adv) + 2").unwrap()),
+            "duality(adv) + 2"
+        );
+    }
+
+    #[test]
+    fn action() {
+        let action = Action {
+            modifier: Some(Expression::parse("2").unwrap()),
+            advantage: Advantage::Normal,
+        };
+        let result = action
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [3, 11].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(result.duality().duality(), Duality::Fear);
+        assert_eq!(result.total(), Number::from(16));
+        assert_eq!(result.label().unwrap(), "with Fear");
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "Hope [3] + Fear [11] (with Fear) + 2 = 16"
+        );
+    }
+}
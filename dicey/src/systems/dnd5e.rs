@@ -0,0 +1,157 @@
+//! Dungeons & Dragons 5th edition: d20 checks and attacks, with advantage and disadvantage.
+
+use crate::{
+    Advantage, AttackTemplate, DiceRollSource, EvaluatedExpression, Expression, FancyFormat,
+    Number, Result, Rollable, Verbosity, expression::format_bold,
+};
+
+use super::{Outcome, Preset, with_modifier};
+
+/// An attack roll. Critical hits double the damage dice.
+pub type Attack = AttackTemplate;
+
+/// An ability check or saving throw: d20 plus a modifier, against an optional difficulty class.
+#[derive(Clone, Debug)]
+pub struct Check {
+    /// Ability modifier, plus proficiency and any other bonuses.
+    pub modifier: Option<Expression>,
+    /// How the d20 is rolled.
+    pub advantage: Advantage,
+    /// Difficulty class: the check succeeds if the total is at least this.
+    pub dc: Option<i64>,
+}
+
+impl FancyFormat for Check {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        let mut s = self.advantage.notation().to_string();
+        if let Some(modifier) = &self.modifier {
+            s += &format!(" + {}", modifier.format(markdown, verbose));
+        }
+        if let Some(dc) = self.dc {
+            s += &format!(" vs DC {dc}");
+        }
+        s
+    }
+}
+
+impl Rollable for Check {
+    type Roll = Result<EvaluatedCheck>;
+
+    fn roll_with_source(&self, rng: &mut dyn DiceRollSource) -> Self::Roll {
+        let roll =
+            with_modifier(self.advantage.notation(), &self.modifier)?.roll_with_source(rng)?;
+        // The d20 is the left most term, so the first kept die.
+        let natural = roll.dice()[0].value;
+        Ok(EvaluatedCheck {
+            success: self.dc.map(|dc| roll.total() >= Number::from(dc)),
+            roll,
+            natural,
+            dc: self.dc,
+        })
+    }
+}
+
+impl Preset for Check {
+    fn roll_outcome(&self, rng: &mut dyn DiceRollSource) -> Result<Box<dyn Outcome>> {
+        Ok(Box::new(self.roll_with_source(rng)?))
+    }
+}
+
+/// Result of rolling a [Check].
+#[derive(Debug)]
+pub struct EvaluatedCheck {
+    roll: Box<dyn EvaluatedExpression>,
+    natural: i64,
+    dc: Option<i64>,
+    success: Option<bool>,
+}
+
+impl EvaluatedCheck {
+    /// The kept d20.
+    pub fn natural(&self) -> i64 {
+        self.natural
+    }
+
+    /// The d20 plus modifiers.
+    pub fn total(&self) -> Number {
+        self.roll.total()
+    }
+
+    /// If the check met the difficulty class, or [None] if there was no difficulty class.
+    pub fn success(&self) -> Option<bool> {
+        self.success
+    }
+}
+
+impl FancyFormat for EvaluatedCheck {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        let roll = self.roll.format(markdown, verbose);
+        match (self.label(), self.dc) {
+            (Some(label), Some(dc)) => {
+                format!("{} {roll} vs DC {dc}", format_bold(label, markdown))
+            }
+            _ => roll,
+        }
+    }
+}
+
+impl Outcome for EvaluatedCheck {
+    fn label(&self) -> Option<String> {
+        self.success
+            .map(|success| if success { "Success" } else { "Failure" }.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::IteratorDiceRollSource;
+
+    #[test]
+    fn check() {
+        let check = Check {
+            modifier: Some(Expression::parse("4").unwrap()),
+            advantage: Advantage::Advantage,
+            dc: Some(15),
+        };
+        assert_eq!(
+            check.format(false, Verbosity::Medium),
+            "2d20K1 + 4 vs DC 15"
+        );
+        let result = check
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [11, 6].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(result.natural(), 11);
+        assert_eq!(result.success(), Some(true));
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "Success [11, Drop(6)]K1 + 4 = 15 vs DC 15"
+        );
+
+        let result = check
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [3, 10].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(result.natural(), 10);
+        assert_eq!(result.success(), Some(false));
+    }
+
+    #[test]
+    fn no_dc() {
+        let check = Check {
+            modifier: None,
+            advantage: Advantage::Normal,
+            dc: None,
+        };
+        let result = check
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [7].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(result.label(), None);
+        assert_eq!(result.format(false, Verbosity::Medium), "[7] = 7");
+    }
+}
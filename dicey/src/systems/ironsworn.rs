@@ -0,0 +1,299 @@
+//! Ironsworn and Starforged: an action die plus stat and adds, against two challenge dice.
+
+use crate::{
+    DiceRollSource, EvaluatedExpression, Expression, FancyFormat, KeptDie, Number, Result,
+    Rollable, Rounding, Verbosity,
+    dice_expression::format_rolls,
+    expression::{ExpressionResult, ExpressionRollable, format_italic},
+};
+
+use super::{Outcome, Preset};
+
+/// How an [Action] went, from worst to best.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Hit {
+    /// The action score beat neither challenge die.
+    Miss,
+    /// The action score beat one challenge die.
+    Weak,
+    /// The action score beat both challenge dice.
+    Strong,
+}
+
+impl Hit {
+    /// Like "Weak hit".
+    pub fn label(&self) -> &'static str {
+        match self {
+            Hit::Miss => "Miss",
+            Hit::Weak => "Weak hit",
+            Hit::Strong => "Strong hit",
+        }
+    }
+
+    fn against(score: i64, challenge: [u32; 2]) -> Hit {
+        match challenge.iter().filter(|c| score > i64::from(**c)).count() {
+            0 => Hit::Miss,
+            1 => Hit::Weak,
+            _ => Hit::Strong,
+        }
+    }
+}
+
+/// An action roll, like `ironsworn(2 + 1, momentum=5)`.
+#[derive(Clone, Debug, Default)]
+pub struct Action {
+    /// Stat and adds, added to the action die.
+    pub modifier: Option<Expression>,
+    /// Current momentum, to offer burning it, or to cancel the action die when negative.
+    pub momentum: Option<i64>,
+}
+
+impl Action {
+    /// The highest an action score can be.
+    pub const MAX_SCORE: i64 = 10;
+}
+
+impl FancyFormat for Action {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        let mut s = format!(
+            "ironsworn({}",
+            self.modifier
+                .as_ref()
+                .map_or("0".to_string(), |m| m.format(markdown, verbose))
+        );
+        if let Some(momentum) = self.momentum {
+            s += &format!(", momentum={momentum}");
+        }
+        s + ")"
+    }
+}
+
+impl Rollable for Action {
+    type Roll = Result<EvaluatedAction>;
+
+    fn roll_with_source(&self, rng: &mut dyn DiceRollSource) -> Self::Roll {
+        let action_die = rng.roll_single_die(6) as u32;
+        let modifier = match &self.modifier {
+            Some(modifier) => Some(modifier.roll_with_source(rng)?),
+            None => None,
+        };
+        let challenge = [
+            rng.roll_single_die(10) as u32,
+            rng.roll_single_die(10) as u32,
+        ];
+        Ok(EvaluatedAction {
+            action_die,
+            modifier,
+            challenge,
+            momentum: self.momentum,
+        })
+    }
+}
+
+impl ExpressionRollable for Action {
+    fn expression_roll(&self, rng: &mut dyn DiceRollSource) -> ExpressionResult {
+        Ok(Box::new(self.roll_with_source(rng)?))
+    }
+
+    fn roll_total(&self, rng: &mut dyn DiceRollSource) -> Result<Number> {
+        Ok(self.roll_with_source(rng)?.total())
+    }
+}
+
+impl Preset for Action {
+    fn roll_outcome(&self, rng: &mut dyn DiceRollSource) -> Result<Box<dyn Outcome>> {
+        Ok(Box::new(self.roll_with_source(rng)?))
+    }
+}
+
+/// Result of rolling an [Action].
+#[derive(Debug)]
+pub struct EvaluatedAction {
+    action_die: u32,
+    modifier: Option<Box<dyn EvaluatedExpression>>,
+    challenge: [u32; 2],
+    momentum: Option<i64>,
+}
+
+impl EvaluatedAction {
+    /// The challenge dice.
+    pub fn challenge(&self) -> [u32; 2] {
+        self.challenge
+    }
+
+    /// If negative momentum matching the action die cancelled it.
+    pub fn cancelled(&self) -> bool {
+        self.momentum
+            .is_some_and(|m| m < 0 && -m == i64::from(self.action_die))
+    }
+
+    /// Action die (unless cancelled) plus the modifier, capped at [Action::MAX_SCORE].
+    pub fn action_score(&self) -> i64 {
+        let die = if self.cancelled() {
+            0
+        } else {
+            i64::from(self.action_die)
+        };
+        let modifier = self
+            .modifier
+            .as_ref()
+            .map_or(0, |m| m.total().to_integer(Rounding::Down));
+        (die + modifier).min(Action::MAX_SCORE)
+    }
+
+    /// Result of comparing the action score to the challenge dice.
+    pub fn hit(&self) -> Hit {
+        Hit::against(self.action_score(), self.challenge)
+    }
+
+    /// If both challenge dice are the same.
+    pub fn is_match(&self) -> bool {
+        self.challenge[0] == self.challenge[1]
+    }
+
+    /// The better result burning momentum would give instead, if it would give one.
+    pub fn burn(&self) -> Option<Hit> {
+        let burned = Hit::against(self.momentum?, self.challenge);
+        (burned > self.hit()).then_some(burned)
+    }
+}
+
+impl EvaluatedExpression for EvaluatedAction {
+    /// The action score.
+    fn total(&self) -> Number {
+        self.action_score().into()
+    }
+
+    fn format_history(&self, markdown: bool, verbose: Verbosity) -> String {
+        let action = if self.cancelled() {
+            format_rolls(
+                [if markdown {
+                    format!("~~*{}*~~", self.action_die)
+                } else {
+                    format!("Cancel({})", self.action_die)
+                }]
+                .iter(),
+                markdown,
+            )
+        } else {
+            format_rolls([self.action_die].iter(), markdown)
+        };
+        let mut s = format!("Action {action}");
+        if let Some(modifier) = &self.modifier {
+            s += &format!(" + {}", modifier.format_history(markdown, verbose));
+        }
+        let mut outcome = format_italic(self.label().unwrap(), markdown);
+        if let Some(burn) = self.burn() {
+            outcome += &format!(
+                "; burn momentum for {}",
+                format_italic(burn.label(), markdown)
+            );
+        }
+        format!(
+            "{s} vs Challenge {} ({outcome})",
+            format_rolls(self.challenge.iter(), markdown)
+        )
+    }
+
+    fn dice(&self) -> Vec<KeptDie> {
+        let mut dice = vec![KeptDie {
+            value: self.action_die.into(),
+            min: 1,
+            max: 6,
+        }];
+        dice.extend(self.modifier.iter().flat_map(|m| m.dice()));
+        dice
+    }
+}
+
+impl Outcome for EvaluatedAction {
+    fn label(&self) -> Option<String> {
+        Some(if self.is_match() {
+            format!("{} with a match", self.hit().label())
+        } else {
+            self.hit().label().to_string()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::IteratorDiceRollSource;
+
+    fn roll(expression: &str, rolls: Vec<u64>) -> Box<dyn EvaluatedExpression> {
+        Expression::parse(expression)
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut rolls.into_iter(),
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn hits() {
+        let result = roll("ironsworn(2 + 1)", vec![4, 3, 9]);
+        assert_eq!(result.total(), Number::from(7));
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "Action [4] + 2 + 1 vs Challenge [3, 9] (Weak hit) = 7"
+        );
+        let result = roll("ironsworn(3)", vec![6, 5, 5]);
+        assert_eq!(
+            result.format(true, Verbosity::Medium),
+            "Action \\[6\\] + 3 vs Challenge \\[5, 5\\] (*Strong hit with a match*) = **9**"
+        );
+        // Capped at 10, which never beats a 10
+        let result = roll("ironsworn(6)", vec![6, 10, 2]);
+        assert_eq!(result.total(), Number::from(10));
+        assert!(
+            result
+                .format_history(false, Verbosity::Short)
+                .contains("Weak hit")
+        );
+    }
+
+    #[test]
+    fn momentum() {
+        let result = roll("ironsworn(1, momentum=8)", vec![2, 4, 7]);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "Action [2] + 1 vs Challenge [4, 7] (Miss; burn momentum for Strong hit) = 3"
+        );
+        // No better result from burning
+        let result = roll("ironsworn(4, momentum=5)", vec![2, 5, 1]);
+        assert_eq!(
+            result.format_history(false, Verbosity::Short),
+            "Action [2] + 4 vs Challenge [5, 1] (Strong hit)"
+        );
+        let result = roll("ironsworn(2, momentum=-3)", vec![3, 1, 8]);
+        assert_eq!(result.total(), Number::from(2));
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "Action [Cancel(3)] + 2 vs Challenge [1, 8] (Weak hit) = 2"
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                Expression::parse("ironsworn(2+1,momentum=-2)").unwrap()
+            ),
+            "ironsworn(2 + 1, momentum=-2)"
+        );
+    }
+
+    #[test]
+    fn preset() {
+        let action = Action {
+            modifier: Some(Expression::parse("2").unwrap()),
+            momentum: None,
+        };
+        let result = action
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [3, 7, 7].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(result.hit(), Hit::Miss);
+        assert!(result.is_match());
+        assert_eq!(result.label().unwrap(), "Miss with a match");
+    }
+}
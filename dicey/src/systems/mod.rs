@@ -0,0 +1,280 @@
+//! Presets for rolls in specific game systems.
+//!
+//! Each preset rolls its dice and reports a structured outcome (like "Weak hit" or "Partial success"),
+//! not just a number.
+//! Presets can be used directly, or looked up by system name with [preset].
+
+use std::{collections::HashMap, fmt::Debug};
+
+use crate::{
+    Advantage, AttackOutcome, AttackTemplate, DiceRollSource, EvaluatedAttack, Expression,
+    FancyFormat, Result, RollError, Rollable,
+};
+
+pub mod blades;
+pub mod cortex;
+pub mod cthulhu;
+pub mod daggerheart;
+pub mod dnd5e;
+pub mod ironsworn;
+pub mod pbta;
+pub mod savage_worlds;
+pub mod year_zero;
+
+/// A roll in a game system.
+pub trait Preset: Debug + FancyFormat {
+    /// Roll the dice with provided dice roll source, and determine the outcome.
+    fn roll_outcome(&self, rng: &mut dyn DiceRollSource) -> Result<Box<dyn Outcome>>;
+}
+
+/// Structured result of rolling a [Preset].
+pub trait Outcome: Debug + FancyFormat {
+    /// Short description of the outcome, like "Strong hit", if the system has one.
+    fn label(&self) -> Option<String>;
+}
+
+impl Rollable for Box<dyn Preset> {
+    type Roll = Result<Box<dyn Outcome>>;
+
+    fn roll_with_source(&self, rng: &mut dyn DiceRollSource) -> Self::Roll {
+        self.roll_outcome(rng)
+    }
+}
+
+impl Preset for AttackTemplate {
+    fn roll_outcome(&self, rng: &mut dyn DiceRollSource) -> Result<Box<dyn Outcome>> {
+        Ok(Box::new(self.roll_with_source(rng)?))
+    }
+}
+
+impl Outcome for EvaluatedAttack {
+    fn label(&self) -> Option<String> {
+        Some(
+            match self.outcome() {
+                AttackOutcome::CriticalMiss => "Critical miss",
+                AttackOutcome::Miss => "Miss",
+                AttackOutcome::Hit => "Hit",
+                AttackOutcome::CriticalHit => "Critical hit",
+            }
+            .to_string(),
+        )
+    }
+}
+
+/// Parse `dice`, adding `modifier` if there is one.
+fn with_modifier(dice: &str, modifier: &Option<Expression>) -> Result<Expression> {
+    let dice = Expression::parse(dice)?;
+    Ok(match modifier {
+        Some(modifier) => Expression::add(dice, modifier.clone()),
+        None => dice,
+    })
+}
+
+/// Names of all parameters used by any system in [preset].
+pub const PARAMETER_NAMES: &[&str] = &[
+    "m", "adv", "dc", "d", "base", "skill", "gear", "wild", "tn", "pool", "bonus", "penalty",
+    "momentum",
+];
+
+/// Look up a preset by system name, configured from named parameters.
+///
+/// Supported systems and their parameters (all optional unless noted):
+/// - `dnd5e`: `m` modifier, `adv` (`+` for advantage, `-` for disadvantage), `dc`.
+/// - `pbta`: `m` modifier.
+/// - `blades`: `d` number of dice (required).
+/// - `year_zero`: `base`, `skill` and `gear` numbers of dice.
+/// - `savage_worlds`: `d` trait die, like `d8` (required), `wild` die (defaults to `d6`, `none` for extras), `m` modifier, `tn` target number.
+/// - `daggerheart`: `m` modifier, `adv` (`+` for advantage, `-` for disadvantage).
+/// - `cthulhu`: `skill` (required), `bonus` or `penalty` number of dice.
+/// - `cortex`: `pool` of dice, like `d8 d6 d10` (required).
+/// - `ironsworn`: `m` stat plus adds, `momentum`.
+///
+/// Modifiers are dice expressions, which can reference `variables`.
+pub fn preset(
+    system: &str,
+    parameters: &HashMap<String, String>,
+    variables: &HashMap<String, Expression>,
+) -> Result<Box<dyn Preset>> {
+    let parameters = Parameters {
+        parameters,
+        variables,
+    };
+    Ok(match system {
+        "dnd5e" => Box::new(dnd5e::Check {
+            modifier: parameters.expression("m")?,
+            advantage: parameters.advantage("adv")?,
+            dc: parameters.parse("dc")?,
+        }),
+        "pbta" => Box::new(pbta::Move {
+            modifier: parameters.expression("m")?,
+        }),
+        "blades" => Box::new(blades::Action {
+            dice: parameters.required("d")?,
+        }),
+        "year_zero" => Box::new(year_zero::Pool {
+            base: parameters.parse("base")?.unwrap_or(0),
+            skill: parameters.parse("skill")?.unwrap_or(0),
+            gear: parameters.parse("gear")?.unwrap_or(0),
+        }),
+        "savage_worlds" => Box::new(savage_worlds::Trait {
+            die: parameters.die("d")?.ok_or("Missing parameter \"d\"")?,
+            wild: match parameters.parameters.get("wild").map(|wild| wild.trim()) {
+                Some("none") => None,
+                _ => parameters
+                    .die("wild")?
+                    .or(Some(savage_worlds::Trait::WILD_DIE)),
+            },
+            modifier: parameters.expression("m")?,
+            target: parameters
+                .parse("tn")?
+                .unwrap_or(savage_worlds::Trait::TARGET),
+        }),
+        "daggerheart" => Box::new(daggerheart::Action {
+            modifier: parameters.expression("m")?,
+            advantage: parameters.advantage("adv")?,
+        }),
+        "cthulhu" => Box::new(cthulhu::Check {
+            skill: parameters
+                .expression("skill")?
+                .ok_or("Missing parameter \"skill\"")?,
+            bonus: parameters.parse::<i64>("bonus")?.unwrap_or(0)
+                - parameters.parse::<i64>("penalty")?.unwrap_or(0),
+        }),
+        "cortex" => Box::new(cortex::Pool::parse(
+            parameters
+                .parameters
+                .get("pool")
+                .ok_or("Missing parameter \"pool\"")?,
+        )?),
+        "ironsworn" => Box::new(ironsworn::Action {
+            modifier: parameters.expression("m")?,
+            momentum: parameters.parse("momentum")?,
+        }),
+        _ => return Err(format!("Unknown game system \"{system}\"").into()),
+    })
+}
+
+struct Parameters<'a> {
+    parameters: &'a HashMap<String, String>,
+    variables: &'a HashMap<String, Expression>,
+}
+
+impl Parameters<'_> {
+    fn parse<T: std::str::FromStr>(&self, name: &str) -> Result<Option<T>> {
+        self.parameters
+            .get(name)
+            .map(|value| {
+                value.trim().parse::<T>().map_err(|_| {
+                    RollError::ParamError(format!("Invalid value \"{value}\" for \"{name}\""))
+                })
+            })
+            .transpose()
+    }
+
+    fn required<T: std::str::FromStr>(&self, name: &str) -> Result<T> {
+        self.parse(name)?
+            .ok_or_else(|| format!("Missing parameter \"{name}\"").into())
+    }
+
+    fn expression(&self, name: &str) -> Result<Option<Expression>> {
+        self.parameters
+            .get(name)
+            .map(|value| Expression::parse_with_variables(value, self.variables))
+            .transpose()
+    }
+
+    fn advantage(&self, name: &str) -> Result<Advantage> {
+        Ok(match self.parameters.get(name).map(|s| s.trim()) {
+            None | Some("") => Advantage::Normal,
+            Some("+") => Advantage::Advantage,
+            Some("-") => Advantage::Disadvantage,
+            Some(value) => Err(format!("Invalid value \"{value}\" for \"{name}\""))?,
+        })
+    }
+
+    /// A die size, like "d8" or "8".
+    fn die(&self, name: &str) -> Result<Option<u32>> {
+        self.parameters
+            .get(name)
+            .map(|value| {
+                let trimmed = value.trim();
+                trimmed
+                    .strip_prefix(['d', 'D'])
+                    .unwrap_or(trimmed)
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|sides| *sides > 1)
+                    .ok_or_else(|| {
+                        RollError::ParamError(format!("Invalid die \"{value}\" for \"{name}\""))
+                    })
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Verbosity, tests::IteratorDiceRollSource};
+
+    fn parameters(values: &[(&str, &str)]) -> HashMap<String, String> {
+        values
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn lookup() {
+        let mut variables = HashMap::default();
+        variables.insert("str".to_string(), Expression::parse("3").unwrap());
+        let preset = preset("pbta", &parameters(&[("m", "$str")]), &variables).unwrap();
+        let outcome = preset
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [4, 3].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(outcome.label().unwrap(), "Strong hit");
+        assert_eq!(
+            outcome.format(false, Verbosity::Short),
+            "Strong hit [4, 3] + $str = 10"
+        );
+    }
+
+    #[test]
+    fn extras() {
+        let variables = HashMap::default();
+        let wild = |value: &str| {
+            preset(
+                "savage_worlds",
+                &parameters(&[("d", "d8"), ("wild", value)]),
+                &variables,
+            )
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [3, 5].into_iter(),
+            })
+            .unwrap()
+            .format(false, Verbosity::Short)
+        };
+        assert_eq!(wild("none"), "Failure Trait [3] 🡲 [3] = 3");
+        assert_eq!(wild("d6"), "Success Trait [3] 🡲 [3] Wild [5] 🡲 [5] = 5");
+    }
+
+    #[test]
+    fn lookup_errors() {
+        let variables = HashMap::default();
+        assert_eq!(
+            preset("chess", &parameters(&[]), &variables).unwrap_err(),
+            RollError::ParamError("Unknown game system \"chess\"".into())
+        );
+        assert_eq!(
+            preset("blades", &parameters(&[]), &variables).unwrap_err(),
+            RollError::ParamError("Missing parameter \"d\"".into())
+        );
+        assert_eq!(
+            preset("savage_worlds", &parameters(&[("d", "x")]), &variables).unwrap_err(),
+            RollError::ParamError("Invalid die \"x\" for \"d\"".into())
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! Powered by the Apocalypse: 2d6 plus a stat, read as a miss, weak hit or strong hit.
+
+use crate::{
+    DiceRollSource, EvaluatedExpression, Expression, FancyFormat, Result, Rollable, Rounding,
+    Verbosity, expression::format_bold,
+};
+
+use super::{Outcome, Preset, with_modifier};
+
+/// A move: roll 2d6 plus a modifier.
+#[derive(Clone, Debug)]
+pub struct Move {
+    /// Usually a stat, plus any ongoing bonuses.
+    pub modifier: Option<Expression>,
+}
+
+/// Band of results for a [Move].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveResult {
+    /// 6 or less.
+    Miss,
+    /// 7 to 9.
+    WeakHit,
+    /// 10 or more.
+    StrongHit,
+}
+
+impl MoveResult {
+    /// The band for a total.
+    pub fn from_total(total: i64) -> MoveResult {
+        match total {
+            ..=6 => MoveResult::Miss,
+            7..=9 => MoveResult::WeakHit,
+            10.. => MoveResult::StrongHit,
+        }
+    }
+
+    /// Human readable name of the band.
+    pub fn label(&self) -> &'static str {
+        match self {
+            MoveResult::Miss => "Miss",
+            MoveResult::WeakHit => "Weak hit",
+            MoveResult::StrongHit => "Strong hit",
+        }
+    }
+}
+
+impl FancyFormat for Move {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        match &self.modifier {
+            Some(modifier) => format!("2d6 + {}", modifier.format(markdown, verbose)),
+            None => "2d6".to_string(),
+        }
+    }
+}
+
+impl Rollable for Move {
+    type Roll = Result<EvaluatedMove>;
+
+    fn roll_with_source(&self, rng: &mut dyn DiceRollSource) -> Self::Roll {
+        let roll = with_modifier("2d6", &self.modifier)?.roll_with_source(rng)?;
+        Ok(EvaluatedMove {
+            result: MoveResult::from_total(roll.total().to_integer(Rounding::Down)),
+            roll,
+        })
+    }
+}
+
+impl Preset for Move {
+    fn roll_outcome(&self, rng: &mut dyn DiceRollSource) -> Result<Box<dyn Outcome>> {
+        Ok(Box::new(self.roll_with_source(rng)?))
+    }
+}
+
+/// Result of rolling a [Move].
+#[derive(Debug)]
+pub struct EvaluatedMove {
+    roll: Box<dyn EvaluatedExpression>,
+    result: MoveResult,
+}
+
+impl EvaluatedMove {
+    /// Which band the total landed in.
+    pub fn result(&self) -> MoveResult {
+        self.result
+    }
+
+    /// The dice and modifier.
+    pub fn roll(&self) -> &dyn EvaluatedExpression {
+        &*self.roll
+    }
+}
+
+impl FancyFormat for EvaluatedMove {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        format!(
+            "{} {}",
+            format_bold(self.result.label(), markdown),
+            self.roll.format(markdown, verbose)
+        )
+    }
+}
+
+impl Outcome for EvaluatedMove {
+    fn label(&self) -> Option<String> {
+        Some(self.result.label().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::IteratorDiceRollSource;
+
+    #[test]
+    fn bands() {
+        let roll = |modifier: &str, dice: [u64; 2]| {
+            Move {
+                modifier: Some(Expression::parse(modifier).unwrap()),
+            }
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut dice.into_iter(),
+            })
+            .unwrap()
+        };
+        assert_eq!(roll("0", [3, 3]).result(), MoveResult::Miss);
+        assert_eq!(roll("1", [3, 3]).result(), MoveResult::WeakHit);
+        assert_eq!(roll("-1", [5, 5]).result(), MoveResult::WeakHit);
+        assert_eq!(roll("2", [4, 4]).result(), MoveResult::StrongHit);
+        assert_eq!(
+            roll("2", [4, 4]).format(true, Verbosity::Medium),
+            "**Strong hit** \\[4, 4\\] + 2 = **10**"
+        );
+    }
+}
@@ -0,0 +1,309 @@
+//! Savage Worlds: an exploding trait die, and for wild cards an exploding wild die, keeping the highest.
+
+use std::collections::HashMap;
+
+use pest::iterators::{Pair, Pairs};
+
+use crate::{
+    DiceRollSource, EvaluatedExpression, Expression, FancyFormat, KeptDie, Number, Result,
+    Rollable, Rounding, Verbosity,
+    expression::{ExpressionResult, ExpressionRollable, format_bold, parse_expression},
+    parser::Rule,
+};
+
+use super::{Outcome, Preset};
+
+/// A trait roll.
+#[derive(Clone, Debug)]
+pub struct Trait {
+    /// Sides of the trait die.
+    pub die: u32,
+    /// Sides of the wild die, or [None] for extras.
+    pub wild: Option<u32>,
+    /// Added to the highest die.
+    pub modifier: Option<Expression>,
+    /// Total needed for a success. Every 4 over this is a raise.
+    pub target: i64,
+}
+
+impl Trait {
+    /// Size of the usual wild die.
+    pub const WILD_DIE: u32 = 6;
+    /// The usual target number.
+    pub const TARGET: i64 = 4;
+}
+
+/// Like `sw(d8, wild=d6, mod=1)`.
+impl FancyFormat for Trait {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        let mut s = format!("sw(d{}", self.die);
+        match self.wild {
+            Some(wild) => s += &format!(", wild=d{wild}"),
+            None => s += ", wild=none",
+        }
+        if let Some(modifier) = &self.modifier {
+            s += &format!(", mod={}", modifier.format(markdown, verbose));
+        }
+        if self.target != Trait::TARGET {
+            s += &format!(", tn={}", self.target);
+        }
+        s + ")"
+    }
+}
+
+impl Rollable for Trait {
+    type Roll = Result<EvaluatedTrait>;
+
+    fn roll_with_source(&self, rng: &mut dyn DiceRollSource) -> Self::Roll {
+        let trait_die = Expression::parse(&format!("1d{}!", self.die))?.roll_with_source(rng)?;
+        let wild_die = match self.wild {
+            Some(wild) => Some(Expression::parse(&format!("1d{wild}!"))?.roll_with_source(rng)?),
+            None => None,
+        };
+        let modifier = match &self.modifier {
+            Some(modifier) => Some(modifier.roll_with_source(rng)?),
+            None => None,
+        };
+        Ok(EvaluatedTrait {
+            trait_die,
+            wild_die,
+            modifier,
+            target: self.target,
+        })
+    }
+}
+
+/// Parse the contents of `sw(d8, wild=d6, mod=+1, tn=4)`.
+pub(crate) fn parse_trait(
+    mut pairs: Pairs<Rule>,
+    variables: &HashMap<String, Expression>,
+) -> Result<Expression> {
+    let die = |pair: Pair<Rule>| -> Result<u32> {
+        let sides = pair.into_inner().nth(1).unwrap().as_str().parse::<u32>()?;
+        if sides < 2 {
+            return Err(format!("Invalid trait die d{sides}").into());
+        }
+        Ok(sides)
+    };
+    let mut roll = Trait {
+        die: die(pairs.next().unwrap())?,
+        wild: Some(Trait::WILD_DIE),
+        modifier: None,
+        target: Trait::TARGET,
+    };
+    for pair in pairs {
+        let rule = pair.as_rule();
+        let value = pair.into_inner().next().unwrap();
+        match rule {
+            Rule::sw_wild if value.as_rule() == Rule::sw_no_wild => roll.wild = None,
+            Rule::sw_wild => roll.wild = Some(die(value)?),
+            Rule::sw_mod => roll.modifier = Some(parse_expression(value.into_inner(), variables)?),
+            Rule::sw_tn => roll.target = value.as_str().replace(' ', "").parse::<i64>()?,
+            _ => unreachable!("{:#?}", value),
+        }
+    }
+    Ok(Expression::new(roll))
+}
+
+/// Whether `pair` is `sw(...)`, possibly in brackets.
+///
+/// Its success and raises only count the modifier, so it can't be part of a larger sum.
+pub(crate) fn is_trait(pair: &Pair<Rule>) -> bool {
+    match pair.as_rule() {
+        Rule::sw => true,
+        Rule::block_expr => {
+            let mut inner = pair.clone().into_inner().next().unwrap().into_inner();
+            matches!((inner.next(), inner.next()), (Some(pair), None) if is_trait(&pair))
+        }
+        _ => false,
+    }
+}
+
+impl ExpressionRollable for Trait {
+    fn expression_roll(&self, rng: &mut dyn DiceRollSource) -> ExpressionResult {
+        Ok(Box::new(self.roll_with_source(rng)?))
+    }
+
+    fn roll_total(&self, rng: &mut dyn DiceRollSource) -> Result<Number> {
+        Ok(self.roll_with_source(rng)?.total())
+    }
+}
+
+impl Preset for Trait {
+    fn roll_outcome(&self, rng: &mut dyn DiceRollSource) -> Result<Box<dyn Outcome>> {
+        Ok(Box::new(self.roll_with_source(rng)?))
+    }
+}
+
+/// Result of rolling a [Trait].
+#[derive(Debug)]
+pub struct EvaluatedTrait {
+    trait_die: Box<dyn EvaluatedExpression>,
+    wild_die: Option<Box<dyn EvaluatedExpression>>,
+    modifier: Option<Box<dyn EvaluatedExpression>>,
+    target: i64,
+}
+
+impl EvaluatedTrait {
+    /// If the total met the target number.
+    pub fn success(&self) -> bool {
+        !self.critical_failure() && self.total() >= Number::from(self.target)
+    }
+
+    /// Number of raises: each full 4 points over the target number.
+    pub fn raises(&self) -> i64 {
+        if !self.success() {
+            return 0;
+        }
+        let over = (self.total() - Number::from(self.target)).to_integer(Rounding::Down);
+        over / 4
+    }
+
+    /// If a wild card rolled 1 on both the trait and wild die.
+    pub fn critical_failure(&self) -> bool {
+        let one = Number::from(1);
+        match &self.wild_die {
+            Some(wild) => self.trait_die.total() == one && wild.total() == one,
+            None => false,
+        }
+    }
+}
+
+impl EvaluatedExpression for EvaluatedTrait {
+    /// The higher of the trait and wild die, plus the modifier.
+    fn total(&self) -> Number {
+        let die = match &self.wild_die {
+            Some(wild) if wild.total() > self.trait_die.total() => wild.total(),
+            _ => self.trait_die.total(),
+        };
+        die + self
+            .modifier
+            .as_ref()
+            .map_or(Number::from(0), |m| m.total())
+    }
+
+    fn format_history(&self, markdown: bool, verbose: Verbosity) -> String {
+        let mut s = format!(
+            "{} Trait {}",
+            format_bold(self.label().unwrap(), markdown),
+            self.trait_die.format_history(markdown, verbose)
+        );
+        if let Some(wild) = &self.wild_die {
+            s += &format!(" Wild {}", wild.format_history(markdown, verbose));
+        }
+        if let Some(modifier) = &self.modifier {
+            s += &format!(" + {}", modifier.format_history(markdown, verbose));
+        }
+        s
+    }
+
+    fn dice(&self) -> Vec<KeptDie> {
+        let mut dice = self.trait_die.dice();
+        if let Some(wild) = &self.wild_die {
+            dice.extend(wild.dice());
+        }
+        dice
+    }
+}
+
+impl Outcome for EvaluatedTrait {
+    fn label(&self) -> Option<String> {
+        Some(match (self.success(), self.raises()) {
+            _ if self.critical_failure() => "Critical failure".to_string(),
+            (false, _) => "Failure".to_string(),
+            (true, 0) => "Success".to_string(),
+            (true, 1) => "Success with a raise".to_string(),
+            (true, n) => format!("Success with {n} raises"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::IteratorDiceRollSource;
+
+    fn roll(modifier: &str, rolls: Vec<u64>) -> EvaluatedTrait {
+        Trait {
+            die: 8,
+            wild: Some(Trait::WILD_DIE),
+            modifier: Some(Expression::parse(modifier).unwrap()),
+            target: Trait::TARGET,
+        }
+        .roll_with_source(&mut IteratorDiceRollSource {
+            iterator: &mut rolls.into_iter(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn wild_die() {
+        let result = roll("1", vec![2, 5]);
+        assert_eq!(result.total(), 6.0);
+        assert!(result.success());
+        assert_eq!(result.raises(), 0);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "Success Trait [2]!8 Wild [5]!6 + 1 = 6"
+        );
+    }
+
+    #[test]
+    fn aces_and_raises() {
+        let result = roll("0", vec![8, 8, 1, 3]);
+        assert_eq!(result.total(), 17.0);
+        assert_eq!(result.raises(), 3);
+        assert_eq!(result.label().unwrap(), "Success with 3 raises");
+    }
+
+    #[test]
+    fn failure() {
+        let result = roll("-2", vec![3, 4]);
+        assert!(!result.success());
+        assert_eq!(result.raises(), 0);
+        assert_eq!(result.label().unwrap(), "Failure");
+    }
+
+    #[test]
+    fn critical_failure() {
+        let result = roll("5", vec![1, 1]);
+        assert!(result.critical_failure());
+        assert!(!result.success());
+        assert_eq!(result.label().unwrap(), "Critical failure");
+        assert!(!roll("0", vec![1, 2]).critical_failure());
+    }
+
+    #[test]
+    fn expression() {
+        let roll = |expression: &str, rolls: Vec<u64>| {
+            Expression::parse(expression)
+                .unwrap()
+                .roll_with_source(&mut IteratorDiceRollSource {
+                    iterator: &mut rolls.into_iter(),
+                })
+                .unwrap()
+        };
+        let result = roll("sw(d8, wild=d6, mod=+1)", vec![8, 3, 6, 6, 2]);
+        assert_eq!(result.total(), 15.0);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "Success with 2 raises Trait [8(Exploded)🡵3]!8 Wild [6(Exploded)🡵6(Exploded)🡵2]!6 + 1 = 15"
+        );
+        let result = roll("sw(d6, wild=none, tn=6, mod=1)", vec![5]);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "Success Trait [5]!6 + 1 = 6"
+        );
+        assert!(Expression::parse("sw(d6, wild=none, tn=6) + 1").is_err());
+        assert!(Expression::parse("2 * (sw(d6))").is_err());
+        assert!(Expression::parse("sw(d6)[fire]").is_err());
+        assert!(Expression::parse("(sw(d6) - 1)").is_err());
+        assert!(Expression::parse("(sw(d6))").is_ok());
+        assert!(Expression::parse("sw(d10, mod=$fighting)").is_err());
+        assert!(Expression::parse("sw(d1)").is_err());
+        assert_eq!(
+            format!("{}", Expression::parse("sw(d10,mod=2)").unwrap()),
+            "sw(d10, wild=d6, mod=2)"
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Year Zero Engine: pools of d6 where each 6 is a success, and a failed roll can be pushed.
+
+use crate::{
+    DiceRollSource, FancyFormat, Result, Rollable, Verbosity, dice_expression::format_rolls,
+    dice_expression::limit_dice, expression::format_bold,
+};
+
+use super::{Outcome, Preset};
+
+/// A roll of base (attribute), skill and gear dice.
+#[derive(Clone, Copy, Debug)]
+pub struct Pool {
+    /// Number of base dice, from the attribute.
+    pub base: u32,
+    /// Number of skill dice.
+    pub skill: u32,
+    /// Number of gear dice.
+    pub gear: u32,
+}
+
+fn roll_dice(count: u32, rng: &mut dyn DiceRollSource) -> Vec<u32> {
+    (0..count).map(|_| rng.roll_single_die(6) as u32).collect()
+}
+
+impl FancyFormat for Pool {
+    fn format(&self, _markdown: bool, _verbose: Verbosity) -> String {
+        format!(
+            "{} base, {} skill, {} gear",
+            self.base, self.skill, self.gear
+        )
+    }
+}
+
+impl Rollable for Pool {
+    type Roll = Result<EvaluatedPool>;
+
+    fn roll_with_source(&self, rng: &mut dyn DiceRollSource) -> Self::Roll {
+        limit_dice(
+            (self.base as usize) + (self.skill as usize) + (self.gear as usize),
+            "Year Zero pool",
+        )?;
+        Ok(EvaluatedPool {
+            base: roll_dice(self.base, rng),
+            skill: roll_dice(self.skill, rng),
+            gear: roll_dice(self.gear, rng),
+            pushed: false,
+        })
+    }
+}
+
+impl Preset for Pool {
+    fn roll_outcome(&self, rng: &mut dyn DiceRollSource) -> Result<Box<dyn Outcome>> {
+        Ok(Box::new(self.roll_with_source(rng)?))
+    }
+}
+
+/// Result of rolling a [Pool].
+#[derive(Clone, Debug)]
+pub struct EvaluatedPool {
+    base: Vec<u32>,
+    skill: Vec<u32>,
+    gear: Vec<u32>,
+    pushed: bool,
+}
+
+impl EvaluatedPool {
+    /// Number of 6s rolled.
+    pub fn successes(&self) -> usize {
+        self.all().filter(|d| *d == 6).count()
+    }
+
+    /// If this roll has been pushed.
+    pub fn pushed(&self) -> bool {
+        self.pushed
+    }
+
+    /// Number of 1s on base dice: each causes attribute damage (or stress) if pushed.
+    pub fn attribute_banes(&self) -> usize {
+        self.base.iter().filter(|d| **d == 1).count()
+    }
+
+    /// Number of 1s on gear dice: each reduces the gear bonus if pushed.
+    pub fn gear_banes(&self) -> usize {
+        self.gear.iter().filter(|d| **d == 1).count()
+    }
+
+    /// Push the roll: reroll every die which is not a 6, except for 1s on base and gear dice.
+    ///
+    /// A roll can only be pushed once.
+    pub fn push(&self, rng: &mut dyn DiceRollSource) -> Result<EvaluatedPool> {
+        if self.pushed {
+            return Err("A roll can only be pushed once".into());
+        }
+        let mut reroll = |dice: &[u32], keep_ones: bool| -> Vec<u32> {
+            dice.iter()
+                .map(|d| match d {
+                    6 => 6,
+                    1 if keep_ones => 1,
+                    _ => rng.roll_single_die(6) as u32,
+                })
+                .collect()
+        };
+        Ok(EvaluatedPool {
+            base: reroll(&self.base, true),
+            skill: reroll(&self.skill, false),
+            gear: reroll(&self.gear, true),
+            pushed: true,
+        })
+    }
+
+    fn all(&self) -> impl Iterator<Item = u32> {
+        self.base
+            .iter()
+            .chain(&self.skill)
+            .chain(&self.gear)
+            .copied()
+    }
+}
+
+impl FancyFormat for EvaluatedPool {
+    fn format(&self, markdown: bool, _verbose: Verbosity) -> String {
+        let mut parts = vec![format_bold(self.label().unwrap(), markdown)];
+        for (name, dice) in [
+            ("Base", &self.base),
+            ("Skill", &self.skill),
+            ("Gear", &self.gear),
+        ] {
+            if !dice.is_empty() {
+                parts.push(format!("{name} {}", format_rolls(dice.iter(), markdown)));
+            }
+        }
+        if self.pushed {
+            parts.push(format!(
+                "(pushed: {} attribute damage, {} gear damage)",
+                self.attribute_banes(),
+                self.gear_banes()
+            ));
+        }
+        parts.join(" ")
+    }
+}
+
+impl Outcome for EvaluatedPool {
+    fn label(&self) -> Option<String> {
+        Some(match self.successes() {
+            0 => "Failure".to_string(),
+            1 => "1 success".to_string(),
+            n => format!("{n} successes"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::IteratorDiceRollSource;
+
+    #[test]
+    fn roll_and_push() {
+        let pool = Pool {
+            base: 2,
+            skill: 2,
+            gear: 1,
+        };
+        let result = pool
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut [1, 3, 1, 4, 2].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(result.successes(), 0);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "Failure Base [1, 3] Skill [1, 4] Gear [2]"
+        );
+
+        let pushed = result
+            .push(&mut IteratorDiceRollSource {
+                iterator: &mut [6, 5, 6, 1].into_iter(),
+            })
+            .unwrap();
+        assert_eq!(pushed.successes(), 2);
+        assert_eq!(pushed.attribute_banes(), 1);
+        assert_eq!(pushed.gear_banes(), 1);
+        assert_eq!(
+            pushed.format(true, Verbosity::Medium),
+            "**2 successes** Base \\[1, 6\\] Skill \\[5, 6\\] Gear \\[1\\] (pushed: 1 attribute damage, 1 gear damage)"
+        );
+
+        assert!(
+            pushed
+                .push(&mut IteratorDiceRollSource {
+                    iterator: &mut [].into_iter(),
+                })
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,647 @@
+//! Random tables: named lists of entries picked by a roll, like encounter or loot tables.
+
+use std::{collections::HashMap, ops::RangeInclusive};
+
+use crate::{
+    DiceRollSource, EvaluatedExpression, Expression, FancyFormat, Result, Rollable, Rounding,
+    Verbosity,
+    dice_expression::format_rolls,
+    expression::{format_bold, format_italic},
+};
+
+/// A random table.
+///
+/// Each line of the definition is one entry, optionally written as a markdown list item.
+/// Entries either all have ranges (`1-3: Goblin`, `6: Ogre`) or all have weights (`3x: Goblin`, or no prefix for a weight of 1).
+/// Ranges must cover every number from the lowest to the highest once,
+/// except for tables indexed by digit dice like `dd66` (`11: Ambush`, `12-16: Nothing`, `21: ...`),
+/// which are detected from the gaps between their ranges, and must have an entry for every roll.
+/// Digit dice tables without gaps, like `11-33: Nothing` and `34-66: Bandits`, are rolled uniformly
+/// unless given their dice with [Table::with_dice].
+///
+/// Entry text can embed dice expressions like `[[2d4]]`, and roll on other tables with `$name`
+/// (a `$` followed by a digit, like `$5`, is kept as text).
+#[derive(Clone, Debug)]
+pub struct Table {
+    entries: Vec<Entry>,
+    dice: Option<Expression>,
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    range: RangeInclusive<i64>,
+    text: Vec<Segment>,
+}
+
+#[derive(Clone, Debug)]
+enum Segment {
+    Text(String),
+    Dice(Expression),
+    Table(String),
+}
+
+impl Table {
+    /// Parse a table definition.
+    pub fn parse(s: &str) -> Result<Table> {
+        Table::parse_with_variables(s, &HashMap::default())
+    }
+
+    /// Parse a table definition, with variables available to embedded dice expressions.
+    pub fn parse_with_variables(s: &str, variables: &HashMap<String, Expression>) -> Result<Table> {
+        let mut ranged = vec![];
+        let mut weighted = vec![];
+        for line in s.lines() {
+            let line = strip_list_marker(line.trim());
+            if line.is_empty() {
+                continue;
+            }
+            match parse_prefix(line)? {
+                (Prefix::Range(range), rest) => ranged.push((range, rest)),
+                (Prefix::Weight(weight), rest) => weighted.push((weight, rest)),
+            }
+        }
+        if !ranged.is_empty() && !weighted.is_empty() {
+            return Err("Table entries must all have ranges, or all have weights".into());
+        }
+
+        let mut entries = vec![];
+        let mut digit_dice = None;
+        if ranged.is_empty() {
+            let mut next = Some(1i64);
+            for (weight, text) in weighted {
+                let start = next.ok_or("Table weights add up to too much")?;
+                let end = start
+                    .checked_add(weight - 1)
+                    .ok_or("Table weights add up to too much")?;
+                entries.push(Entry {
+                    range: start..=end,
+                    text: parse_text(text, variables)?,
+                });
+                next = end.checked_add(1);
+            }
+        } else {
+            ranged.sort_by_key(|(range, _)| *range.start());
+            digit_dice = digit_dice_for(ranged.iter().map(|(range, _)| range))?;
+            for (range, text) in ranged {
+                if let Some(previous) = entries.last()
+                    && (previous.range.end() >= range.start()
+                        || (digit_dice.is_none() && previous.range.end() + 1 != *range.start()))
+                {
+                    return Err(format!(
+                        "Table ranges must not overlap or leave gaps, but {}-{} is followed by {}-{}",
+                        previous.range.start(),
+                        previous.range.end(),
+                        range.start(),
+                        range.end()
+                    )
+                    .into());
+                }
+                entries.push(Entry {
+                    range,
+                    text: parse_text(text, variables)?,
+                });
+            }
+        }
+        if entries.is_empty() {
+            return Err("Table has no entries".into());
+        }
+        let table = Table {
+            entries,
+            dice: digit_dice
+                .map(|sides| Expression::parse(&format!("dd{sides}")))
+                .transpose()?,
+        };
+        if table.sides() == 0 {
+            return Err("Table ranges cover too many values".into());
+        }
+        Ok(table)
+    }
+
+    /// Pick entries with `dice` instead of uniformly between the lowest and highest range.
+    /// Totals beyond either end of the table pick the first or last entry.
+    pub fn with_dice(self, dice: Expression) -> Table {
+        Table {
+            dice: Some(dice),
+            ..self
+        }
+    }
+
+    fn lowest(&self) -> i64 {
+        *self.entries[0].range.start()
+    }
+
+    fn highest(&self) -> i64 {
+        *self.entries[self.entries.len() - 1].range.end()
+    }
+
+    /// Number of values from the lowest to the highest range, or 0 if that overflows.
+    fn sides(&self) -> u64 {
+        self.highest()
+            .abs_diff(self.lowest())
+            .checked_add(1)
+            .unwrap_or(0)
+    }
+}
+
+impl FancyFormat for Table {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        match &self.dice {
+            Some(dice) => dice.format(markdown, verbose),
+            None if self.lowest() == 1 => format!("1d{}", self.highest()),
+            None => format!("{}-{}", self.lowest(), self.highest()),
+        }
+    }
+}
+
+enum Prefix {
+    Range(RangeInclusive<i64>),
+    Weight(i64),
+}
+
+fn strip_list_marker(line: &str) -> &str {
+    for marker in ["- ", "* ", "+ "] {
+        if let Some(rest) = line.strip_prefix(marker) {
+            return rest.trim_start();
+        }
+    }
+    line
+}
+
+/// Split an entry into its range or weight, and its text.
+fn parse_prefix(line: &str) -> Result<(Prefix, &str)> {
+    let Some((prefix, rest)) = line.split_once(':') else {
+        return Ok((Prefix::Weight(1), line));
+    };
+    let prefix = prefix.trim();
+    let rest = rest.trim();
+    let number = |s: &str| s.trim().parse::<i64>().ok();
+    if let Some(weight) = prefix.strip_suffix(['x', 'X']).and_then(number) {
+        if weight < 1 {
+            return Err(format!("Table weight must be positive, got {weight}").into());
+        }
+        return Ok((Prefix::Weight(weight), rest));
+    }
+    if let Some(value) = number(prefix) {
+        return Ok((Prefix::Range(value..=value), rest));
+    }
+    // Look for the separator after the first character, so the low end may be negative.
+    if let Some((low, high)) = prefix
+        .char_indices()
+        .skip(1)
+        .find(|(_, c)| *c == '-')
+        .map(|(i, _)| (&prefix[..i], &prefix[i + 1..]))
+        && let (Some(low), Some(high)) = (number(low), number(high))
+    {
+        if low > high {
+            return Err(format!("Table range {low}-{high} is backwards").into());
+        }
+        return Ok((Prefix::Range(low..=high), rest));
+    }
+    // A colon in the text of an entry without a prefix.
+    Ok((Prefix::Weight(1), line))
+}
+
+/// The sides of digit dice (like `66` for `dd66`) which the ranges index, if they are not contiguous.
+///
+/// The lowest range must start at 11..1, the highest end has the largest value for each digit,
+/// and every roll of the digit dice must have an entry.
+fn digit_dice_for<'a>(
+    ranges: impl Iterator<Item = &'a RangeInclusive<i64>> + Clone,
+) -> Result<Option<i64>> {
+    let (Some(first), Some(highest)) = (
+        ranges.clone().next(),
+        ranges.clone().map(|r| *r.end()).max(),
+    ) else {
+        return Ok(None);
+    };
+    let contiguous = ranges
+        .clone()
+        .zip(ranges.clone().skip(1))
+        .all(|(a, b)| a.end().checked_add(1) == Some(*b.start()));
+    let digits = highest.to_string();
+    if contiguous
+        || digits.len() < 2
+        || digits.len() > 9
+        || first.start().to_string() != "1".repeat(digits.len())
+    {
+        return Ok(None);
+    }
+    let fits = |value: i64| {
+        let value = value.to_string();
+        value.len() == digits.len()
+            && value
+                .bytes()
+                .zip(digits.bytes())
+                .all(|(v, max)| v != b'0' && v <= max)
+    };
+    if !ranges
+        .clone()
+        .flat_map(|range| [*range.start(), *range.end()])
+        .all(fits)
+    {
+        return Ok(None);
+    }
+    // The roll after `value`, counting up the last digit and carrying into the others
+    let next_roll = |value: i64| -> i64 {
+        let mut value = value.to_string().into_bytes();
+        for (digit, max) in value.iter_mut().zip(digits.bytes()).rev() {
+            if *digit < max {
+                *digit += 1;
+                break;
+            }
+            *digit = b'1';
+        }
+        String::from_utf8(value).unwrap().parse().unwrap()
+    };
+    for (a, b) in ranges.clone().zip(ranges.skip(1)) {
+        let next = next_roll(*a.end());
+        if next < *b.start() {
+            return Err(format!("Table has no entry for {next} on dd{highest}").into());
+        }
+    }
+    Ok(Some(highest))
+}
+
+/// Split entry text into plain text, `[[dice]]` and `$table` references.
+fn parse_text(mut s: &str, variables: &HashMap<String, Expression>) -> Result<Vec<Segment>> {
+    let mut segments = vec![];
+    let mut text = String::new();
+    while let Some(c) = s.chars().next() {
+        if let Some(rest) = s.strip_prefix("[[") {
+            let (dice, rest) = rest.split_once("]]").ok_or("Unclosed [[ in table entry")?;
+            segments.push(Segment::Text(std::mem::take(&mut text)));
+            segments.push(Segment::Dice(Expression::parse_with_variables(
+                dice, variables,
+            )?));
+            s = rest;
+        } else if let Some(rest) = s.strip_prefix('$') {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            if end == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) {
+                text.push('$');
+                s = rest;
+            } else {
+                segments.push(Segment::Text(std::mem::take(&mut text)));
+                segments.push(Segment::Table(rest[..end].to_string()));
+                s = &rest[end..];
+            }
+        } else {
+            text.push(c);
+            s = &s[c.len_utf8()..];
+        }
+    }
+    segments.push(Segment::Text(text));
+    segments.retain(|segment| !matches!(segment, Segment::Text(text) if text.is_empty()));
+    Ok(segments)
+}
+
+/// A set of named tables, which can refer to each other.
+#[derive(Clone, Debug, Default)]
+pub struct Tables {
+    tables: HashMap<String, Table>,
+}
+
+impl Tables {
+    /// Add a table, replacing any table with the same name.
+    pub fn insert(&mut self, name: impl Into<String>, table: Table) {
+        self.tables.insert(name.into(), table);
+    }
+
+    /// Look up a table by name.
+    pub fn get(&self, name: &str) -> Option<&Table> {
+        self.tables.get(name)
+    }
+
+    /// Something which rolls on the named table.
+    pub fn roller(&self, name: &str) -> Result<TableRoller<'_>> {
+        self.find(name)?;
+        Ok(TableRoller {
+            tables: self,
+            name: name.to_string(),
+        })
+    }
+
+    fn find(&self, name: &str) -> Result<&Table> {
+        self.get(name)
+            .ok_or_else(|| format!("Unknown table \"{name}\"").into())
+    }
+
+    fn roll_table(
+        &self,
+        name: &str,
+        stack: &mut Vec<String>,
+        rng: &mut dyn DiceRollSource,
+    ) -> Result<EvaluatedTable> {
+        if stack.iter().any(|n| n == name) {
+            stack.push(name.to_string());
+            return Err(format!("Table cycle: {}", stack.join(" -> ")).into());
+        }
+        let table = self.find(name)?;
+        stack.push(name.to_string());
+
+        let dice = match &table.dice {
+            Some(dice) => Some(dice.roll_with_source(rng)?),
+            None => None,
+        };
+        let roll = match &dice {
+            Some(dice) => dice.total().to_integer(Rounding::Down),
+            None => {
+                let offset = rng.roll_single_die(table.sides()) - 1;
+                table.lowest().wrapping_add_unsigned(offset)
+            }
+        };
+        let entry = if roll < table.lowest() {
+            &table.entries[0]
+        } else if roll > table.highest() {
+            &table.entries[table.entries.len() - 1]
+        } else {
+            table
+                .entries
+                .iter()
+                .find(|entry| entry.range.contains(&roll))
+                .ok_or_else(|| format!("Table \"{name}\" has no entry for {roll}"))?
+        };
+
+        let mut text = vec![];
+        for segment in &entry.text {
+            text.push(match segment {
+                Segment::Text(s) => EvaluatedSegment::Text(s.clone()),
+                Segment::Dice(expression) => {
+                    EvaluatedSegment::Dice(expression.roll_with_source(rng)?)
+                }
+                Segment::Table(name) => EvaluatedSegment::Table(self.roll_table(name, stack, rng)?),
+            });
+        }
+
+        stack.pop();
+        Ok(EvaluatedTable {
+            name: name.to_string(),
+            dice,
+            roll,
+            text,
+        })
+    }
+}
+
+/// Rolls on a table from a set of [Tables], made by [Tables::roller].
+#[derive(Clone, Debug)]
+pub struct TableRoller<'a> {
+    tables: &'a Tables,
+    name: String,
+}
+
+impl FancyFormat for TableRoller<'_> {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        match self.tables.get(&self.name) {
+            Some(table) => format!("{} {}", self.name, table.format(markdown, verbose)),
+            None => self.name.clone(),
+        }
+    }
+}
+
+impl Rollable for TableRoller<'_> {
+    type Roll = Result<EvaluatedTable>;
+
+    fn roll_with_source(&self, rng: &mut dyn DiceRollSource) -> Self::Roll {
+        self.tables.roll_table(&self.name, &mut vec![], rng)
+    }
+}
+
+/// Result of rolling on a table.
+#[derive(Debug)]
+pub struct EvaluatedTable {
+    name: String,
+    dice: Option<Box<dyn EvaluatedExpression>>,
+    roll: i64,
+    text: Vec<EvaluatedSegment>,
+}
+
+#[derive(Debug)]
+enum EvaluatedSegment {
+    Text(String),
+    Dice(Box<dyn EvaluatedExpression>),
+    Table(EvaluatedTable),
+}
+
+impl EvaluatedTable {
+    /// Name of the table rolled on.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The number which picked the entry.
+    pub fn roll(&self) -> i64 {
+        self.roll
+    }
+
+    /// Text of the picked entry, with dice totals and the text of nested tables filled in.
+    pub fn text(&self) -> String {
+        self.format_text(false, Verbosity::Short)
+    }
+
+    /// Results of the tables this entry rolled on.
+    pub fn nested(&self) -> impl Iterator<Item = &EvaluatedTable> {
+        self.text.iter().filter_map(|segment| match segment {
+            EvaluatedSegment::Table(table) => Some(table),
+            _ => None,
+        })
+    }
+
+    fn format_text(&self, markdown: bool, verbose: Verbosity) -> String {
+        self.text
+            .iter()
+            .map(|segment| match segment {
+                EvaluatedSegment::Text(s) => s.clone(),
+                EvaluatedSegment::Dice(dice) if verbose == Verbosity::Verbose => format!(
+                    "{} ({})",
+                    format_bold(dice.total(), markdown),
+                    dice.format_history(markdown, verbose)
+                ),
+                EvaluatedSegment::Dice(dice) => format_bold(dice.total(), markdown),
+                EvaluatedSegment::Table(table) => table.format_text(markdown, verbose),
+            })
+            .collect()
+    }
+}
+
+impl FancyFormat for EvaluatedTable {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        let roll = match &self.dice {
+            Some(dice) => dice.format_history(markdown, verbose),
+            None => format_rolls([self.roll].iter(), markdown),
+        };
+        format!(
+            "{} {roll}: {}",
+            format_italic(&self.name, markdown),
+            self.format_text(markdown, verbose)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::IteratorDiceRollSource;
+
+    fn roll(tables: &Tables, name: &str, rolls: Vec<u64>) -> Result<EvaluatedTable> {
+        tables
+            .roller(name)?
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut rolls.into_iter(),
+            })
+    }
+
+    fn encounters() -> Tables {
+        let mut tables = Tables::default();
+        tables.insert(
+            "encounter",
+            Table::parse("- 1-3: Goblin\n- 4-5: [[2d4]] wolves\n- 6: roll on $dungeon_twist")
+                .unwrap(),
+        );
+        tables.insert(
+            "dungeon_twist",
+            Table::parse("3x: The floor collapses\nA door slams shut").unwrap(),
+        );
+        tables
+    }
+
+    #[test]
+    fn ranges() {
+        let tables = encounters();
+        let result = roll(&tables, "encounter", vec![2]).unwrap();
+        assert_eq!(result.roll(), 2);
+        assert_eq!(result.text(), "Goblin");
+
+        let result = roll(&tables, "encounter", vec![4, 3, 2]).unwrap();
+        assert_eq!(result.text(), "5 wolves");
+        assert_eq!(
+            result.format(true, Verbosity::Medium),
+            "*encounter* \\[4\\]: **5** wolves"
+        );
+        assert_eq!(
+            result.format(false, Verbosity::Verbose),
+            "encounter [4]: 5 ([3, 2]) wolves"
+        );
+    }
+
+    #[test]
+    fn nested() {
+        let tables = encounters();
+        let result = roll(&tables, "encounter", vec![6, 4]).unwrap();
+        assert_eq!(result.text(), "roll on A door slams shut");
+        assert_eq!(result.nested().next().unwrap().name(), "dungeon_twist");
+        let result = roll(&tables, "encounter", vec![6, 3]).unwrap();
+        assert_eq!(result.text(), "roll on The floor collapses");
+        assert_eq!(
+            tables
+                .roller("dungeon_twist")
+                .unwrap()
+                .format(false, Verbosity::Medium),
+            "dungeon_twist 1d4"
+        );
+    }
+
+    #[test]
+    fn dice() {
+        let mut tables = Tables::default();
+        tables.insert(
+            "reaction",
+            Table::parse("2-6: Hostile\n7-9: Unsure\n10-12: Friendly")
+                .unwrap()
+                .with_dice(Expression::parse("2d6 + 1").unwrap()),
+        );
+        let result = roll(&tables, "reaction", vec![6, 6]).unwrap();
+        assert_eq!(result.roll(), 13);
+        assert_eq!(result.text(), "Friendly");
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "reaction [6, 6] + 1: Friendly"
+        );
+    }
+
+    #[test]
+    fn digit_dice() {
+        let table =
+            Table::parse("11-16: Nothing\n21-36: Tracks\n41-65: Bandits\n66: Dragon").unwrap();
+        assert_eq!(table.format(false, Verbosity::Medium), "1dd66");
+        let mut tables = Tables::default();
+        tables.insert("wilderness", table);
+        let result = roll(&tables, "wilderness", vec![6, 5]).unwrap();
+        assert_eq!(result.roll(), 65);
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "wilderness [65]: Bandits"
+        );
+        assert_eq!(
+            roll(&tables, "wilderness", vec![6, 6]).unwrap().text(),
+            "Dragon"
+        );
+
+        // Every roll needs an entry
+        assert_eq!(
+            Table::parse("11-16: a\n21-23: b\n26: c").unwrap_err(),
+            "Table has no entry for 24 on dd26".into()
+        );
+        assert_eq!(
+            Table::parse("11: a\n13: b").unwrap_err(),
+            "Table has no entry for 12 on dd13".into()
+        );
+        // Without gaps, digit dice must be given explicitly
+        let table = Table::parse("11-33: a\n34-66: b").unwrap();
+        assert_eq!(table.format(false, Verbosity::Medium), "11-66");
+        tables.insert(
+            "contiguous",
+            table.with_dice(Expression::parse("dd66").unwrap()),
+        );
+        let result = roll(&tables, "contiguous", vec![3, 4]).unwrap();
+        assert_eq!(result.roll(), 34);
+        assert_eq!(result.text(), "b");
+        assert!(Table::parse("11-16: a\n21-26: b\n30: c").is_err());
+        assert!(Table::parse("11-21: a\n16-26: b").is_err());
+    }
+
+    #[test]
+    fn cycle() {
+        let mut tables = Tables::default();
+        tables.insert("a", Table::parse("Go to $b").unwrap());
+        tables.insert("b", Table::parse("Back to $a").unwrap());
+        assert_eq!(
+            roll(&tables, "a", vec![1, 1]).unwrap_err(),
+            "Table cycle: a -> b -> a".into()
+        );
+        assert_eq!(
+            tables.roller("c").unwrap_err(),
+            "Unknown table \"c\"".into()
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Table::parse("").is_err());
+        assert!(Table::parse("1-3: a\n3-4: b").is_err());
+        assert!(Table::parse("1-3: a\n5: b").is_err());
+        assert!(Table::parse("1-3: a\n2x: b").is_err());
+        assert!(Table::parse("[[2d]] gold").is_err());
+        assert!(Table::parse("1-2: Note: fragile\n3: $5 and a hat").is_ok());
+        assert_eq!(
+            Table::parse("9223372036854775807x: a\nb").unwrap_err(),
+            "Table weights add up to too much".into()
+        );
+        assert_eq!(
+            Table::parse("-9223372036854775808--1: a\n0-9223372036854775807: b").unwrap_err(),
+            "Table ranges cover too many values".into()
+        );
+        // The widest table which fits
+        let mut tables = Tables::default();
+        tables.insert(
+            "wide",
+            Table::parse("-9223372036854775807--1: a\n0-9223372036854775807: b").unwrap(),
+        );
+        assert_eq!(roll(&tables, "wide", vec![1]).unwrap().roll(), -i64::MAX);
+        assert_eq!(
+            roll(&tables, "wide", vec![u64::MAX]).unwrap().roll(),
+            i64::MAX
+        );
+    }
+}
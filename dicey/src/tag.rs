@@ -0,0 +1,301 @@
+//! Tagged parts of a total, like the damage types in `1d8[fire] + 2d6[slashing]`.
+
+use std::fmt::Display;
+
+use pest::iterators::Pair;
+
+use crate::{
+    DiceRollSource, EvaluatedExpression, Expression, FancyFormat, KeptDie, Number, Result,
+    Rollable, Rounding, Verbosity,
+    compiled::Op,
+    expression::{ExpressionResult, ExpressionRollable, format_bold, format_italic},
+    parser::Rule,
+};
+
+/// An expression whose total counts towards the subtotal for `tag`.
+#[derive(Debug)]
+pub(crate) struct Tagged<T> {
+    pub(crate) inner: T,
+    pub(crate) tag: String,
+}
+
+/// Like `[fire]`, escaped for markdown.
+fn format_tag(tag: &str, markdown: bool) -> String {
+    if markdown {
+        format!("\\[{tag}\\]")
+    } else {
+        format!("[{tag}]")
+    }
+}
+
+impl FancyFormat for Tagged<Expression> {
+    fn format(&self, markdown: bool, verbose: Verbosity) -> String {
+        format!(
+            "{}{}",
+            self.inner.format(markdown, verbose),
+            format_tag(&self.tag, markdown)
+        )
+    }
+}
+
+impl ExpressionRollable for Tagged<Expression> {
+    fn expression_roll(&self, rng: &mut dyn DiceRollSource) -> ExpressionResult {
+        Ok(Box::new(Tagged {
+            inner: self.inner.roll_with_source(rng)?,
+            tag: self.tag.clone(),
+        }))
+    }
+
+    fn expression_roll_max(&self) -> ExpressionResult {
+        Ok(Box::new(Tagged {
+            inner: self.inner.roll_max()?,
+            tag: self.tag.clone(),
+        }))
+    }
+
+    fn roll_total(&self, rng: &mut dyn DiceRollSource) -> Result<Number> {
+        self.inner.roll_total(rng)
+    }
+
+    fn compile(&self, ops: &mut Vec<Op>) -> bool {
+        self.inner.compile(ops);
+        true
+    }
+}
+
+impl EvaluatedExpression for Tagged<Box<dyn EvaluatedExpression>> {
+    fn total(&self) -> Number {
+        self.inner.total()
+    }
+
+    fn format_history(&self, markdown: bool, verbose: Verbosity) -> String {
+        format!(
+            "{}{}",
+            self.inner.format_history(markdown, verbose),
+            format_tag(&self.tag, markdown)
+        )
+    }
+
+    fn dice(&self) -> Vec<KeptDie> {
+        self.inner.dice()
+    }
+
+    fn outputs(&self) -> Vec<(String, Number)> {
+        self.inner.outputs()
+    }
+
+    fn tags(&self) -> Vec<(String, Number)> {
+        vec![(self.tag.clone(), self.inner.total())]
+    }
+}
+
+/// How a tagged subtotal is multiplied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Resistance {
+    /// Halved, rounding down.
+    Resist,
+    /// Doubled.
+    Vulnerable,
+    /// Reduced to 0.
+    Immune,
+}
+
+impl Resistance {
+    fn apply(self, total: Number) -> Number {
+        match self {
+            Resistance::Resist => (total / 2.into()).to_integer(Rounding::Down).into(),
+            Resistance::Vulnerable => total * 2.into(),
+            Resistance::Immune => 0.into(),
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Resistance::Resist => "resisted",
+            Resistance::Vulnerable => "vulnerable",
+            Resistance::Immune => "immune",
+        }
+    }
+}
+
+impl Display for Resistance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Resistance::Resist => "resist",
+            Resistance::Vulnerable => "vuln",
+            Resistance::Immune => "immune",
+        })
+    }
+}
+
+/// Resistances to tags, applied to a command's total.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Resistances(Vec<(Resistance, Vec<String>)>);
+
+impl Resistances {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Add the resistance in `resist=fire,cold`.
+    pub(crate) fn parse(&mut self, pair: Pair<Rule>) -> Result<()> {
+        let mut inner = pair.into_inner();
+        let resistance = match inner.next().unwrap().as_rule() {
+            Rule::resist => Resistance::Resist,
+            Rule::vulnerable => Resistance::Vulnerable,
+            _ => Resistance::Immune,
+        };
+        let mut tags = vec![];
+        for tag in inner.map(|tag| tag.as_str().to_string()) {
+            if self.get(&tag).is_some() || tags.contains(&tag) {
+                return Err(format!("More than one resistance to \"{tag}\"").into());
+            }
+            tags.push(tag);
+        }
+        self.0.push((resistance, tags));
+        Ok(())
+    }
+
+    fn get(&self, tag: &str) -> Option<Resistance> {
+        self.0
+            .iter()
+            .find(|(_, tags)| tags.iter().any(|t| t == tag))
+            .map(|(resistance, _)| *resistance)
+    }
+
+    /// Apply resistances to the `tags` of a result.
+    pub(crate) fn apply(&self, tags: Vec<(String, Number)>) -> Vec<Subtotal> {
+        tags.into_iter()
+            .map(|(tag, raw)| {
+                let resistance = self.get(&tag);
+                Subtotal {
+                    total: resistance.map_or(raw, |r| r.apply(raw)),
+                    tag,
+                    raw,
+                    resistance,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Display for Resistances {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (resistance, tags) in &self.0 {
+            write!(f, " {resistance}={}", tags.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// The part of a total from leaves with the same tag.
+#[derive(Clone, Debug)]
+pub(crate) struct Subtotal {
+    pub(crate) tag: String,
+    /// Before any resistance.
+    pub(crate) raw: Number,
+    pub(crate) total: Number,
+    resistance: Option<Resistance>,
+}
+
+/// Like ` 🡲 fire: 3 (resisted), slashing: 9`.
+pub(crate) fn format_subtotals(subtotals: &[Subtotal], markdown: bool) -> String {
+    if subtotals.is_empty() {
+        return String::new();
+    }
+    let subtotals: Vec<String> = subtotals
+        .iter()
+        .map(|subtotal| {
+            let resistance = match subtotal.resistance {
+                Some(resistance) => format!(" ({})", format_italic(resistance.label(), markdown)),
+                None => String::new(),
+            };
+            format!(
+                "{}: {}{resistance}",
+                subtotal.tag,
+                format_bold(subtotal.total, markdown)
+            )
+        })
+        .collect();
+    format!(" 🡲 {}", subtotals.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, EvaluatedCommand, FancyFormat, RollError, Verbosity};
+    use crate::{Number, Rollable, tests::IteratorDiceRollSource};
+
+    fn roll(command: &str, rolls: Vec<u64>) -> EvaluatedCommand {
+        Command::parse(command)
+            .unwrap()
+            .roll_with_source(&mut IteratorDiceRollSource {
+                iterator: &mut rolls.into_iter(),
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn subtotals() {
+        let result = roll(
+            "1d8[fire] + 2d6[slashing] + 1d8[fire] + 3",
+            vec![3, 4, 5, 6],
+        );
+        assert_eq!(result.total(), Some(21.into()));
+        assert_eq!(
+            result.subtotals(),
+            [
+                ("fire".to_string(), Number::from(9)),
+                ("slashing".to_string(), Number::from(9))
+            ]
+        );
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "[3][fire] + [4, 5][slashing] + [6][fire] + 3 = 21 🡲 fire: 9, slashing: 9"
+        );
+        assert_eq!(
+            result.format(true, Verbosity::Medium),
+            "\\[3\\]\\[fire\\] + \\[4, 5\\]\\[slashing\\] + \\[6\\]\\[fire\\] + 3 = **21** 🡲 fire: **9**, slashing: **9**"
+        );
+        // A tag on a block covers all of it, and multiplying scales the subtotal
+        let result = roll("(1d6 + 2)[cold] * 2 - 1d4[cold]", vec![4, 3]);
+        assert_eq!(result.subtotals(), [("cold".to_string(), Number::from(9))]);
+        assert!(roll("2d6", vec![1, 2]).subtotals().is_empty());
+    }
+
+    #[test]
+    fn resistances() {
+        let result = roll(
+            "1d8[fire] + 2d6[slashing] + 1d4[poison] resist=fire vuln=slashing immune=poison",
+            vec![7, 4, 5, 3],
+        );
+        assert_eq!(result.total(), Some(21.into()));
+        assert_eq!(
+            result.format(false, Verbosity::Medium),
+            "[7][fire] + [4, 5][slashing] + [3][poison] = 19 🡲 fire: 3 (resisted), \
+             slashing: 18 (vulnerable), poison: 0 (immune) = 21"
+        );
+        // Summed over repetitions before resisting
+        let command = Command::parse("(1d6[fire] + 1) ^+ 3 resist=fire,cold").unwrap();
+        assert_eq!(format!("{command}"), "1d6[fire] + 1 ^+ 3 resist=fire,cold");
+        let totals = command
+            .roll_totals_with_source(
+                1,
+                &mut IteratorDiceRollSource {
+                    iterator: &mut [1, 2, 4].into_iter(),
+                },
+            )
+            .unwrap();
+        assert_eq!(totals, [Number::from(6)]);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Command::parse("1d6[fire] resist=fire immune=cold,fire").unwrap_err(),
+            RollError::ParamError("More than one resistance to \"fire\"".into())
+        );
+        assert!(Command::parse("1d6[fire] vs 1d6 resist=fire").is_err());
+        assert!(Command::parse("(1d6[fire]) ^ 2 resist=fire").is_err());
+        assert!(Command::parse("1d6[]").is_err());
+    }
+}
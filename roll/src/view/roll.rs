@@ -1,4 +1,7 @@
-use dicey::{Command, EvaluatedExpression, Expression, FancyFormat, Rollable, Variable, Verbosity};
+use dicey::{
+    Advantage, AttackTemplate, Bag, Command, Expression, FancyFormat, Rollable, Table, Tables,
+    Variable, Verbosity,
+};
 use dioxus::prelude::*;
 use dioxus_markdown::{CustomComponents, Markdown, ReadWriteBox};
 use subslice_offset::SubsliceOffset;
@@ -6,6 +9,7 @@ use subslice_offset::SubsliceOffset;
 use crate::components::button::*;
 use crate::{LogItem, view::log::LOG};
 
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::vec;
@@ -20,15 +24,50 @@ pub(crate) fn Rollers(lines: Signal<String>) -> Element {
     let lines_slice = &lines_holder[..];
 
     let mut constants = HashMap::default();
+    let mut tables = Tables::default();
+    // Table whose list items are being collected: its name, dice, list items and the index of its roll button.
+    let mut pending_table: Option<(String, Option<String>, String, usize)> = None;
 
     for line in lines_slice.lines() {
         let offset = lines_slice.subslice_offset(line).unwrap();
+
+        if let Some((_, _, items, _)) = &mut pending_table
+            && is_list_item(line)
+        {
+            items.push_str(line);
+            items.push('\n');
+        } else if let Some(table) = pending_table.take() {
+            markdown[table.3] = finish_table(table, &mut tables, &constants);
+        }
+
+        if let Some((name, dice)) = parse_table_header(line) {
+            pending_table = Some((name, dice, String::new(), markdown.len()));
+            // Replaced by the roll button once the table's list is complete.
+            markdown.push(rsx!());
+            continue;
+        }
         let roller = try_roller(line, &constants);
         let (line, negative_offset) = match roller {
             Some(_) => (format!("<R d=\"{line}\"/>"), 6),
             None => (line.to_string(), 0),
         };
 
+        if let Some((name, bag)) = parse_bag_definition(&line) {
+            let message = match bag {
+                Ok(bag) => {
+                    let message =
+                        format!("Bag: ${name} = {}", bag.format(false, Verbosity::Medium));
+                    constants.insert(name, Expression::bag(Rc::new(RefCell::new(bag))));
+                    message
+                }
+                Err(error) => format!("Invalid bag ${name}: {error}"),
+            };
+            markdown.push(rsx!(
+                p { "{message}" }
+            ));
+            continue;
+        }
+
         if let Ok(con) = Variable::parse_with_variables(&line, &constants) {
             let message = format!("Constant: {} = {}", &con.identifier, &con.expression);
             markdown.push(rsx!(
@@ -53,6 +92,15 @@ pub(crate) fn Rollers(lines: Signal<String>) -> Element {
         });
 
         components.register("Roll", |props| {
+            if let Some(system) = props.get("system") {
+                let parameters = dicey::systems::PARAMETER_NAMES
+                    .iter()
+                    .filter_map(|name| props.get(name).map(|value| (name.to_string(), value)))
+                    .collect();
+                return Ok(rsx! {
+                    SystemRoll { system, parameters }
+                });
+            }
             Ok(rsx! {
                 Roll { spec: props.get("d").unwrap_or("Invalid".to_string()) }
             })
@@ -64,6 +112,12 @@ pub(crate) fn Rollers(lines: Signal<String>) -> Element {
             })
         });
 
+        components.register("Table", |props| {
+            Ok(rsx! {
+                TableRoll { name: props.get("name").unwrap_or("Invalid".to_string()) }
+            })
+        });
+
         components.register("A", |props| {
             Ok(rsx! {
                 Attack {
@@ -86,11 +140,15 @@ pub(crate) fn Rollers(lines: Signal<String>) -> Element {
                     children: md,
                     constants: Constants {
                         values: constants.clone().into(),
+                        tables: tables.clone().into(),
                     },
                 }
             }
         ));
     }
+    if let Some(table) = pending_table.take() {
+        markdown[table.3] = finish_table(table, &mut tables, &constants);
+    }
 
     rsx!(
         h2 { "Roll:" }
@@ -102,6 +160,69 @@ pub(crate) fn Rollers(lines: Signal<String>) -> Element {
     )
 }
 
+/// Parse a table header like `$encounter = table` or `$reaction = table 2d6`, returning the name and dice.
+fn parse_table_header(line: &str) -> Option<(String, Option<String>)> {
+    let (name, definition) = line.trim().strip_prefix('$')?.split_once('=')?;
+    let dice = definition.trim().strip_prefix("table")?.trim();
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some((
+        name.to_string(),
+        (!dice.is_empty()).then(|| dice.to_string()),
+    ))
+}
+
+/// Parse a bag definition like `$chaos_bag = bag 3x Elder Sign = 1, 5x -1 = -1`, returning the name and bag.
+fn parse_bag_definition(line: &str) -> Option<(String, dicey::Result<Bag>)> {
+    let (name, definition) = line.trim().strip_prefix('$')?.split_once('=')?;
+    let tokens = definition.trim_start().strip_prefix("bag ")?;
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some((name.to_string(), Bag::parse(tokens)))
+}
+
+fn is_list_item(line: &str) -> bool {
+    let line = line.trim_start();
+    ["- ", "* ", "+ "]
+        .iter()
+        .any(|marker| line.starts_with(marker))
+}
+
+/// Parse a table from its list items and add it to `tables`, returning its roll button.
+fn finish_table(
+    (name, dice, items, _): (String, Option<String>, String, usize),
+    tables: &mut Tables,
+    constants: &HashMap<String, Expression>,
+) -> Element {
+    let table = Table::parse_with_variables(&items, constants).and_then(|table| match &dice {
+        Some(dice) => Ok(table.with_dice(Expression::parse_with_variables(dice, constants)?)),
+        None => Ok(table),
+    });
+    match table {
+        Ok(table) => {
+            tables.insert(name.clone(), table);
+            rsx!(
+                p {
+                    ConstantsProvider {
+                        constants: Constants {
+                            values: constants.clone().into(),
+                            tables: tables.clone().into(),
+                        },
+                        TableRoll { name }
+                    }
+                }
+            )
+        }
+        Err(error) => rsx!(
+            p { title: "{error}", "Invalid table ${name}" }
+        ),
+    }
+}
+
 #[component]
 fn ConstantsProvider(children: Element, constants: Constants) -> Element {
     use_context_provider(|| constants);
@@ -111,11 +232,12 @@ fn ConstantsProvider(children: Element, constants: Constants) -> Element {
 #[derive(Default, Clone)]
 struct Constants {
     values: Rc<HashMap<String, Expression>>,
+    tables: Rc<Tables>,
 }
 
 impl PartialEq for Constants {
     fn eq(&self, other: &Self) -> bool {
-        Rc::ptr_eq(&self.values, &other.values)
+        Rc::ptr_eq(&self.values, &other.values) && Rc::ptr_eq(&self.tables, &other.tables)
         //     if self.values.len() != other.values.len() {
         //         return false;
         //     }
@@ -192,6 +314,61 @@ pub fn Roll(spec: String) -> Element {
     }
 }
 
+/**
+ * A roll button for a game system preset, like `<Roll system="blades" d="3"/>`.
+ */
+#[component]
+pub fn SystemRoll(system: String, parameters: HashMap<String, String>) -> Element {
+    let constants = use_context::<Constants>();
+    match dicey::systems::preset(&system, &parameters, &constants.values) {
+        Ok(preset) => {
+            let text = format!("{system} {}", preset.format(false, Verbosity::Medium));
+            rsx!(
+                Button {
+                    title: preset.format(false, Verbosity::Verbose),
+                    onclick: move |_| {
+                        let message = match preset.roll() {
+                            Ok(outcome) => outcome.format(true, Verbosity::Medium),
+                            Err(err) => format!("{err}"),
+                        };
+                        LOG.write().log.push(LogItem::new(message));
+                    },
+                    "{text}"
+                }
+            )
+        }
+        Err(error) => roll_error(error, &system),
+    }
+}
+
+/**
+ * A roll button for a random table, defined earlier in the document as a markdown list.
+ */
+#[component]
+pub fn TableRoll(name: String) -> Element {
+    let constants = use_context::<Constants>();
+    match constants.tables.roller(&name) {
+        Ok(roller) => {
+            let text = roller.format(false, Verbosity::Medium);
+            let tables = constants.tables.clone();
+            rsx!(
+                Button {
+                    title: text.clone(),
+                    onclick: move |_| {
+                        let message = match tables.roller(&name).and_then(|roller| roller.roll()) {
+                            Ok(result) => result.format(true, Verbosity::Medium),
+                            Err(err) => format!("{err}"),
+                        };
+                        LOG.write().log.push(LogItem::new(message));
+                    },
+                    "{text}"
+                }
+            )
+        }
+        Err(error) => roll_error(error, &name),
+    }
+}
+
 fn validate_roller(spec: &str, constants: Constants) -> Result<Command, Element> {
     Command::parse_with_variables(spec, &constants.values).map_err(|e| roll_error(e, spec))
 }
@@ -210,10 +387,6 @@ fn roll_error(error: dicey::RollError, spec: &str) -> Element {
     )
 }
 
-fn get_dice_string(roll: &dyn EvaluatedExpression) -> String {
-    roll.format_history(true, Verbosity::Medium)
-}
-
 /**
  * Display text, or a roll button depending on if string is a valid roll specification (in dicey dice notation).
  */
@@ -256,91 +429,38 @@ pub fn Attack(modifier: String, damage_dice: String, damage_fixed: String) -> El
         }
     };
 
-    let regular = "1d20";
-    let advantage = "2d20K1";
-    let disadvantage = "2d20k1";
-
-    fn roll(
-        attack: &str,
-        modifier: &Expression,
-        damage_dice: &Expression,
-        damage_fixed: &Expression,
-    ) {
-        let s = match roll_inner(attack, modifier, damage_dice, damage_fixed) {
-            Ok(s) => s,
+    let template = AttackTemplate::new(modifier_roller, damage_dice_roller, damage_fixed_roller);
+
+    fn roll(template: &AttackTemplate, advantage: Advantage) {
+        let template = template.clone().with_advantage(advantage);
+        let s = match template.roll() {
+            Ok(attack) => attack.format(true, Verbosity::Medium),
             Err(e) => format!("{e}"),
         };
 
         LOG.write().log.push(LogItem::new(s));
     }
 
-    fn roll_inner(
-        attack: &str,
-        modifier: &Expression,
-        damage_dice: &Expression,
-        damage_fixed: &Expression,
-    ) -> dicey::Result<String> {
-        let attack_roller = Expression::parse(attack)?;
-        let attack_roll: Box<dyn EvaluatedExpression> = attack_roller.roll()?;
-
-        let modifier = modifier.roll()?.total();
-
-        let attack = attack_roll.total() + modifier;
-
-        let damage_dice_roll = damage_dice.roll()?;
-
-        let damage_fixed = damage_fixed.roll()?.total();
-
-        let attack_string = get_dice_string(&*attack_roll);
-        let damage_string = get_dice_string(&*damage_dice_roll);
-        let damage_total = damage_dice_roll.total() + damage_fixed;
-        let crit = get_crit(&*attack_roll);
-
-        Ok(match crit {
-            Critic::No => format!(
-                "*To Hit*: **{attack}** = {attack_string} + {modifier} *Damage*: **{damage_total}** = {damage_string} + {damage_fixed}"
-            ),
-            Critic::Min => format!("**Crit Miss** {attack_string}"),
-            Critic::Max => {
-                let damage_dice_roll_2 = damage_dice.roll()?;
-                let damage_string_2 = get_dice_string(&*damage_dice_roll_2);
-                let damage_total = damage_total + damage_dice_roll_2.total();
-                format!(
-                    "**Crit** {attack_string} *Damage*: **{damage_total}** = {damage_string} + {damage_string_2} + {damage_fixed}"
-                )
-            }
-        })
-    }
+    let modifier_text = template.modifier.format(false, Verbosity::Medium);
+    let damage_text = template.damage_dice.format(false, Verbosity::Medium);
+    let damage_fixed_text = template.damage_fixed.format(false, Verbosity::Medium);
+    let title = template.format(false, Verbosity::Verbose);
 
-    let modifier_text = modifier_roller.format(false, Verbosity::Medium);
-    let damage_text = damage_dice_roller.format(false, Verbosity::Medium);
-    let damage_fixed_text = damage_fixed_roller.format(false, Verbosity::Medium);
-    let title = format!(
-        "1d20 + {} to hit for {} + {} damage",
-        modifier_roller.format(false, Verbosity::Verbose),
-        damage_dice_roller.format(false, Verbosity::Verbose),
-        damage_fixed_roller.format(false, Verbosity::Verbose)
-    );
-
-    let modifier_roller_2 = modifier_roller.clone();
-    let modifier_roller_3 = modifier_roller.clone();
-    let damage_dice_roller_2 = damage_dice_roller.clone();
-    let damage_dice_roller_3 = damage_dice_roller.clone();
-    let damage_fixed_roller_2 = damage_fixed_roller.clone();
-    let damage_fixed_roller_3 = damage_fixed_roller.clone();
+    let template_2 = template.clone();
+    let template_3 = template.clone();
     rsx!(
         span {
             Button {
-                title: disadvantage,
+                title: Advantage::Disadvantage.notation(),
                 onclick: move |_| {
-                    roll(disadvantage, &modifier_roller, &damage_dice_roller, &damage_fixed_roller);
+                    roll(&template, Advantage::Disadvantage);
                 },
                 b { "-" }
             }
             Button {
                 title,
                 onclick: move |_| {
-                    roll(regular, &modifier_roller_2, &damage_dice_roller_2, &damage_fixed_roller_2);
+                    roll(&template_2, Advantage::Normal);
                 },
                 span {
                     b { "1d20 + {modifier_text}" }
@@ -351,42 +471,12 @@ pub fn Attack(modifier: String, damage_dice: String, damage_fixed: String) -> El
 
             }
             Button {
-                title: advantage,
+                title: Advantage::Advantage.notation(),
                 onclick: move |_| {
-                    roll(
-                        advantage,
-                        &modifier_roller_3,
-                        &damage_dice_roller_3,
-                        &damage_fixed_roller_3,
-                    );
+                    roll(&template_3, Advantage::Advantage);
                 },
                 b { "+" }
             }
         }
     )
 }
-
-fn get_crit(r: &dyn EvaluatedExpression) -> Critic {
-    let crit = r.total() == 20.0;
-    if crit {
-        return Critic::Max;
-    }
-    let crit = r.total() == 1.0;
-
-    if crit {
-        return Critic::Min;
-    }
-
-    Critic::No
-}
-
-/// Used to mark a dice roll if its result is a critic.
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
-pub enum Critic {
-    /// Normal result
-    No,
-    /// Minimum reached
-    Min,
-    /// Maximum reached
-    Max,
-}